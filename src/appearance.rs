@@ -0,0 +1,84 @@
+//! Follows the desktop's dark or light preference for `popup.theme =
+//! "system"`, as the freedesktop appearance portal reports it.
+use iced::Subscription;
+
+use crate::Message;
+
+/// The portal's `color-scheme` value asking for light windows; 0 means no
+/// preference and 1 dark.
+#[cfg(feature = "dbus")]
+const PREFER_LIGHT: u32 = 2;
+
+/// The `color-scheme` number in `value`, which `Read` wraps in a further
+/// variant.
+#[cfg(feature = "dbus")]
+fn scheme(value: &zbus::zvariant::Value) -> Option<u32> {
+    match value {
+        zbus::zvariant::Value::U32(scheme) => Some(*scheme),
+        zbus::zvariant::Value::Value(inner) => scheme(inner),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "dbus")]
+async fn watch(output: &mut iced::futures::channel::mpsc::Sender<Message>) -> zbus::Result<()> {
+    use iced::futures::{SinkExt, StreamExt};
+    use zbus::zvariant::OwnedValue;
+
+    const NAMESPACE: &str = "org.freedesktop.appearance";
+    const KEY: &str = "color-scheme";
+
+    let connection = zbus::Connection::session().await?;
+    let settings = zbus::Proxy::new(
+        &connection,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+    )
+    .await?;
+    // Subscribed first so a change while reading is not lost
+    let mut changes = settings.receive_signal("SettingChanged").await?;
+    let reply = match settings.call_method("ReadOne", &(NAMESPACE, KEY)).await {
+        Ok(reply) => reply,
+        // Portals before version 2 only have the deprecated Read
+        Err(_) => settings.call_method("Read", &(NAMESPACE, KEY)).await?,
+    };
+    let value: OwnedValue = reply.body().deserialize()?;
+    if let Some(scheme) = scheme(&value) {
+        let _ = output.send(Message::DarkMode(scheme != PREFER_LIGHT)).await;
+    }
+    while let Some(signal) = changes.next().await {
+        let Ok((namespace, key, value)) =
+            signal.body().deserialize::<(String, String, OwnedValue)>()
+        else {
+            continue;
+        };
+        if namespace != NAMESPACE || key != KEY {
+            continue;
+        }
+        if let Some(scheme) = scheme(&value) {
+            let _ = output.send(Message::DarkMode(scheme != PREFER_LIGHT)).await;
+        }
+    }
+    Ok(())
+}
+
+struct Appearance;
+
+/// Emits [`Message::DarkMode`] with the desktop's preference, then on every
+/// change. Needs the `dbus` feature; without it the popup stays dark.
+pub fn subscription() -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<Appearance>(),
+        1,
+        |mut output| async move {
+            #[cfg(feature = "dbus")]
+            if let Err(error) = watch(&mut output).await {
+                log::warn!("the desktop's color scheme goes unnoticed: {error}");
+            }
+            #[cfg(not(feature = "dbus"))]
+            let _ = &mut output;
+            std::future::pending().await
+        },
+    )
+}
@@ -0,0 +1,294 @@
+//! Bindings to the system C libchewing, for dictionary and behavior parity with
+//! distribution packages.
+use std::{
+    ffi::{c_char, c_int, c_ushort, c_void, CStr, CString},
+    ops::Range,
+};
+
+use chewing::editor::keyboard::KeyCode;
+
+use super::Backend;
+use crate::{
+    config::{Config, Layout},
+    error::ChewingWlError,
+};
+
+#[repr(C)]
+struct ChewingContext {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct IntervalType {
+    from: c_int,
+    to: c_int,
+}
+
+#[link(name = "chewing")]
+extern "C" {
+    fn chewing_new() -> *mut ChewingContext;
+    fn chewing_delete(ctx: *mut ChewingContext);
+    fn chewing_Reset(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_set_maxChiSymbolLen(ctx: *mut ChewingContext, n: c_int);
+    fn chewing_set_candPerPage(ctx: *mut ChewingContext, n: c_int);
+    fn chewing_set_autoShiftCur(ctx: *mut ChewingContext, mode: c_int);
+    fn chewing_set_KBType(ctx: *mut ChewingContext, kb_type: c_int) -> c_int;
+    fn chewing_handle_Default(ctx: *mut ChewingContext, key: c_int) -> c_int;
+    fn chewing_handle_Space(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_ShiftSpace(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Enter(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Esc(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Backspace(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Del(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Tab(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Left(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Right(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Up(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Down(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_Home(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_End(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_PageUp(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_handle_PageDown(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_buffer_String_static(ctx: *const ChewingContext) -> *const c_char;
+    fn chewing_bopomofo_String_static(ctx: *const ChewingContext) -> *const c_char;
+    fn chewing_bopomofo_Check(ctx: *const ChewingContext) -> c_int;
+    fn chewing_cursor_Current(ctx: *const ChewingContext) -> c_int;
+    fn chewing_interval_Enumerate(ctx: *mut ChewingContext);
+    fn chewing_interval_hasNext(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_interval_Get(ctx: *mut ChewingContext, interval: *mut IntervalType);
+    fn chewing_get_phoneSeq(ctx: *const ChewingContext) -> *mut c_ushort;
+    fn chewing_get_phoneSeqLen(ctx: *const ChewingContext) -> c_int;
+    fn chewing_phone_to_bopomofo(phone: c_ushort, buf: *mut c_char, len: c_ushort) -> c_int;
+    fn chewing_free(ptr: *mut c_void);
+    fn chewing_cand_Enumerate(ctx: *mut ChewingContext);
+    fn chewing_cand_hasNext(ctx: *mut ChewingContext) -> c_int;
+    fn chewing_cand_String_static(ctx: *mut ChewingContext) -> *const c_char;
+    fn chewing_cand_choose_by_index(ctx: *mut ChewingContext, index: c_int) -> c_int;
+    fn chewing_userphrase_lookup(
+        ctx: *mut ChewingContext,
+        phrase: *const c_char,
+        bopomofo: *const c_char,
+    ) -> c_int;
+}
+
+/// Same value the Rust editor uses, so the C library never auto-commits
+/// behind the input method's back.
+const MAX_CHI_SYMBOL_LEN: c_int = 39;
+
+/// libchewing's `KB_*` number for `layout`.
+fn kb_type(layout: Layout) -> c_int {
+    match layout {
+        Layout::Standard => 0,
+        Layout::Hsu => 1,
+        Layout::Et26 => 5,
+        Layout::DachenCp26 => 8,
+    }
+}
+
+pub struct LibChewing {
+    ctx: *mut ChewingContext,
+}
+
+impl LibChewing {
+    /// libchewing assembles its own dictionaries, so the dictionary
+    /// selection in `config` does not apply.
+    pub fn new(config: &Config) -> Result<Self, ChewingWlError> {
+        let ctx = unsafe { chewing_new() };
+        if ctx.is_null() {
+            return Err(ChewingWlError::LibChewing);
+        }
+        unsafe {
+            chewing_set_maxChiSymbolLen(ctx, MAX_CHI_SYMBOL_LEN);
+            chewing_set_candPerPage(ctx, 10);
+            chewing_set_autoShiftCur(ctx, config.preedit.auto_shift_cursor as c_int);
+            chewing_set_KBType(ctx, kb_type(config.keyboard.layout));
+        }
+        Ok(LibChewing { ctx })
+    }
+}
+
+impl Drop for LibChewing {
+    fn drop(&mut self) {
+        unsafe { chewing_delete(self.ctx) }
+    }
+}
+
+fn to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether `c` is a Han character, which has a syllable in the buffer.
+fn has_syllable(c: char) -> bool {
+    matches!(
+        u32::from(c),
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x323AF
+    )
+}
+
+/// The syllables of the characters of `buffer` in `range`, given that only
+/// its Han characters have one of the `phones`. libchewing keeps no
+/// syllable for symbols, so they are skipped; when the characters do not
+/// add up to the syllables, none are told.
+fn phone_range(buffer: &str, phones: usize, range: Range<usize>) -> Option<Range<usize>> {
+    let chars: Vec<char> = buffer.chars().collect();
+    let syllables = |chars: &[char]| chars.iter().filter(|c| has_syllable(**c)).count();
+    if syllables(&chars) != phones {
+        return None;
+    }
+    let start = syllables(chars.get(..range.start)?);
+    Some(start..start + syllables(chars.get(range)?))
+}
+
+impl Backend for LibChewing {
+    fn process_key(&mut self, key: KeyCode) {
+        let ctx = self.ctx;
+        unsafe {
+            match key {
+                KeyCode::Space => chewing_handle_Space(ctx),
+                KeyCode::Enter => chewing_handle_Enter(ctx),
+                KeyCode::Esc => chewing_handle_Esc(ctx),
+                KeyCode::Backspace => chewing_handle_Backspace(ctx),
+                KeyCode::Del => chewing_handle_Del(ctx),
+                KeyCode::Tab => chewing_handle_Tab(ctx),
+                KeyCode::Left => chewing_handle_Left(ctx),
+                KeyCode::Right => chewing_handle_Right(ctx),
+                KeyCode::Up => chewing_handle_Up(ctx),
+                KeyCode::Down => chewing_handle_Down(ctx),
+                KeyCode::Home => chewing_handle_Home(ctx),
+                KeyCode::End => chewing_handle_End(ctx),
+                KeyCode::PageUp => chewing_handle_PageUp(ctx),
+                KeyCode::PageDown => chewing_handle_PageDown(ctx),
+                _ => 0,
+            };
+        }
+    }
+
+    fn process_shift_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Space => unsafe {
+                chewing_handle_ShiftSpace(self.ctx);
+            },
+            _ => self.process_key(key),
+        }
+    }
+
+    fn process_ascii(&mut self, ascii: u8) {
+        unsafe {
+            chewing_handle_Default(self.ctx, ascii as c_int);
+        }
+    }
+
+    fn preedit(&self) -> String {
+        let (buffer, bopomofo) = unsafe {
+            (
+                chewing_buffer_String_static(self.ctx),
+                chewing_bopomofo_String_static(self.ctx),
+            )
+        };
+        format!("{}{}", to_string(buffer), to_string(bopomofo))
+    }
+
+    fn composing_syllable(&self) -> bool {
+        unsafe { chewing_bopomofo_Check(self.ctx) == 1 }
+    }
+
+    fn cursor(&self) -> usize {
+        unsafe { chewing_cursor_Current(self.ctx) as usize }
+    }
+
+    fn segments(&self) -> Vec<Range<usize>> {
+        let mut segments = Vec::new();
+        unsafe {
+            chewing_interval_Enumerate(self.ctx);
+            while chewing_interval_hasNext(self.ctx) == 1 {
+                let mut interval = IntervalType::default();
+                chewing_interval_Get(self.ctx, &mut interval);
+                segments.push(interval.from as usize..interval.to as usize);
+            }
+        }
+        segments
+    }
+
+    fn reading(&self, range: Range<usize>) -> String {
+        let buffer = to_string(unsafe { chewing_buffer_String_static(self.ctx) });
+        let mut reading = Vec::new();
+        unsafe {
+            let len = chewing_get_phoneSeqLen(self.ctx).max(0) as usize;
+            let phones = chewing_get_phoneSeq(self.ctx);
+            if phones.is_null() {
+                return String::new();
+            }
+            for &phone in phone_range(&buffer, len, range)
+                .and_then(|range| std::slice::from_raw_parts(phones, len).get(range))
+                .unwrap_or_default()
+            {
+                let mut buf = [0 as c_char; 16];
+                if chewing_phone_to_bopomofo(phone, buf.as_mut_ptr(), buf.len() as c_ushort) >= 0 {
+                    reading.push(to_string(buf.as_ptr()));
+                }
+            }
+            chewing_free(phones.cast());
+        }
+        reading.join(" ")
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        let mut candidates = Vec::new();
+        unsafe {
+            chewing_cand_Enumerate(self.ctx);
+            while chewing_cand_hasNext(self.ctx) == 1 {
+                candidates.push(to_string(chewing_cand_String_static(self.ctx)));
+            }
+        }
+        candidates
+    }
+
+    fn select(&mut self, index: usize) {
+        unsafe {
+            chewing_cand_choose_by_index(self.ctx, index as c_int);
+        }
+    }
+
+    fn clear(&mut self) {
+        unsafe {
+            chewing_Reset(self.ctx);
+        }
+    }
+
+    fn flush(&mut self) {
+        // libchewing writes learned phrases through on every commit.
+    }
+
+    /// libchewing tells whether the user has the phrase but not how often it
+    /// was picked, so a known phrase counts once.
+    fn user_frequency(&mut self, reading: &str, phrase: &str) -> u32 {
+        let (Ok(phrase), Ok(reading)) = (CString::new(phrase), CString::new(reading)) else {
+            return 0;
+        };
+        let known =
+            unsafe { chewing_userphrase_lookup(self.ctx, phrase.as_ptr(), reading.as_ptr()) };
+        u32::from(known == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_have_no_syllable() {
+        assert_eq!(phone_range("你好", 2, 1..2), Some(1..2));
+        assert_eq!(phone_range("，你好", 2, 1..3), Some(0..2));
+        assert_eq!(phone_range("你，好", 2, 2..3), Some(1..2));
+        assert_eq!(phone_range("你，好", 2, 0..3), Some(0..2));
+        assert_eq!(phone_range("你好", 2, 1..3), None);
+        // A character the syllables cannot account for
+        assert_eq!(phone_range("〇你好", 3, 1..3), None);
+    }
+}
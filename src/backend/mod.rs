@@ -0,0 +1,247 @@
+//! Conversion backends driving the composition buffer.
+use std::ops::Range;
+
+use chewing::editor::keyboard::KeyCode;
+
+use crate::{config::Config, error::ChewingWlError};
+
+#[cfg(feature = "libchewing")]
+mod ffi;
+#[cfg(not(feature = "libchewing"))]
+mod rust;
+
+/// The editor operations the input method needs from a conversion engine.
+pub trait Backend {
+    /// Feeds a named editing key (Enter, Backspace, arrows, ...) to the editor.
+    fn process_key(&mut self, key: KeyCode);
+
+    /// Feeds a named editing key with Shift held to the editor.
+    fn process_shift_key(&mut self, key: KeyCode);
+
+    /// Feeds a printable ascii key to the editor.
+    fn process_ascii(&mut self, ascii: u8);
+
+    /// The converted buffer followed by the syllable being composed.
+    fn preedit(&self) -> String;
+
+    /// Whether a syllable is still being typed.
+    fn composing_syllable(&self) -> bool;
+
+    /// The editor cursor, counted in characters.
+    fn cursor(&self) -> usize;
+
+    /// Character ranges of the phrases the converted buffer is segmented
+    /// into.
+    fn segments(&self) -> Vec<Range<usize>>;
+
+    /// The zhuyin of the characters in `range`, space separated.
+    fn reading(&self, range: Range<usize>) -> String;
+
+    /// All candidates for the phrase at the cursor, once the candidate list is open.
+    fn candidates(&self) -> Vec<String>;
+
+    /// Selects the candidate at `index` of the open candidate list.
+    fn select(&mut self, index: usize);
+
+    /// Drops the composition buffer.
+    fn clear(&mut self);
+
+    /// Persists learned phrases and frequencies.
+    fn flush(&mut self);
+
+    /// Whether the dictionaries have been opened.
+    fn is_loaded(&self) -> bool {
+        true
+    }
+
+    /// How often the user has picked `phrase` for `reading`, zhuyin
+    /// syllables separated by spaces, or 0 if the backend cannot tell.
+    fn user_frequency(&mut self, _reading: &str, _phrase: &str) -> u32 {
+        0
+    }
+
+    /// Why the editor failed to start, the first time it is asked.
+    fn take_error(&mut self) -> Option<ChewingWlError> {
+        None
+    }
+}
+
+/// Creates the backend selected at compile time, deferred until first use if
+/// `dictionaries.load_on_first_use` is set.
+pub fn new(config: &Config) -> Box<dyn Backend> {
+    if config.dictionaries.load_on_first_use {
+        Box::new(Lazy {
+            config: config.clone(),
+            backend: None,
+        })
+    } else {
+        load(config)
+    }
+}
+
+/// A backend that composes nothing and opens no dictionary, for drawing the
+/// popup offscreen.
+pub fn inert() -> Box<dyn Backend> {
+    Box::new(Unavailable(None))
+}
+
+fn load(config: &Config) -> Box<dyn Backend> {
+    #[cfg(feature = "libchewing")]
+    let backend = ffi::LibChewing::new(config);
+    #[cfg(not(feature = "libchewing"))]
+    let backend = rust::Chewing::new(config);
+    match backend {
+        Ok(backend) => Box::new(backend),
+        Err(error) => {
+            log::error!("{error}");
+            Box::new(Unavailable(Some(error)))
+        }
+    }
+}
+
+/// Stands in for an editor that failed to start. It composes nothing, so
+/// the input method passes keys through until the cause is fixed.
+struct Unavailable(Option<ChewingWlError>);
+
+impl Backend for Unavailable {
+    fn process_key(&mut self, _key: KeyCode) {}
+
+    fn process_shift_key(&mut self, _key: KeyCode) {}
+
+    fn process_ascii(&mut self, _ascii: u8) {}
+
+    fn preedit(&self) -> String {
+        String::new()
+    }
+
+    fn composing_syllable(&self) -> bool {
+        false
+    }
+
+    fn cursor(&self) -> usize {
+        0
+    }
+
+    fn segments(&self) -> Vec<Range<usize>> {
+        Vec::new()
+    }
+
+    fn reading(&self, _range: Range<usize>) -> String {
+        String::new()
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn select(&mut self, _index: usize) {}
+
+    fn clear(&mut self) {}
+
+    fn flush(&mut self) {}
+
+    fn take_error(&mut self) -> Option<ChewingWlError> {
+        self.0.take()
+    }
+}
+
+/// A backend that is only created once a key is fed to it. Until then the
+/// buffer reads as empty.
+struct Lazy {
+    config: Config,
+    backend: Option<Box<dyn Backend>>,
+}
+
+impl Lazy {
+    fn loaded(&mut self) -> &mut dyn Backend {
+        let config = &self.config;
+        self.backend
+            .get_or_insert_with(|| {
+                log::info!("loading dictionaries on first use");
+                load(config)
+            })
+            .as_mut()
+    }
+}
+
+impl Backend for Lazy {
+    fn process_key(&mut self, key: KeyCode) {
+        self.loaded().process_key(key);
+    }
+
+    fn process_shift_key(&mut self, key: KeyCode) {
+        self.loaded().process_shift_key(key);
+    }
+
+    fn process_ascii(&mut self, ascii: u8) {
+        self.loaded().process_ascii(ascii);
+    }
+
+    fn preedit(&self) -> String {
+        self.backend
+            .as_ref()
+            .map_or_else(String::new, |backend| backend.preedit())
+    }
+
+    fn composing_syllable(&self) -> bool {
+        self.backend
+            .as_ref()
+            .is_some_and(|backend| backend.composing_syllable())
+    }
+
+    fn cursor(&self) -> usize {
+        self.backend.as_ref().map_or(0, |backend| backend.cursor())
+    }
+
+    fn segments(&self) -> Vec<Range<usize>> {
+        self.backend
+            .as_ref()
+            .map_or_else(Vec::new, |backend| backend.segments())
+    }
+
+    fn reading(&self, range: Range<usize>) -> String {
+        self.backend
+            .as_ref()
+            .map_or_else(String::new, |backend| backend.reading(range))
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        self.backend
+            .as_ref()
+            .map_or_else(Vec::new, |backend| backend.candidates())
+    }
+
+    fn select(&mut self, index: usize) {
+        if let Some(backend) = &mut self.backend {
+            backend.select(index);
+        }
+    }
+
+    fn clear(&mut self) {
+        if let Some(backend) = &mut self.backend {
+            backend.clear();
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(backend) = &mut self.backend {
+            backend.flush();
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    fn user_frequency(&mut self, reading: &str, phrase: &str) -> u32 {
+        self.backend
+            .as_mut()
+            .map_or(0, |backend| backend.user_frequency(reading, phrase))
+    }
+
+    fn take_error(&mut self) -> Option<ChewingWlError> {
+        self.backend
+            .as_mut()
+            .and_then(|backend| backend.take_error())
+    }
+}
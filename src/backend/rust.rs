@@ -0,0 +1,156 @@
+use std::{ops::Range, str::FromStr};
+
+#[cfg(feature = "pinyin")]
+use chewing::editor::zhuyin_layout::Pinyin;
+use chewing::{
+    conversion::ChewingEngine,
+    dictionary::{Layered, SystemDictionaryLoader},
+    editor::{
+        keyboard::{AnyKeyboardLayout, KeyCode, KeyboardLayout, Modifiers as Mods, Qwerty},
+        BasicEditor, Editor, EditorOptions, LaxUserFreqEstimate,
+    },
+    zhuyin::Syllable,
+};
+
+use super::Backend;
+use crate::{
+    config::Config,
+    dictionary,
+    error::ChewingWlError,
+    layout,
+    user_data::{self, UserFile},
+};
+
+pub struct Chewing {
+    editor: Editor,
+    keyboard: AnyKeyboardLayout,
+    user_file: UserFile,
+}
+
+impl Chewing {
+    pub fn new(config: &Config) -> Result<Self, ChewingWlError> {
+        let sys_loader = SystemDictionaryLoader::new();
+        let dictionaries = dictionary::load_system(&config.dictionaries);
+        if dictionaries.is_empty() {
+            return Err(ChewingWlError::NoSystemDictionary);
+        }
+        let (user_dictionary, user_file) =
+            user_data::load_user_dictionary(config.user_dictionary.backups)
+                .map_err(ChewingWlError::UserDictionary)?;
+        let abbrev = sys_loader
+            .load_abbrev()
+            .map_err(|error| ChewingWlError::SystemData {
+                table: "abbreviation table",
+                reason: error.to_string(),
+            })?;
+        let estimate = LaxUserFreqEstimate::max_from(user_dictionary.as_ref());
+        let dict = Layered::new(dictionaries, user_dictionary);
+        let conversion_engine = Box::new(ChewingEngine::new());
+        let sym_sel =
+            sys_loader
+                .load_symbol_selector()
+                .map_err(|error| ChewingWlError::SystemData {
+                    table: "symbol table",
+                    reason: error.to_string(),
+                })?;
+        // The zhuyin layout is the syllable editor's business, the keyboard
+        // only maps the keys as labeled on a US keyboard
+        let keyboard = AnyKeyboardLayout::Qwerty(Qwerty);
+        let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
+        #[cfg(feature = "pinyin")]
+        editor.set_syllable_editor(Box::new(Pinyin::hanyu()));
+        #[cfg(not(feature = "pinyin"))]
+        editor.set_syllable_editor(layout::syllable_editor(config.keyboard.layout));
+        editor.set_editor_options(EditorOptions {
+            auto_shift_cursor: config.preedit.auto_shift_cursor,
+            ..Default::default()
+        });
+        Ok(Chewing {
+            editor,
+            keyboard,
+            user_file,
+        })
+    }
+}
+
+impl Backend for Chewing {
+    fn process_key(&mut self, key: KeyCode) {
+        self.editor.process_keyevent(self.keyboard.map(key));
+    }
+
+    fn process_shift_key(&mut self, key: KeyCode) {
+        self.editor
+            .process_keyevent(self.keyboard.map_with_mod(key, Mods::shift()));
+    }
+
+    fn process_ascii(&mut self, ascii: u8) {
+        self.editor.process_keyevent(self.keyboard.map_ascii(ascii));
+    }
+
+    fn preedit(&self) -> String {
+        format!(
+            "{}{}",
+            self.editor.display(),
+            self.editor.syllable_buffer_display()
+        )
+    }
+
+    fn composing_syllable(&self) -> bool {
+        !self.editor.syllable_buffer_display().is_empty()
+    }
+
+    fn cursor(&self) -> usize {
+        self.editor.cursor()
+    }
+
+    fn segments(&self) -> Vec<Range<usize>> {
+        self.editor
+            .intervals()
+            .map(|interval| interval.start..interval.end)
+            .collect()
+    }
+
+    fn reading(&self, range: Range<usize>) -> String {
+        let Some(symbols) = self.editor.symbols().get(range) else {
+            return String::new();
+        };
+        symbols
+            .iter()
+            .filter_map(|symbol| symbol.to_syllable())
+            .map(|syllable| syllable.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        self.editor.all_candidates().unwrap_or_default()
+    }
+
+    fn select(&mut self, index: usize) {
+        let _ = self.editor.select(index);
+    }
+
+    fn clear(&mut self) {
+        self.editor.clear();
+    }
+
+    fn flush(&mut self) {
+        self.user_file.save(self.editor.user_dict());
+    }
+
+    fn user_frequency(&mut self, reading: &str, phrase: &str) -> u32 {
+        let Some(syllables) = reading
+            .split(' ')
+            .map(|syllable| Syllable::from_str(syllable).ok())
+            .collect::<Option<Vec<Syllable>>>()
+        else {
+            return 0;
+        };
+        self.editor
+            .user_dict()
+            .lookup_all_phrases(&syllables[..])
+            .iter()
+            .find(|found| found.as_str() == phrase)
+            .map_or(0, |found| found.freq())
+    }
+}
@@ -0,0 +1,36 @@
+//! Sends a command to the running chewingwl and prints the reply, e.g.
+//! `chewingwlctl candidates`, `chewingwlctl watch-candidates`,
+//! `chewingwlctl dump-state redact`, `chewingwlctl low-latency on` or
+//! `chewingwlctl log-level debug 5`.
+use std::{
+    env,
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+fn main() -> ExitCode {
+    let command = env::args().skip(1).collect::<Vec<_>>().join(" ");
+    if command.is_empty() {
+        eprintln!("usage: chewingwlctl <command> [args...]");
+        return ExitCode::from(2);
+    }
+    let Some(path) =
+        env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("chewingwl.sock"))
+    else {
+        eprintln!("XDG_RUNTIME_DIR is not set");
+        return ExitCode::FAILURE;
+    };
+    let result = UnixStream::connect(&path).and_then(|mut stream| {
+        writeln!(stream, "{command}")?;
+        io::copy(&mut stream, &mut io::stdout())
+    });
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("cannot talk to chewingwl at {}: {error}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
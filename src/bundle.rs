@@ -0,0 +1,249 @@
+//! `chewingwl export-debug-bundle`: gathers what a bug report needs into one
+//! tarball, with credentials removed from the config.
+use std::{
+    env,
+    fs::{self, DirBuilder},
+    io,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config, ipc};
+
+/// Sync settings that may hold credentials or private hosts.
+const REDACTED: [&str; 3] = ["url", "username", "password"];
+
+const ENVIRONMENT: [&str; 7] = [
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_DESKTOP",
+    "XDG_SESSION_TYPE",
+    "DESKTOP_SESSION",
+    "WAYLAND_DISPLAY",
+    "LANG",
+    "CHEWING_PATH",
+];
+
+struct Options {
+    output: Option<PathBuf>,
+    log: Option<PathBuf>,
+    traces: bool,
+}
+
+fn parse(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        output: None,
+        log: None,
+        traces: false,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => options.output = Some(args.next()?.into()),
+            "--log" => options.log = Some(args.next()?.into()),
+            "--traces" => options.traces = true,
+            _ => return None,
+        }
+    }
+    Some(options)
+}
+
+pub fn export(args: &[String]) -> i32 {
+    let Some(options) = parse(args) else {
+        eprintln!(
+            "usage: chewingwl export-debug-bundle [--output <file>] [--log <file>] [--traces]"
+        );
+        return 2;
+    };
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let name = format!("chewingwl-debug-{stamp}");
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{name}.tar.gz")));
+    // Only this user may read it, and one left behind is never reused
+    let staging = env::var_os("XDG_RUNTIME_DIR")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join(&name);
+    if let Err(error) = DirBuilder::new().mode(0o700).create(&staging) {
+        eprintln!(
+            "cannot stage debug bundle in {}: {error}",
+            staging.display()
+        );
+        return 1;
+    }
+    let result = collect(&staging, &options).and_then(|()| archive(&staging, &output));
+    let _ = fs::remove_dir_all(&staging);
+    match result {
+        Ok(()) => {
+            println!("wrote {}", output.display());
+            println!("check it for anything private before attaching it to an issue");
+            0
+        }
+        Err(error) => {
+            eprintln!("cannot export debug bundle: {error}");
+            1
+        }
+    }
+}
+
+fn collect(dir: &Path, options: &Options) -> io::Result<()> {
+    fs::write(dir.join("version.txt"), version())?;
+    fs::write(dir.join("environment.txt"), environment())?;
+    if let Some(config) = sanitized_config()? {
+        fs::write(dir.join("config.toml"), config)?;
+    }
+    match &options.log {
+        Some(log) => {
+            fs::copy(log, dir.join("chewingwl.log"))?;
+        }
+        None => {
+            if let Some(journal) = journal() {
+                fs::write(dir.join("journal.log"), journal)?;
+            }
+        }
+    }
+    if options.traces {
+        let traces = ipc::request("transitions")
+            .unwrap_or_else(|error| format!("chewingwl is not reachable: {error}\n"));
+        fs::write(dir.join("transitions.txt"), traces)?;
+    }
+    Ok(())
+}
+
+fn archive(dir: &Path, output: &Path) -> io::Result<()> {
+    let (Some(parent), Some(name)) = (dir.parent(), dir.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "bad staging path",
+        ));
+    };
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(output)
+        .arg("-C")
+        .arg(parent)
+        .arg(name)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("tar exited with {status}")))
+    }
+}
+
+fn version() -> String {
+    let features: Vec<&str> = [
+        ("libchewing", cfg!(feature = "libchewing")),
+        ("pinyin", cfg!(feature = "pinyin")),
+        ("sync", cfg!(feature = "sync")),
+        ("dbus", cfg!(feature = "dbus")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+    format!(
+        "chewingwl {}\nfeatures: {}\nkernel: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        features.join(", "),
+        kernel.trim(),
+    )
+}
+
+/// The session variables that name the compositor and locale.
+fn environment() -> String {
+    ENVIRONMENT
+        .iter()
+        .map(|name| {
+            let value = env::var(name).unwrap_or_else(|_| "<unset>".to_string());
+            format!("{name}={value}\n")
+        })
+        .collect()
+}
+
+fn sanitized_config() -> io::Result<Option<String>> {
+    let Some(path) = config::path() else {
+        return Ok(None);
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    sanitize(&text).map(Some)
+}
+
+/// `text` with the sync settings of the config and of every profile
+/// redacted.
+fn sanitize(text: &str) -> io::Result<String> {
+    // A file that does not parse cannot be sanitized, so it is left out
+    let Ok(mut table) = text.parse::<toml::Table>() else {
+        return Ok("# config.toml does not parse, left out\n".to_string());
+    };
+    redact_sync(&mut table);
+    if let Some(toml::Value::Table(profiles)) = table.get_mut("profiles") {
+        for (_, profile) in profiles.iter_mut() {
+            if let toml::Value::Table(profile) = profile {
+                redact_sync(profile);
+            }
+        }
+    }
+    toml::to_string(&table).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn redact_sync(table: &mut toml::Table) {
+    if let Some(toml::Value::Table(sync)) = table.get_mut("sync") {
+        for key in REDACTED {
+            if let Some(value) = sync.get_mut(key) {
+                *value = toml::Value::String("<redacted>".to_string());
+            }
+        }
+    }
+}
+
+/// Recent messages when running as a systemd user service.
+fn journal() -> Option<Vec<u8>> {
+    let output = Command::new("journalctl")
+        .args([
+            "--user",
+            "--identifier",
+            "chewingwl",
+            "--lines",
+            "5000",
+            "--no-pager",
+        ])
+        .output()
+        .ok()?;
+    (output.status.success() && !output.stdout.is_empty()).then_some(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_settings_are_redacted_in_profiles_too() {
+        let text = r#"
+            [sync]
+            url = "https://dav.example.com/chewing.dat"
+            on_startup = true
+
+            [profiles.work.sync]
+            username = "someone"
+            password = "hunter2"
+
+            [profiles.work.popup]
+            font = "Noto Sans CJK TC"
+        "#;
+        let sanitized = sanitize(text).unwrap();
+        for secret in ["dav.example.com", "someone", "hunter2"] {
+            assert!(!sanitized.contains(secret), "{secret} left in {sanitized}");
+        }
+        assert!(sanitized.contains("on_startup = true"));
+        assert!(sanitized.contains("Noto Sans CJK TC"));
+    }
+}
@@ -0,0 +1,55 @@
+//! Browsing the CJK blocks of Unicode in the candidate popup, for
+//! characters no reading leads to. Opened with `` ` `` from the popup, the
+//! key that also opens the symbol table.
+
+/// The blocks offered, each up to its last assigned code point.
+const BLOCKS: [(&str, u32, u32); 18] = [
+    ("CJK Unified Ideographs", 0x4E00, 0x9FFF),
+    ("CJK Extension A", 0x3400, 0x4DBF),
+    ("CJK Extension B", 0x20000, 0x2A6DF),
+    ("CJK Extension C", 0x2A700, 0x2B739),
+    ("CJK Extension D", 0x2B740, 0x2B81D),
+    ("CJK Extension E", 0x2B820, 0x2CEA1),
+    ("CJK Extension F", 0x2CEB0, 0x2EBE0),
+    ("CJK Extension G", 0x30000, 0x3134A),
+    ("CJK Extension H", 0x31350, 0x323AF),
+    ("CJK Extension I", 0x2EBF0, 0x2EE5D),
+    ("CJK Compatibility Ideographs", 0xF900, 0xFAD9),
+    ("CJK Compatibility Supplement", 0x2F800, 0x2FA1D),
+    ("CJK Radicals Supplement", 0x2E80, 0x2EF3),
+    ("Kangxi Radicals", 0x2F00, 0x2FD5),
+    ("CJK Strokes", 0x31C0, 0x31E3),
+    ("CJK Symbols and Punctuation", 0x3000, 0x303F),
+    ("Bopomofo", 0x3105, 0x312F),
+    ("Bopomofo Extended", 0x31A0, 0x31BF),
+];
+
+#[derive(Debug, Default)]
+pub struct CharMap {
+    block: usize,
+}
+
+impl CharMap {
+    /// The characters of the current block, as candidates.
+    pub fn characters(&self) -> Vec<String> {
+        let (_, first, last) = BLOCKS[self.block];
+        (first..=last)
+            .filter_map(char::from_u32)
+            .map(String::from)
+            .collect()
+    }
+
+    pub fn next_block(&mut self) {
+        self.block = (self.block + 1) % BLOCKS.len();
+    }
+
+    pub fn previous_block(&mut self) {
+        self.block = (self.block + BLOCKS.len() - 1) % BLOCKS.len();
+    }
+
+    /// The block name and range, shown above the grid.
+    pub fn title(&self) -> String {
+        let (name, first, last) = BLOCKS[self.block];
+        format!("{name}  U+{first:04X}–U+{last:04X}  (Tab for the next block)")
+    }
+}
@@ -0,0 +1,169 @@
+//! Subcommands that run instead of the input method.
+use std::path::Path;
+
+use crate::{
+    bundle,
+    config::{self, Config},
+    dictionary, hotkey, ipc, keymap, lookup_bench, screenshot, soak, user_data,
+};
+
+/// Runs the subcommand named in `args`, returning the exit code, or `None`
+/// if the input method should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.get(1).map(String::as_str) {
+        Some("bench-lookup") => Some(lookup_bench::run()),
+        Some("dict-info") => Some(dict_info()),
+        Some("doctor" | "--doctor") => Some(doctor()),
+        Some("export-debug-bundle") => Some(bundle::export(&args[2..])),
+        Some("keymap") => Some(keymap::run(&args[2..])),
+        Some("restore-phrases") => Some(restore_phrases(&args[2..])),
+        Some("--screenshot-theme") => Some(screenshot::run(&args[2..])),
+        // Not advertised, it hammers the dictionaries for as long as asked
+        Some("--soak") => Some(soak::run(&args[2..])),
+        #[cfg(feature = "sync")]
+        Some("sync") => Some(sync()),
+        _ => None,
+    }
+}
+
+fn dict_info() -> i32 {
+    println!("search path:");
+    for dir in dictionary::search_path() {
+        println!("  {}", dir.display());
+    }
+    let discovered = dictionary::discover();
+    let selected = dictionary::select(&Config::load().dictionaries);
+    let mut code = 0;
+    for report in dictionary::inspect() {
+        let path = report
+            .path
+            .as_ref()
+            .map_or("<missing>".to_string(), |p| p.display().to_string());
+        let disabled = report
+            .path
+            .as_ref()
+            .is_some_and(|p| discovered.contains(p) && !selected.contains(p));
+        println!("{path}{}", if disabled { " (disabled)" } else { "" });
+        match report.warning {
+            Some(warning) => {
+                code = 1;
+                println!("  warning: {warning}");
+            }
+            None => {
+                println!("  name:    {}", report.name);
+                println!("  version: {}", report.version);
+                println!("  phrases: {}", report.phrases);
+            }
+        }
+    }
+    code
+}
+
+fn doctor() -> i32 {
+    if Path::new(config::SYSTEM_PATH).exists() {
+        println!("system config: {}", config::SYSTEM_PATH);
+    }
+    if let Some(path) = config::path() {
+        println!("config: {}", path.display());
+    }
+    let conflicts = hotkey::conflicts(&Config::load());
+    if conflicts.is_empty() {
+        println!("no key binding conflicts found");
+        return 0;
+    }
+    for conflict in &conflicts {
+        println!("warning: {conflict}");
+    }
+    1
+}
+
+fn restore_phrases(args: &[String]) -> i32 {
+    let path = match user_data::user_dictionary_path() {
+        Ok(path) => path,
+        Err(error) => {
+            eprintln!("cannot locate user dictionary: {error}");
+            return 1;
+        }
+    };
+    match args {
+        [flag, backup] if flag == "--from" => {
+            let keep = Config::load().user_dictionary.backups;
+            restore_from(&path, Path::new(backup), keep, ipc::is_running())
+        }
+        [] => {
+            println!("backups of {}:", path.display());
+            for backup in user_data::backups(&path) {
+                println!("  {}", backup.display());
+            }
+            println!("restore one with: chewingwl restore-phrases --from <backup>");
+            0
+        }
+        _ => {
+            eprintln!("usage: chewingwl restore-phrases [--from <backup>]");
+            2
+        }
+    }
+}
+
+fn restore_from(path: &Path, backup: &Path, keep: usize, running: bool) -> i32 {
+    if running {
+        // Its next flush would write the old phrases back
+        eprintln!("chewingwl is running; quit it before restoring");
+        return 1;
+    }
+    match user_data::restore(path, backup, keep) {
+        Ok(()) => {
+            println!("restored {} from {}", path.display(), backup.display());
+            0
+        }
+        Err(error) => {
+            eprintln!("restore failed: {error}");
+            1
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+fn sync() -> i32 {
+    let config = Config::load();
+    if config.sync.url.is_empty() {
+        eprintln!("set [sync] url in config.toml to enable sync");
+        return 2;
+    }
+    if ipc::is_running() {
+        // Its next flush would write over the merged phrases
+        eprintln!("chewingwl is running; quit it before syncing");
+        return 1;
+    }
+    match crate::sync::run(&config.sync, config.user_dictionary.backups) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("sync failed: {error}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn restoring_is_refused_while_running() {
+        let dir = env::temp_dir().join(format!("chewingwl-restore-cli-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chewing.dat");
+        let backup = dir.join("chewing.dat.1");
+        fs::write(&path, "current").unwrap();
+        fs::write(&backup, "backup").unwrap();
+        assert_eq!(restore_from(&path, &backup, 3, true), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "current");
+        assert_eq!(user_data::backups(&path), [backup.clone()]);
+        assert_eq!(restore_from(&path, &backup, 3, false), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "backup");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,111 @@
+//! Longer phrases starting with the syllables composed so far, offered while
+//! typing and under the candidates so a long phrase takes fewer keys.
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use chewing::{
+    dictionary::{Dictionary, Trie},
+    zhuyin::Syllable,
+};
+
+use crate::{config, dictionary};
+
+/// Completions offered at most, one per Ctrl+digit.
+pub const MAX_COMPLETIONS: usize = 9;
+/// Syllables typed before completions are looked for; a single one starts
+/// too many phrases to be useful.
+const MIN_SYLLABLES: usize = 2;
+
+/// A phrase of the system dictionaries, by reading.
+struct Entry {
+    syllables: Vec<Syllable>,
+    phrase: String,
+    frequency: u32,
+}
+
+/// The phrases of the selected system dictionaries by first syllable, read
+/// once as the dictionaries have no prefix lookup. Only phrases longer than
+/// [`MIN_SYLLABLES`] can complete anything, so the rest, most of each
+/// dictionary, are left out, as are phrases listed by more than one.
+pub struct Completions {
+    by_first: HashMap<Syllable, Vec<Entry>>,
+}
+
+impl Completions {
+    pub fn open(config: &config::Dictionaries) -> Self {
+        let mut by_first: HashMap<Syllable, Vec<Entry>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for trie in dictionary::select(config)
+            .iter()
+            .filter_map(|path| Trie::open(path).ok())
+        {
+            for (syllables, phrase) in trie.entries() {
+                if syllables.len() <= MIN_SYLLABLES {
+                    continue;
+                }
+                let Some(&first) = syllables.first() else {
+                    continue;
+                };
+                if !seen.insert((syllables.clone(), phrase.as_str().to_string())) {
+                    continue;
+                }
+                by_first.entry(first).or_default().push(Entry {
+                    frequency: phrase.freq(),
+                    phrase: phrase.as_str().to_string(),
+                    syllables,
+                });
+            }
+        }
+        Completions { by_first }
+    }
+
+    /// Phrases longer than `reading`, zhuyin syllables separated by spaces,
+    /// that start with it. Ranked by `user_frequency` where the user has
+    /// picked them before, then by their frequency in the dictionaries.
+    pub fn find(
+        &self,
+        reading: &str,
+        mut user_frequency: impl FnMut(&str, &str) -> u32,
+    ) -> Vec<String> {
+        let Some(syllables) = reading
+            .split(' ')
+            .map(|syllable| Syllable::from_str(syllable).ok())
+            .collect::<Option<Vec<Syllable>>>()
+            .filter(|syllables| syllables.len() >= MIN_SYLLABLES)
+        else {
+            return Vec::new();
+        };
+        let Some(entries) = syllables.first().and_then(|first| self.by_first.get(first)) else {
+            return Vec::new();
+        };
+        let mut found: Vec<(u32, u32, &str)> = entries
+            .iter()
+            .filter(|entry| {
+                entry.syllables.len() > syllables.len() && entry.syllables.starts_with(&syllables)
+            })
+            .map(|entry| {
+                let reading = entry
+                    .syllables
+                    .iter()
+                    .map(Syllable::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let user = user_frequency(&reading, &entry.phrase);
+                (user, entry.frequency, entry.phrase.as_str())
+            })
+            .collect();
+        found.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)));
+        let mut phrases: Vec<String> = Vec::new();
+        for (_, _, phrase) in found {
+            if !phrases.iter().any(|known| known == phrase) {
+                phrases.push(phrase.to_string());
+            }
+            if phrases.len() == MAX_COMPLETIONS {
+                break;
+            }
+        }
+        phrases
+    }
+}
@@ -0,0 +1,128 @@
+//! Keeps dead key and Compose sequences in one piece.
+//!
+//! The client composes dead keys and Compose sequences from the forwarded
+//! key events, so once a sequence starts every key of it has to reach the
+//! client, and none of it may be fed to the editor.
+
+/// `XKB_KEY_Multi_key`, the Compose key.
+const MULTI_KEY: u32 = 0xff20;
+/// The range of `XKB_KEY_dead_*` keysyms.
+const DEAD_KEYS: std::ops::RangeInclusive<u32> = 0xfe50..=0xfe93;
+/// `XKB_KEY_Shift_L` through `XKB_KEY_Hyper_R`, which may be held while a
+/// sequence is typed without advancing it.
+const MODIFIERS: std::ops::RangeInclusive<u32> = 0xffe1..=0xffee;
+/// `XKB_KEY_ISO_Level3_Shift`, AltGr on most layouts.
+const LEVEL3_SHIFT: u32 = 0xfe03;
+
+#[derive(Debug, Default)]
+pub struct ComposeState {
+    /// Keys still expected to complete the current sequence.
+    remaining: usize,
+}
+
+impl ComposeState {
+    /// Feeds a pressed key, returning whether it belongs to a dead key or
+    /// Compose sequence and must be forwarded untouched.
+    pub fn forward(&mut self, keysym: u32) -> bool {
+        if DEAD_KEYS.contains(&keysym) {
+            // A dead key following a dead key combines with it
+            self.remaining = self.remaining.max(1);
+            true
+        } else if keysym == MULTI_KEY {
+            self.remaining = 2;
+            true
+        } else if self.remaining > 0 {
+            if !MODIFIERS.contains(&keysym) && keysym != LEVEL3_SHIFT {
+                self.remaining -= 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.remaining = 0;
+    }
+}
+
+/// The ascii key the editor understands, if the key produced exactly one
+/// ascii character. Anything else, like `é` typed directly on a French
+/// layout, has no zhuyin meaning and must not be truncated to a byte.
+pub fn ascii(utf8: Option<&str>) -> Option<u8> {
+    let mut chars = utf8?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Some(c as u8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEAD_CIRCUMFLEX: u32 = 0xfe52;
+    const DEAD_ACUTE: u32 = 0xfe51;
+    const DEAD_DIAERESIS: u32 = 0xfe57;
+    const SHIFT_L: u32 = 0xffe1;
+    const E: u32 = 0x65;
+    const A: u32 = 0x61;
+    const O: u32 = 0x6f;
+    const QUOTEDBL: u32 = 0x22;
+
+    #[test]
+    fn french_dead_circumflex_forwards_both_keys() {
+        let mut state = ComposeState::default();
+        assert!(state.forward(DEAD_CIRCUMFLEX));
+        assert!(state.forward(E));
+        assert!(!state.forward(E));
+    }
+
+    #[test]
+    fn shift_inside_sequence_does_not_complete_it() {
+        let mut state = ComposeState::default();
+        assert!(state.forward(DEAD_DIAERESIS));
+        assert!(state.forward(SHIFT_L));
+        assert!(state.forward(A));
+        assert!(!state.forward(A));
+    }
+
+    #[test]
+    fn german_dead_acute_twice_then_letter() {
+        let mut state = ComposeState::default();
+        assert!(state.forward(DEAD_ACUTE));
+        assert!(state.forward(DEAD_ACUTE));
+        assert!(state.forward(E));
+        assert!(!state.forward(E));
+    }
+
+    #[test]
+    fn compose_sequence_forwards_three_keys() {
+        let mut state = ComposeState::default();
+        assert!(state.forward(MULTI_KEY));
+        assert!(state.forward(O));
+        assert!(state.forward(QUOTEDBL));
+        assert!(!state.forward(O));
+    }
+
+    #[test]
+    fn reset_drops_pending_sequence() {
+        let mut state = ComposeState::default();
+        assert!(state.forward(MULTI_KEY));
+        state.reset();
+        assert!(!state.forward(O));
+    }
+
+    #[test]
+    fn only_single_ascii_characters_reach_the_editor() {
+        assert_eq!(ascii(Some("j")), Some(b'j'));
+        assert_eq!(ascii(Some(";")), Some(b';'));
+        // German ü and ß, French é and ç typed directly
+        assert_eq!(ascii(Some("ü")), None);
+        assert_eq!(ascii(Some("ß")), None);
+        assert_eq!(ascii(Some("é")), None);
+        assert_eq!(ascii(Some("ç")), None);
+        assert_eq!(ascii(Some("")), None);
+        assert_eq!(ascii(None), None);
+    }
+}
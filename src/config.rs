@@ -0,0 +1,1145 @@
+//! User configuration read from `$XDG_CONFIG_HOME/chewingwl/config.toml`.
+use std::{
+    collections::HashMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::{
+    hotkey::{Action, Hotkey},
+    tone::ToneMarks,
+};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub keyboard: Keyboard,
+    pub keys: Keys,
+    pub preedit: Preedit,
+    pub popup: Popup,
+    pub dictionaries: Dictionaries,
+    pub user_dictionary: UserDictionary,
+    pub sync: Sync,
+    pub session: Session,
+    pub history: History,
+    pub stats: Stats,
+    pub hotkeys: Hotkeys,
+    pub gestures: Gestures,
+    pub osd: Osd,
+    pub surfaces: Surfaces,
+    pub speech: Speech,
+    pub debug: Diagnostics,
+    /// Whose standard the character forms typed follow.
+    pub region: Region,
+    /// The profile in use, from `--profile`, `chewingwlctl profile` or this
+    /// key in the config.
+    pub profile: Option<String>,
+    /// Named sets of settings layered over the rest of the config, e.g.
+    /// `[profiles.teaching.popup]`; each may set any of the sections above.
+    pub profiles: HashMap<String, toml::Table>,
+    /// The profile asked for by name rather than by the `profile` key,
+    /// which a reload keeps.
+    #[serde(skip)]
+    pub chosen_profile: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Keyboard {
+    pub layout: Layout,
+    /// How the keys typed map onto the US keyboard the zhuyin layouts are
+    /// drawn on, for system keymaps other than QWERTY. Keymaps for other
+    /// scripts than Latin, as Cyrillic or Greek, go by position regardless.
+    /// The compositor's keymap is not read, positions are those of a US
+    /// keyboard, and keys typed with AltGr reach zhuyin as typed.
+    pub remap: Remap,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Remap {
+    /// The system keymap is QWERTY, or zhuyin goes by the labels of the
+    /// keys.
+    #[default]
+    None,
+    /// The system keymap is US Dvorak.
+    Dvorak,
+    /// The system keymap is Colemak.
+    Colemak,
+    /// Zhuyin goes by where the key is, whatever the system keymap.
+    Physical,
+}
+
+/// Which keys type which zhuyin symbols.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// The Daqian layout printed on most Taiwanese keyboards.
+    #[default]
+    Standard,
+    /// Hsu's layout, zhuyin on the letter keys only, grouped by sound, with
+    /// the tones on Space, D, F, J and S.
+    Hsu,
+    /// The 26-key version of the Eten layout; keys holding two symbols type
+    /// the second when pressed again.
+    Et26,
+    /// The standard layout folded onto the letter keys, pressing a key again
+    /// for the second symbol on it.
+    DachenCp26,
+}
+
+impl Layout {
+    pub const ALL: &'static [Layout] = &[
+        Layout::Standard,
+        Layout::Hsu,
+        Layout::Et26,
+        Layout::DachenCp26,
+    ];
+
+    /// The name used in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Standard => "standard",
+            Layout::Hsu => "hsu",
+            Layout::Et26 => "et26",
+            Layout::DachenCp26 => "dachen-cp26",
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Standard => f.write_str("Standard (Daqian)"),
+            Layout::Hsu => f.write_str("Hsu"),
+            Layout::Et26 => f.write_str("ET26"),
+            Layout::DachenCp26 => f.write_str("Dachen CP26"),
+        }
+    }
+}
+
+/// How keys behave in Chinese mode.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Keys {
+    pub enter_when_empty: EnterPolicy,
+    pub stray_in_popup: StrayKeyPolicy,
+    pub escape_in_popup: EscapePolicy,
+    /// How Down opens the candidate popup while composing.
+    pub down: DownPolicy,
+    /// What a Ctrl or Alt shortcut does to the composition before it is
+    /// sent to the application.
+    pub shortcut_while_composing: ShortcutPolicy,
+    /// Keysyms forwarded untouched in every state, e.g. `[0xff13]` for
+    /// Pause, on top of the function, media and Print keys which always are.
+    pub always_forward: Vec<u32>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownPolicy {
+    /// Every press opens the popup.
+    #[default]
+    Press,
+    /// Holding Down opens the popup, a short press moves to the end of the
+    /// composition.
+    Hold,
+    /// Pressing Down again within `gestures.double_tap_ms` of releasing it
+    /// opens the popup, the first press moves to the end of the composition.
+    DoubleTap,
+}
+
+/// What happens to the composition when a Ctrl or Alt shortcut is pressed
+/// while composing. The shortcut itself always reaches the application.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShortcutPolicy {
+    /// Commit the composition first, so e.g. Ctrl+S saves it.
+    #[default]
+    Commit,
+    /// Drop the composition.
+    Cancel,
+}
+
+/// What a printable key that picks no candidate does while the popup is open.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrayKeyPolicy {
+    /// Drop the key.
+    #[default]
+    Ignore,
+    /// Close the popup and type the key into the composition.
+    Cancel,
+    /// Keep the key and type it once a candidate is picked.
+    Buffer,
+}
+
+/// What Escape does while the popup is open. `hotkeys.cancel_selection`
+/// always keeps the composition.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EscapePolicy {
+    /// Close the popup, keeping the composition.
+    #[default]
+    Close,
+    /// Close the popup and drop the composition, as Escape does while
+    /// composing.
+    Clear,
+}
+
+/// What Enter does in Chinese mode while nothing is being composed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnterPolicy {
+    /// Send Enter to the application.
+    #[default]
+    Forward,
+    /// Send Enter and drop any half-typed syllable or Compose sequence, so
+    /// the next key starts from a clean Chinese state.
+    Reset,
+    /// Type an ideographic space before the newline, as some document
+    /// conventions expect.
+    FullWidthSpace,
+}
+
+/// The composition shown inline in the application.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Preedit {
+    /// Shown between phrases after Tab changes the segmentation, until the
+    /// next key; empty disables it.
+    pub segment_separator: String,
+    /// Move the cursor past a phrase once one of its candidates is picked.
+    pub auto_shift_cursor: bool,
+    /// How tones are marked wherever zhuyin shows, in the preedit, the popup
+    /// and the history panel.
+    pub tone_marks: ToneMarks,
+    /// Updates sent to the application per second at most while typing
+    /// fast, later keys being folded into the next update; 0 for no limit.
+    pub max_rate: u32,
+    /// `max_rate` for particular clients by class or app ID, e.g.
+    /// `slack = 30`, for those that fall behind.
+    pub client_max_rate: HashMap<String, u32>,
+}
+
+impl Default for Preedit {
+    fn default() -> Self {
+        Preedit {
+            segment_separator: "·".to_string(),
+            auto_shift_cursor: false,
+            tone_marks: ToneMarks::default(),
+            max_rate: 0,
+            client_max_rate: HashMap::new(),
+        }
+    }
+}
+
+/// The most candidates a popup page holds.
+const MAX_CANDIDATES: usize = 30;
+
+/// The candidate popup.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Popup {
+    /// Candidates per popup column, at most thirty. Past the selection keys
+    /// the labels take two keys, a prefix from `label_prefixes` and then a
+    /// selection key.
+    pub candidates: usize,
+    /// Candidate pages shown side by side, from 2 to 6; `compact` shows
+    /// only one.
+    pub columns: usize,
+    /// Keys picking the candidates of the highlighted page, in order, e.g.
+    /// `"asdfghjkl;"`. A page lists no more candidates than there are labels.
+    pub selection_keys: String,
+    /// Keys typed before a selection key to pick the candidates past the
+    /// first row of keys, e.g. `"abc"` labels the 11th candidate `a1`. They
+    /// only pick while the page is longer than the selection keys.
+    pub label_prefixes: String,
+    pub theme: PopupTheme,
+    pub font: PopupFont,
+    pub style: PopupStyle,
+    /// Colors of the highlighted candidate.
+    pub highlight: Highlight,
+    /// Opening the popup walks through the phrases from the start of the
+    /// buffer, each pick moving on to the next phrase until the end.
+    pub batch_selection: bool,
+    /// Up on the first candidate goes to the last one of the page, Down on
+    /// the last one goes on to the next page or back to the first.
+    pub wrap_selection: bool,
+    /// Marks the column of the highlighted page when several are shown.
+    pub page_indicator: PageIndicator,
+    /// Opacity of the text in the other columns, from 0 to 1.
+    pub inactive_page_opacity: f32,
+    /// Show only the highlighted page instead of up to four columns, for
+    /// small screens and tiling setups.
+    pub compact: bool,
+    /// Lay the highlighted page out in a single row under the text, like
+    /// the candidate bar of other input methods, rather than in columns.
+    /// Left and Right then move the highlight, Up and Down turn the page.
+    pub bar: bool,
+    /// How the candidates are written; set it in a profile, e.g.
+    /// `[profiles.vertical.popup]`, to switch for vertical documents.
+    pub writing: Writing,
+    /// Hovering a candidate for half a second shows its reading and the
+    /// dictionary it comes from.
+    pub tooltips: bool,
+    /// Show the zhuyin reading next to each candidate, for learners and
+    /// for telling homophones apart.
+    pub annotations: bool,
+    /// Show the composition above the candidates. Clicking one of its
+    /// characters lists the candidates there instead, to fix an earlier
+    /// phrase without walking the cursor back.
+    pub composition: bool,
+    /// Show the candidates as dots while the screen is shared, so a
+    /// meeting does not see what is being typed. Needs `pw-cli`.
+    pub hide_while_sharing: bool,
+    /// Mirror the candidate list to `chewingwlctl watch-candidates` and
+    /// the `org.chewingwl.Candidates` D-Bus signal, for screen readers and
+    /// overlays. Any program of the user can then read what is typed.
+    pub export_candidates: bool,
+    /// List up to nine longer phrases starting with the composition, picked
+    /// with Ctrl and their number: under the candidates, and alone in the
+    /// popup while typing. Reads the phrases of three syllables or more of
+    /// the system dictionaries into memory on first use.
+    pub completions: bool,
+    /// How variant forms of the same character, e.g. 臺 and 台, are listed.
+    pub variants: VariantDisplay,
+    /// Multiplier applied on top of the compositor's scale. On an
+    /// output scaled by e.g. 1.5 the compositor scales the popup itself.
+    pub scale: f64,
+    /// Further multipliers by output name, e.g. `"DP-1" = 1.5`.
+    pub output_scale: HashMap<String, f64>,
+}
+
+impl Default for Popup {
+    fn default() -> Self {
+        Popup {
+            candidates: 10,
+            columns: 4,
+            selection_keys: "1234567890".into(),
+            label_prefixes: "abc".into(),
+            theme: PopupTheme::default(),
+            font: PopupFont::default(),
+            style: PopupStyle::default(),
+            highlight: Highlight::default(),
+            batch_selection: false,
+            wrap_selection: false,
+            page_indicator: PageIndicator::default(),
+            inactive_page_opacity: 0.5,
+            compact: false,
+            bar: false,
+            writing: Writing::default(),
+            tooltips: false,
+            annotations: false,
+            composition: false,
+            hide_while_sharing: false,
+            export_candidates: false,
+            completions: false,
+            variants: VariantDisplay::default(),
+            scale: 1.0,
+            output_scale: HashMap::new(),
+        }
+    }
+}
+
+impl Popup {
+    /// The selection keys, each once, falling back to the digits if none
+    /// are set.
+    pub fn selection_keys(&self) -> Vec<char> {
+        let mut keys: Vec<char> = Vec::new();
+        for key in self
+            .selection_keys
+            .chars()
+            .filter(|key| !key.is_whitespace())
+        {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        if keys.is_empty() {
+            keys = "1234567890".chars().collect();
+        }
+        keys
+    }
+
+    /// The labels of a page, `candidates` of them as far as the keys go:
+    /// the selection keys, then each label prefix followed by each of them.
+    pub fn selection_labels(&self) -> Vec<String> {
+        let keys = self.selection_keys();
+        let mut prefixes: Vec<char> = Vec::new();
+        for prefix in self
+            .label_prefixes
+            .chars()
+            .filter(|key| !key.is_whitespace())
+        {
+            if !keys.contains(&prefix) && !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        let prefixed = prefixes
+            .iter()
+            .flat_map(|prefix| keys.iter().map(move |key| format!("{prefix}{key}")));
+        keys.iter()
+            .map(char::to_string)
+            .chain(prefixed)
+            .take(self.candidates.clamp(1, MAX_CANDIDATES))
+            .collect()
+    }
+}
+
+/// The text of the candidates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PopupFont {
+    /// An installed font family, e.g. `"Noto Sans CJK TC"`, instead of the
+    /// renderer's default.
+    pub family: Option<String>,
+    /// A font file loaded at startup, for one that is not installed; name
+    /// its family in `family` to use it.
+    pub file: Option<PathBuf>,
+    /// Size of the candidates, in logical pixels.
+    pub size: u16,
+    /// Size of the selection key labels.
+    pub label_size: u16,
+}
+
+impl Default for PopupFont {
+    fn default() -> Self {
+        PopupFont {
+            family: None,
+            file: None,
+            size: 50,
+            label_size: 50,
+        }
+    }
+}
+
+/// The frame of the popup and how each candidate is drawn: highlighted,
+/// under the pointer or neither. Colors left out follow `theme`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PopupStyle {
+    pub background: Option<HexColor>,
+    /// Color of the text.
+    pub text: Option<HexColor>,
+    /// Color of the border, the text color if not set.
+    pub border: Option<HexColor>,
+    /// Width of the border, 0 for none.
+    pub border_width: f32,
+    pub border_radius: f32,
+    /// Opacity of the background, from 0 to 1, for compositors that blend
+    /// the popup with what is underneath. What shows through is not
+    /// blurred, the blur protocols needing the popup's surface, which the
+    /// toolkit keeps to itself.
+    pub background_opacity: f32,
+    /// Background of the highlighted candidate, taking over from
+    /// `highlight`.
+    pub selected: Option<HexColor>,
+    /// Text of the highlighted candidate.
+    pub selected_text: Option<HexColor>,
+    /// Background of the candidate under the pointer while the keys have
+    /// moved the highlight elsewhere.
+    pub hovered: Option<HexColor>,
+    /// Text of the candidate under the pointer.
+    pub hovered_text: Option<HexColor>,
+    /// Color of the selection key labels, the text color if not set. The
+    /// labels of the highlighted or hovered candidate take its text color
+    /// when that is set.
+    pub label: Option<HexColor>,
+    /// Color of the frame around the highlighted page, the text color if
+    /// not set.
+    pub indicator: Option<HexColor>,
+    /// Width of that frame.
+    pub indicator_width: f32,
+    /// Weight of the candidates.
+    pub weight: FontWeight,
+    /// Weight of the highlighted candidate, `weight` if not set.
+    pub selected_weight: Option<FontWeight>,
+    /// Weight of the candidate under the pointer, `weight` if not set.
+    pub hovered_weight: Option<FontWeight>,
+    /// Weight of the selection key labels.
+    pub label_weight: FontWeight,
+}
+
+impl Default for PopupStyle {
+    fn default() -> Self {
+        PopupStyle {
+            background: None,
+            text: None,
+            border: None,
+            border_width: 3.0,
+            border_radius: 10.0,
+            background_opacity: 1.0,
+            selected: None,
+            selected_text: None,
+            hovered: None,
+            hovered_text: None,
+            label: None,
+            indicator: None,
+            indicator_width: 2.0,
+            weight: FontWeight::default(),
+            selected_weight: None,
+            hovered_weight: None,
+            label_weight: FontWeight::default(),
+        }
+    }
+}
+
+/// A color written `"#rrggbb"` or `"#rrggbbaa"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HexColor(pub [u8; 4]);
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let digits = s.strip_prefix('#').unwrap_or(&s);
+        if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+            return Err(format!(
+                "`{s}` is not a color like `#1e1e2e` or `#1e1e2ecc`"
+            ));
+        }
+        let mut rgba = [0xff; 4];
+        for (channel, pair) in rgba.iter_mut().zip(digits.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).unwrap_or_default();
+            *channel = u8::from_str_radix(pair, 16)
+                .map_err(|_| format!("`{s}` is not a color like `#1e1e2e` or `#1e1e2ecc`"))?;
+        }
+        Ok(HexColor(rgba))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Writing {
+    #[default]
+    Horizontal,
+    /// Each candidate top to bottom under its label, a page's candidates
+    /// right to left and the pages under each other, as in vertical text.
+    /// Left and Right move the highlight, Up and Down turn the page.
+    Vertical,
+}
+
+/// How bold text is drawn, where the font has the weight.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FontWeight {
+    Thin,
+    Light,
+    #[default]
+    Normal,
+    Medium,
+    Semibold,
+    Bold,
+    Black,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PageIndicator {
+    /// Only the number labels of the active column are shown.
+    Labels,
+    /// The active column is also framed.
+    #[default]
+    Border,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupTheme {
+    Dark,
+    Light,
+    /// Dark or light as the desktop prefers, through the appearance
+    /// portal; dark where it cannot tell. Needs the `dbus` feature.
+    #[default]
+    System,
+}
+
+impl PopupTheme {
+    pub const ALL: &'static [PopupTheme] =
+        &[PopupTheme::System, PopupTheme::Dark, PopupTheme::Light];
+
+    /// The name used in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            PopupTheme::Dark => "dark",
+            PopupTheme::Light => "light",
+            PopupTheme::System => "system",
+        }
+    }
+}
+
+impl fmt::Display for PopupTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopupTheme::Dark => f.write_str("Dark"),
+            PopupTheme::Light => f.write_str("Light"),
+            PopupTheme::System => f.write_str("Follow the desktop"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Region {
+    #[default]
+    Taiwan,
+    /// Candidates, preedit and commits use the Hong Kong form of the
+    /// characters whose forms differ, e.g. 裏 for 裡 and 説 for 說.
+    HongKong,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VariantDisplay {
+    /// In the editor's order.
+    #[default]
+    Off,
+    /// Right after the first spelling the editor lists.
+    Group,
+    /// Folded under the first spelling, marked with their number; Tab
+    /// unfolds the highlighted one.
+    Collapse,
+}
+
+/// Palettes for the highlighted candidate. The alternatives to the default
+/// blue stand out by lightness rather than hue, so they hold up under the
+/// common color-vision deficiencies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Highlight {
+    /// White text on dark blue.
+    #[default]
+    Blue,
+    /// Black text on the orange of the Okabe-Ito palette, told apart from
+    /// both themes' backgrounds with any kind of color blindness.
+    Orange,
+    /// The theme's background and text colors swapped, the most contrast
+    /// there is.
+    Inverted,
+}
+
+/// Which discovered system dictionaries are layered into the editor.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Dictionaries {
+    /// File names of dictionaries to skip, e.g. `["extension-idioms.dat"]`.
+    pub disabled: Vec<String>,
+    /// When set, exactly these dictionaries are loaded, in this order.
+    pub order: Option<Vec<String>>,
+    /// Open the dictionaries on the first key typed in Chinese mode rather
+    /// than at startup, keeping memory low until then.
+    pub load_on_first_use: bool,
+}
+
+/// Safekeeping of the learned user dictionary.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserDictionary {
+    /// How many rotated backups to keep; zero disables backups.
+    pub backups: usize,
+    pub flush: FlushPolicy,
+}
+
+impl Default for UserDictionary {
+    fn default() -> Self {
+        UserDictionary {
+            backups: 3,
+            flush: FlushPolicy::default(),
+        }
+    }
+}
+
+/// When learned frequencies are persisted. Any trigger that fires flushes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FlushPolicy {
+    /// Flush after this many commits; zero disables the trigger.
+    pub every_commits: usize,
+    /// Flush once no commit happened for this many seconds; zero disables
+    /// the trigger.
+    pub idle_seconds: u64,
+    /// Flush when the text input loses focus.
+    pub on_deactivate: bool,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy {
+            every_commits: 20,
+            idle_seconds: 30,
+            on_deactivate: true,
+        }
+    }
+}
+
+/// WebDAV sync of the user dictionary, used when built with the `sync`
+/// feature.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Sync {
+    /// URL of the dictionary file on the WebDAV server; empty disables sync.
+    pub url: String,
+    pub username: Option<String>,
+    /// Falls back to `CHEWINGWL_SYNC_PASSWORD` when unset.
+    pub password: Option<String>,
+    /// Sync before the input method opens the dictionary, keys reaching
+    /// the application meanwhile. Skipped while another instance runs.
+    pub on_startup: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Save an unfinished composition on shutdown and offer it on the next
+    /// activation.
+    pub restore: bool,
+    /// Keep the screen from blanking or locking while something is
+    /// composed, through the compositor's idle-inhibit protocol.
+    pub inhibit_idle: bool,
+    /// Let the screen blank anyway once no key was typed for this many
+    /// seconds, so a composition left behind does not keep it on for good.
+    pub inhibit_idle_timeout: u64,
+    /// What happens when logind locks the session. Needs the `dbus`
+    /// feature.
+    pub on_lock: LockPolicy,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            restore: true,
+            inhibit_idle: false,
+            inhibit_idle_timeout: 300,
+            on_lock: LockPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockPolicy {
+    /// Discard the composition without learning from it and switch to
+    /// English until the session is unlocked.
+    #[default]
+    English,
+    /// Leave the composition and mode as they are.
+    Nothing,
+}
+
+/// A record of rare candidates picked from the popup, with their readings,
+/// for finding a character again. Kept on disk only while enabled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct History {
+    pub enabled: bool,
+    /// Picks at this position in the candidate list or further down are
+    /// recorded; 0 records every pick.
+    pub min_rank: usize,
+    pub max_entries: usize,
+    /// Entries older than this many days are dropped.
+    pub keep_days: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            enabled: false,
+            min_rank: 10,
+            max_entries: 100,
+            keep_days: 30,
+        }
+    }
+}
+
+/// Key combinations handled by the input method itself.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Hotkeys {
+    /// Switches between Chinese and English; tapping Shift does when unset.
+    pub toggle_mode: Option<Hotkey>,
+    /// Toggles forwarding every key untouched, for games and remote desktops.
+    pub low_latency: Option<Hotkey>,
+    /// Keeps the candidate popup open, each pick moving on to the next
+    /// phrase. Only active while the popup is shown.
+    pub pin_popup: Option<Hotkey>,
+    /// Closes the candidate popup and keeps the composition as it is,
+    /// whatever `keys.escape_in_popup` makes Escape do.
+    pub cancel_selection: Option<Hotkey>,
+    /// Saves the composition, or the last commit if nothing is being
+    /// composed, as a named snippet. Unbound by default, e.g.
+    /// `add_snippet = "Ctrl+Alt+S"`; the last commit only counts until
+    /// the text field loses focus or the mode is switched.
+    pub add_snippet: Option<Hotkey>,
+    /// Commits whatever is composed and resets to Chinese mode with nothing
+    /// pending, a way out when the input method gets stuck.
+    pub force_reset: Option<Hotkey>,
+    /// Shows the stroke count, radical and code point of the highlighted
+    /// candidate under the popup. Only active while the popup is shown, and
+    /// taken before function keys are forwarded.
+    pub candidate_info: Option<Hotkey>,
+    /// Replaces the candidates with a map of the CJK blocks of Unicode, for
+    /// characters no reading leads to. Only active while the popup is shown.
+    pub character_map: Option<Hotkey>,
+    /// Starts typing a stroke sequence, 1 to 5 for 一丨丿丶乛, or with Tab a
+    /// four-corner code, to find a character by its shape. Needs the data
+    /// files described in the `shape` module. Unbound by default, e.g.
+    /// `shape_lookup = "Ctrl+Alt+B"`; it is taken while nothing is
+    /// composed in Chinese mode.
+    pub shape_lookup: Option<Hotkey>,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys {
+            toggle_mode: None,
+            low_latency: "Ctrl+Alt+F12".parse().ok(),
+            pin_popup: "Ctrl+P".parse().ok(),
+            cancel_selection: None,
+            add_snippet: None,
+            force_reset: "Ctrl+Alt+Escape".parse().ok(),
+            candidate_info: "F1".parse().ok(),
+            character_map: "`".parse().ok(),
+            shape_lookup: None,
+        }
+    }
+}
+
+/// Double taps of single keys, e.g. tapping Control twice with no other key
+/// in between. The action happens as the second tap is released.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Gestures {
+    /// The most time from the first release to the second, in
+    /// milliseconds.
+    pub double_tap_ms: u64,
+    pub double_tap: Vec<DoubleTap>,
+}
+
+impl Default for Gestures {
+    fn default() -> Self {
+        Gestures {
+            double_tap_ms: 300,
+            double_tap: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DoubleTap {
+    pub key: Hotkey,
+    pub action: Action,
+}
+
+/// Transient on-screen notices.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Osd {
+    /// How long a notice stays up, in milliseconds.
+    pub timeout_ms: u64,
+    /// Explain why typing Chinese does nothing in windows without text input
+    /// support.
+    pub unsupported_client: bool,
+    /// Only log notices while the notification daemon is set to do not
+    /// disturb.
+    pub respect_dnd: bool,
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Osd {
+            timeout_ms: 2000,
+            unsupported_client: true,
+            respect_dnd: true,
+        }
+    }
+}
+
+/// Saying each picked candidate aloud, for learners.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Speech {
+    pub enabled: bool,
+    /// A directory of recordings named after what they say, e.g. `你好.ogg`,
+    /// preferred over the synthesizer.
+    pub recordings: Option<PathBuf>,
+    /// Plays a recording, given its path as the last argument.
+    pub player: Vec<String>,
+    /// Speaks text given as its last argument.
+    pub synthesizer: Vec<String>,
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Speech {
+            enabled: false,
+            recordings: None,
+            player: vec!["pw-play".into()],
+            synthesizer: vec!["espeak-ng".into(), "-v".into(), "cmn".into()],
+        }
+    }
+}
+
+/// Where the surfaces besides the candidate popup are stacked, for
+/// compositors that put them under fullscreen windows or the like. The
+/// popup itself is placed by the compositor.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Surfaces {
+    /// Notices, on the overlay layer without the keyboard by default.
+    pub osd: Surface,
+    /// The first-launch setup, on the top layer without the keyboard.
+    pub setup: Surface,
+    /// The commit history panel, on the top layer without the keyboard.
+    pub history: Surface,
+    /// The snippet name dialog, on the overlay layer taking the keyboard.
+    pub snippet: Surface,
+    /// The debug state overlay, on the overlay layer without the keyboard.
+    pub debug: Surface,
+    /// The startup error window, on the overlay layer without the keyboard.
+    pub error: Surface,
+}
+
+/// Overrides of a surface's own layer and keyboard interactivity.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Surface {
+    pub layer: Option<SurfaceLayer>,
+    pub keyboard: Option<Interactivity>,
+}
+
+/// The layer-shell layers, bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SurfaceLayer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+/// Whether a surface takes the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interactivity {
+    None,
+    /// Takes the keyboard for as long as it is shown.
+    Exclusive,
+    /// Takes the keyboard when clicked, as a window would.
+    OnDemand,
+}
+
+/// Typing statistics, kept on this machine only.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Time each zhuyin key from the one before, for `chewingwlctl
+    /// key-timing` to tell which keys of the layout are slow to reach.
+    pub key_timing: bool,
+}
+
+/// Diagnostics that cost a little performance.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Diagnostics {
+    /// Log key handling latency per state every hundred keys.
+    pub latency: bool,
+    /// Show the state and its recent transitions in a screen corner, also
+    /// enabled by `--debug-state-overlay`.
+    pub state_overlay: bool,
+    /// Have the debug log of keys show the text they type. Otherwise only
+    /// keys that type nothing, like Enter or F5, are logged by name, so
+    /// passwords typed in English mode stay out of the log. Also enabled by
+    /// `--debug-log-key-text`.
+    pub log_key_text: bool,
+    /// `--debug-log-key-text` was given, which a reload keeps.
+    #[serde(skip)]
+    pub log_key_text_flag: bool,
+}
+
+/// The directory holding the config file and other chewingwl state.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("chewingwl"))
+}
+
+/// Defaults for every user on the machine, set by the distribution or an
+/// administrator.
+pub const SYSTEM_PATH: &str = "/etc/chewingwl/config.toml";
+
+pub fn path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// The directory for state that should survive restarts but is not
+/// configuration.
+pub fn state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .map(|dir| dir.join("chewingwl"))
+}
+
+impl Config {
+    /// Loads the system config with the user config layered over it, key by
+    /// key, falling back to defaults for whatever neither sets. A missing or
+    /// malformed file is skipped, as is a key of the wrong type.
+    pub fn load() -> Config {
+        Config::load_profile(None).unwrap_or_else(|error| {
+            warn!("ignoring the config: {error}");
+            Config::default()
+        })
+    }
+
+    /// Loads the config with the profile `name` layered over it, or the one
+    /// named by its `profile` key when `name` is `None`. Only a missing
+    /// profile asked for by name is an error; the config may name one that
+    /// has since been removed.
+    pub fn load_profile(name: Option<&str>) -> Result<Config, String> {
+        let mut merged = toml::Table::new();
+        for path in [Some(PathBuf::from(SYSTEM_PATH)), path()]
+            .into_iter()
+            .flatten()
+        {
+            match read(&path) {
+                Ok(table) => merge_valid(&mut merged, table, &path.display().to_string()),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => warn!("ignoring {}: {error}", path.display()),
+            }
+        }
+        let named = name.is_some();
+        let name = name
+            .or_else(|| merged.get("profile").and_then(toml::Value::as_str))
+            .map(str::to_string);
+        if let Some(name) = name {
+            match merged
+                .get("profiles")
+                .and_then(|profiles| profiles.get(&name))
+            {
+                Some(toml::Value::Table(profile)) => {
+                    let profile = profile.clone();
+                    merge_valid(&mut merged, profile, &format!("profile `{name}`"));
+                    merged.insert("profile".into(), toml::Value::String(name));
+                }
+                _ if named => return Err(format!("no profile named `{name}`")),
+                _ => {
+                    warn!("no profile named `{name}`, using the config without one");
+                    merged.remove("profile");
+                }
+            }
+        }
+        let mut config: Config = toml::Value::Table(merged)
+            .try_into()
+            .map_err(|error: toml::de::Error| error.to_string())?;
+        if named {
+            config.chosen_profile = config.profile.clone();
+        }
+        Ok(config)
+    }
+
+    /// Like [`Config::load_profile`], but a malformed file is an error
+    /// rather than skipped, so a half-written file does not reset
+    /// everything it sets.
+    pub fn reload(name: Option<&str>) -> Result<Config, String> {
+        for path in [Some(PathBuf::from(SYSTEM_PATH)), path()]
+            .into_iter()
+            .flatten()
+        {
+            if let Err(error) = read(&path) {
+                if error.kind() != io::ErrorKind::NotFound {
+                    return Err(format!("{}: {error}", path.display()));
+                }
+            }
+        }
+        Config::load_profile(name)
+    }
+}
+
+fn read(path: &Path) -> io::Result<toml::Table> {
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Like [`merge`], but leaves out any key whose value does not fit the
+/// config, saying so, rather than have one mistyped key in `source` discard
+/// every other file and key.
+fn merge_valid(base: &mut toml::Table, over: toml::Table, source: &str) {
+    for (key, value) in over {
+        merge_checked(base, &[key], value, source);
+    }
+}
+
+/// Sets `value` at the key path `at` if the config still loads, or else
+/// tries the keys of `value` one by one when it is a table.
+fn merge_checked(base: &mut toml::Table, at: &[String], value: toml::Value, source: &str) {
+    let mut candidate = base.clone();
+    if let toml::Value::Table(over) = nest(at, value.clone()) {
+        merge(&mut candidate, over);
+    }
+    match toml::Value::Table(candidate.clone()).try_into::<Config>() {
+        Ok(_) => *base = candidate,
+        Err(error) => match value {
+            toml::Value::Table(table) if !table.is_empty() => {
+                for (key, value) in table {
+                    merge_checked(base, &[at, &[key]].concat(), value, source);
+                }
+            }
+            _ => warn!("{source}: ignoring `{}`: {error}", at.join(".")),
+        },
+    }
+}
+
+/// `value` within tables for each key of `at`, outermost first.
+fn nest(at: &[String], value: toml::Value) -> toml::Value {
+    at.iter().rev().fold(value, |value, key| {
+        toml::Value::Table(toml::Table::from_iter([(key.clone(), value)]))
+    })
+}
+
+/// Sets every key of `over` in `base`, descending into tables present in
+/// both so a user can change one key of a section without repeating the
+/// rest of it.
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> toml::Table {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn a_mistyped_key_leaves_the_rest_of_the_config() {
+        let mut merged = table("region = \"hong-kong\"\n[popup]\nbatch_selection = true\n");
+        let user = table("[popup]\nbatch_selection = \"yes\"\n[preedit]\nmax_rate = 30\n");
+        merge_valid(&mut merged, user, "config.toml");
+        let config: Config = toml::Value::Table(merged).try_into().unwrap();
+        assert_eq!(config.region, Region::HongKong);
+        assert!(config.popup.batch_selection);
+        assert_eq!(config.preedit.max_rate, 30);
+    }
+
+    #[test]
+    fn keys_are_nested_outermost_first() {
+        let at = ["popup".to_string(), "batch_selection".to_string()];
+        let nested = nest(&at, toml::Value::Boolean(true));
+        let expected = table("[popup]\nbatch_selection = true\n");
+        assert_eq!(nested, toml::Value::Table(expected));
+    }
+}
@@ -0,0 +1,97 @@
+//! Notices edits of the config files so they apply without a restart.
+use std::{
+    any::TypeId,
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+};
+
+use iced::{
+    futures::{SinkExt, StreamExt},
+    subscription, Subscription,
+};
+use inotify::{Inotify, WatchMask, Watches};
+use log::warn;
+
+use crate::{config, Message};
+
+struct ConfigWatch;
+
+/// Emits [`Message::ConfigChanged`] whenever a config file is written or
+/// replaced. Directories are watched rather than the files, as most editors
+/// save by renaming a new file over the old one. A config directory that
+/// does not exist yet is watched for in its parent.
+pub fn subscription() -> Subscription<Message> {
+    subscription::channel(TypeId::of::<ConfigWatch>(), 4, |mut output| async move {
+        let dirs: Vec<PathBuf> = [config::path(), Some(PathBuf::from(config::SYSTEM_PATH))]
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.parent().map(PathBuf::from))
+            .collect();
+        let mut missing = Vec::new();
+        let events = Inotify::init().and_then(|inotify| {
+            let watches = inotify.watches();
+            for dir in &dirs {
+                if !watch(watches.clone(), dir)? {
+                    missing.push(dir.clone());
+                }
+            }
+            inotify.into_event_stream([0; 1024])
+        });
+        let mut events = match events {
+            Ok(events) => events,
+            Err(error) => {
+                warn!("config changes apply on restart only: {error}");
+                return std::future::pending().await;
+            }
+        };
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) if event.name.as_deref() == Some(OsStr::new("config.toml")) => {
+                    let _ = output.send(Message::ConfigChanged).await;
+                }
+                // A missing config directory was created, perhaps with a
+                // config already in it
+                Ok(event)
+                    if missing
+                        .iter()
+                        .any(|dir| event.name.as_deref() == dir.file_name()) =>
+                {
+                    let mut created = false;
+                    for dir in std::mem::take(&mut missing) {
+                        match watch(events.watches(), &dir) {
+                            Ok(true) => created = true,
+                            Ok(false) => missing.push(dir),
+                            Err(error) => warn!("cannot watch {}: {error}", dir.display()),
+                        }
+                    }
+                    if created {
+                        let _ = output.send(Message::ConfigChanged).await;
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    warn!("stopped watching the config: {error}");
+                    break;
+                }
+            }
+        }
+        std::future::pending().await
+    })
+}
+
+/// Watches `dir` for config files, or its parent for `dir` to be created,
+/// telling whether `dir` itself is watched.
+fn watch(mut watches: Watches, dir: &Path) -> io::Result<bool> {
+    if dir.is_dir() {
+        watches.add(
+            dir,
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+        )?;
+        return Ok(true);
+    }
+    if let Some(parent) = dir.parent().filter(|parent| parent.is_dir()) {
+        watches.add(parent, WatchMask::CREATE | WatchMask::MOVED_TO)?;
+    }
+    Ok(false)
+}
@@ -0,0 +1,200 @@
+//! Discovery and inspection of the dictionaries the Rust backend loads.
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chewing::{
+    dictionary::{Dictionary, Trie, UserDictionaryLoader},
+    zhuyin::Syllable,
+};
+use log::warn;
+
+use crate::config;
+
+const DEFAULT_SEARCH_PATH: [&str; 2] = ["/usr/share/libchewing", "/usr/local/share/libchewing"];
+const WORD_DICTIONARY: &str = "word.dat";
+const PHRASE_DICTIONARY: &str = "tsi.dat";
+
+/// Directories searched for system dictionaries, honoring `CHEWING_PATH`
+/// the same way libchewing does.
+pub fn search_path() -> Vec<PathBuf> {
+    if let Ok(path) = env::var("CHEWING_PATH") {
+        return env::split_paths(&path).collect();
+    }
+    let mut dirs = Vec::new();
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    {
+        dirs.push(data_home.join("libchewing"));
+    }
+    dirs.extend(DEFAULT_SEARCH_PATH.iter().map(PathBuf::from));
+    dirs
+}
+
+/// All system dictionary files on the search path. The first file with a
+/// given name wins, the word and phrase dictionaries always come first and
+/// extension dictionaries follow in name order.
+pub fn discover() -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    for dir in search_path() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "dat"))
+            .collect();
+        files.sort();
+        for file in files {
+            if !found.iter().any(|f| f.file_name() == file.file_name()) {
+                found.push(file);
+            }
+        }
+    }
+    found.sort_by_key(|path| match path.file_name().and_then(|name| name.to_str()) {
+        Some(WORD_DICTIONARY) => 0,
+        Some(PHRASE_DICTIONARY) => 1,
+        _ => 2,
+    });
+    found
+}
+
+/// The discovered dictionaries narrowed down by the user's config: the
+/// explicit `order` if set, otherwise everything not `disabled`.
+pub fn select(config: &config::Dictionaries) -> Vec<PathBuf> {
+    let discovered = discover();
+    let name_of = |path: &PathBuf| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    match &config.order {
+        Some(order) => order
+            .iter()
+            .filter_map(|name| {
+                let path = discovered.iter().find(|path| &name_of(path) == name);
+                if path.is_none() {
+                    warn!("dictionary {name} listed in config was not found");
+                }
+                path.cloned()
+            })
+            .collect(),
+        None => discovered
+            .into_iter()
+            .filter(|path| !config.disabled.contains(&name_of(path)))
+            .collect(),
+    }
+}
+
+/// Opens the selected system dictionaries, skipping any that fail to load.
+pub fn load_system(config: &config::Dictionaries) -> Vec<Box<dyn Dictionary>> {
+    select(config)
+        .into_iter()
+        .filter_map(|path| match Trie::open(&path) {
+            Ok(trie) => Some(Box::new(trie) as Box<dyn Dictionary>),
+            Err(error) => {
+                warn!("failed to load {}: {error}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// The selected system dictionaries by file name, to tell which one a
+/// candidate comes from.
+pub struct Sources {
+    layers: Vec<(String, Trie)>,
+}
+
+impl Sources {
+    pub fn open(config: &config::Dictionaries) -> Self {
+        let layers = select(config)
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                Trie::open(&path).ok().map(|trie| (name, trie))
+            })
+            .collect();
+        Sources { layers }
+    }
+
+    /// The first dictionary listing `phrase` under `reading`, zhuyin
+    /// syllables separated by spaces.
+    pub fn find(&self, reading: &str, phrase: &str) -> Option<&str> {
+        let syllables = reading
+            .split(' ')
+            .map(|syllable| Syllable::from_str(syllable).ok())
+            .collect::<Option<Vec<Syllable>>>()?;
+        self.layers
+            .iter()
+            .find(|(_, trie)| {
+                trie.lookup_all_phrases(&syllables[..])
+                    .iter()
+                    .any(|found| found.as_str() == phrase)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// What could be learned about a single dictionary file.
+pub struct Report {
+    pub path: Option<PathBuf>,
+    pub name: String,
+    pub version: String,
+    pub phrases: usize,
+    pub warning: Option<String>,
+}
+
+impl Report {
+    fn new(path: Option<PathBuf>, dictionary: &dyn Dictionary) -> Self {
+        let info = dictionary.about();
+        Report {
+            path,
+            name: info.name,
+            version: info.version,
+            phrases: dictionary.entries().count(),
+            warning: None,
+        }
+    }
+
+    fn failed(path: Option<PathBuf>, warning: String) -> Self {
+        Report {
+            path,
+            name: String::new(),
+            version: String::new(),
+            phrases: 0,
+            warning: Some(warning),
+        }
+    }
+}
+
+/// Opens every discovered system dictionary and the user dictionary,
+/// recording failures instead of stopping at the first one.
+pub fn inspect() -> Vec<Report> {
+    let mut reports: Vec<Report> = discover()
+        .into_iter()
+        .map(|path| match Trie::open(&path) {
+            Ok(trie) => Report::new(Some(path), &trie),
+            Err(error) => Report::failed(Some(path), error.to_string()),
+        })
+        .collect();
+    for required in [WORD_DICTIONARY, PHRASE_DICTIONARY] {
+        if !reports
+            .iter()
+            .any(|r| r.path.as_ref().and_then(|p| p.file_name()) == Some(required.as_ref()))
+        {
+            reports.push(Report::failed(
+                None,
+                format!("{required} not found on the search path"),
+            ));
+        }
+    }
+    match UserDictionaryLoader::new().load() {
+        Ok(user) => reports.push(Report::new(user.path().map(Path::to_path_buf), user.as_ref())),
+        Err(error) => reports.push(Report::failed(None, format!("user dictionary: {error}"))),
+    }
+    reports
+}
@@ -0,0 +1,64 @@
+//! Whether the desktop is in do-not-disturb mode. There is no common
+//! protocol or portal for it, so the notification daemon is asked, where it
+//! has a way to tell.
+use std::{any::TypeId, time::Duration};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+use tokio::process::Command as Process;
+
+use crate::Message;
+
+/// How often the notification daemon is asked.
+const POLL: Duration = Duration::from_secs(5);
+
+type Answer = fn(&str) -> bool;
+
+/// Whether the first daemon that answers is in do-not-disturb mode, if
+/// any answers.
+async fn active() -> Option<bool> {
+    let queries: [(&str, &[&str], Answer); 4] = [
+        ("makoctl", &["mode"], |stdout| {
+            stdout.lines().any(|mode| mode.trim() == "do-not-disturb")
+        }),
+        ("swaync-client", &["--get-dnd"], |stdout| {
+            stdout.trim() == "true"
+        }),
+        ("dunstctl", &["is-paused"], |stdout| stdout.trim() == "true"),
+        (
+            "gsettings",
+            &["get", "org.gnome.desktop.notifications", "show-banners"],
+            |stdout| stdout.trim() == "false",
+        ),
+    ];
+    for (program, args, answer) in queries {
+        let Ok(output) = Process::new(program).args(args).output().await else {
+            continue;
+        };
+        if output.status.success() {
+            return Some(answer(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+    None
+}
+
+struct DoNotDisturb;
+
+/// Emits [`Message::DoNotDisturb`] whenever do-not-disturb mode is turned on
+/// or off.
+pub fn subscription() -> Subscription<Message> {
+    subscription::channel(TypeId::of::<DoNotDisturb>(), 1, |mut output| async move {
+        let mut active = false;
+        let mut interval = tokio::time::interval(POLL);
+        loop {
+            interval.tick().await;
+            let Some(now) = self::active().await else {
+                log::info!("no notification daemon tells whether not to disturb");
+                return std::future::pending().await;
+            };
+            if now != active {
+                active = now;
+                let _ = output.send(Message::DoNotDisturb(active)).await;
+            }
+        }
+    })
+}
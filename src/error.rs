@@ -0,0 +1,69 @@
+//! What keeps the input method from starting, each with a hint on how to
+//! fix it, for the error window and the log.
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum ChewingWlError {
+    /// No `tsi.dat` or `word.dat` in the dictionary search path.
+    NoSystemDictionary,
+    /// The user dictionary could not be opened, nor restored from a backup.
+    UserDictionary(io::Error),
+    /// A table shipped with the dictionaries could not be loaded.
+    SystemData { table: &'static str, reason: String },
+    /// libchewing could not set up its context.
+    LibChewing,
+    /// The connection to the compositor failed or lacks a protocol.
+    Compositor(String),
+}
+
+impl ChewingWlError {
+    /// What to do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ChewingWlError::NoSystemDictionary | ChewingWlError::SystemData { .. } => {
+                "Install your distribution's libchewing data package, or point \
+                 CHEWING_PATH at the directory holding tsi.dat and word.dat."
+            }
+            ChewingWlError::UserDictionary(_) => {
+                "Check that $XDG_DATA_HOME/chewing is writable, or restore a backup \
+                 with `chewingwl restore-phrases`."
+            }
+            ChewingWlError::LibChewing => {
+                "Reinstall libchewing and its data, or build chewingwl without the \
+                 libchewing feature."
+            }
+            ChewingWlError::Compositor(_) => {
+                "Run chewingwl inside a Wayland session whose compositor supports \
+                 input-method-unstable-v2 and virtual-keyboard-unstable-v1, such as \
+                 Sway or Hyprland."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ChewingWlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChewingWlError::NoSystemDictionary => f.write_str("no system dictionary found"),
+            ChewingWlError::UserDictionary(error) => {
+                write!(f, "cannot open the user dictionary: {error}")
+            }
+            ChewingWlError::SystemData { table, reason } => {
+                write!(f, "cannot load the {table}: {reason}")
+            }
+            ChewingWlError::LibChewing => f.write_str("libchewing failed to initialize"),
+            ChewingWlError::Compositor(reason) => {
+                write!(f, "cannot run on this compositor: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChewingWlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChewingWlError::UserDictionary(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! A window telling why the input method cannot convert and how to fix it,
+//! so a failed start is never only in the log.
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+        },
+    },
+    widget::{button, column, container, text},
+    window, Alignment, Command, Element, Length,
+};
+
+use crate::{config::Surface, error::ChewingWlError, surface, CustomTheme, Message};
+
+const WIDTH: u32 = 420;
+const HEIGHT: u32 = 200;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Dismiss,
+}
+
+pub struct ErrorWindow {
+    id: window::Id,
+    /// The error and its hint, while the window is open.
+    shown: Option<(String, &'static str)>,
+}
+
+impl Default for ErrorWindow {
+    fn default() -> Self {
+        ErrorWindow {
+            id: window::Id::unique(),
+            shown: None,
+        }
+    }
+}
+
+impl ErrorWindow {
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    /// Shows `error`, replacing the one on screen.
+    pub fn open(&mut self, error: &ChewingWlError, surface: Surface) -> Command<Message> {
+        let create = if self.shown.is_none() {
+            get_layer_surface(surface::configured(
+                surface,
+                SctkLayerSurfaceSettings {
+                    id: self.id,
+                    layer: Layer::Overlay,
+                    keyboard_interactivity: KeyboardInteractivity::None,
+                    anchor: Anchor::empty(),
+                    size: Some((Some(WIDTH), Some(HEIGHT))),
+                    namespace: "chewingwl-error".into(),
+                    ..Default::default()
+                },
+            ))
+        } else {
+            Command::none()
+        };
+        self.shown = Some((error.to_string(), error.hint()));
+        create
+    }
+
+    pub fn update(&mut self, event: Event) -> Command<Message> {
+        match event {
+            Event::Dismiss => {
+                self.shown = None;
+                destroy_layer_surface(self.id)
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let (error, hint) = self.shown.clone().unwrap_or_default();
+        container(
+            column(vec![
+                text("chewingwl cannot type Chinese").size(20).into(),
+                text(error).size(16).into(),
+                text(hint).size(14).into(),
+                text("Keys pass through in English until then.")
+                    .size(14)
+                    .into(),
+                button("Dismiss")
+                    .on_press(Message::Error(Event::Dismiss))
+                    .into(),
+            ])
+            .spacing(10.0)
+            .align_items(Alignment::Start),
+        )
+        .padding(16.0)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+            Box::new(CustomTheme::default()),
+        ))
+        .into()
+    }
+}
@@ -0,0 +1,193 @@
+//! What the compositor says has focus. input-method-unstable-v2 tells
+//! neither the focused client nor the output the popup is on, so they are
+//! asked of Hyprland, niri or Sway, whichever answers first, off the UI
+//! thread.
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Copy)]
+enum Compositor {
+    Hyprland,
+    Niri,
+    Sway,
+}
+
+/// The compositor found on the first query, or none of them, so the others
+/// are not tried again on every focus change.
+static COMPOSITOR: OnceLock<Option<Compositor>> = OnceLock::new();
+
+/// The standard output of `program`, if it ran and succeeded.
+async fn query(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn compositor() -> Option<Compositor> {
+    if let Some(compositor) = COMPOSITOR.get() {
+        return *compositor;
+    }
+    let compositor = if query("hyprctl", &["version"]).await.is_some() {
+        Some(Compositor::Hyprland)
+    } else if query("niri", &["msg", "version"]).await.is_some() {
+        Some(Compositor::Niri)
+    } else if query("swaymsg", &["-t", "get_version"]).await.is_some() {
+        Some(Compositor::Sway)
+    } else {
+        log::info!("neither Hyprland, niri nor Sway answered, focus goes unnamed");
+        None
+    };
+    *COMPOSITOR.get_or_init(|| compositor)
+}
+
+/// The identity of the focused client: its class or app ID.
+pub async fn focused_client() -> Option<String> {
+    match compositor().await? {
+        Compositor::Hyprland => field(&query("hyprctl", &["activewindow"]).await?, "class:"),
+        Compositor::Niri => field(&query("niri", &["msg", "focused-window"]).await?, "App ID:"),
+        Compositor::Sway => sway_focused_client(&query("swaymsg", &["-t", "get_tree"]).await?),
+    }
+}
+
+/// A node of the tree `swaymsg -t get_tree` prints.
+#[derive(Deserialize)]
+struct SwayNode {
+    #[serde(default)]
+    focused: bool,
+    app_id: Option<String>,
+    /// Set for Xwayland windows, which have no app ID.
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+}
+
+impl SwayNode {
+    fn focused(&self) -> Option<&SwayNode> {
+        if self.focused {
+            return Some(self);
+        }
+        self.nodes
+            .iter()
+            .chain(&self.floating_nodes)
+            .find_map(SwayNode::focused)
+    }
+}
+
+/// The app ID, or for Xwayland the class, of the focused window in Sway's
+/// tree.
+fn sway_focused_client(tree: &str) -> Option<String> {
+    let tree: SwayNode = serde_json::from_str(tree).ok()?;
+    let window = tree.focused()?;
+    window
+        .app_id
+        .clone()
+        .or_else(|| window.window_properties.as_ref()?.class.clone())
+        .filter(|name| !name.is_empty())
+}
+
+/// The value of the first line starting with `name`, unquoted.
+fn field(output: &str, name: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(name)?.trim().trim_matches('"');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// The name of the focused output, which holds the focused text field and
+/// so the popup.
+pub async fn focused_output() -> Option<String> {
+    match compositor().await? {
+        Compositor::Hyprland => hyprland_focused_output(&query("hyprctl", &["monitors"]).await?),
+        Compositor::Niri => niri_output(&query("niri", &["msg", "focused-output"]).await?),
+        Compositor::Sway => sway_focused_output(&query("swaymsg", &["-t", "get_outputs"]).await?),
+    }
+}
+
+/// `Monitor DP-1 (ID 0):` blocks, the focused one saying `focused: yes`.
+fn hyprland_focused_output(monitors: &str) -> Option<String> {
+    let mut name = None;
+    for line in monitors.lines() {
+        if let Some(header) = line.strip_prefix("Monitor ") {
+            name = header.split_whitespace().next();
+        } else if line.trim() == "focused: yes" {
+            return name.map(str::to_string);
+        }
+    }
+    None
+}
+
+/// `Output "Make Model Serial" (DP-1)`.
+fn niri_output(output: &str) -> Option<String> {
+    let line = output.lines().next()?;
+    let (_, name) = line.strip_suffix(')')?.rsplit_once('(')?;
+    Some(name.to_string())
+}
+
+/// `Output DP-1 'Make Model Serial' (focused)`.
+fn sway_focused_output(outputs: &str) -> Option<String> {
+    outputs
+        .lines()
+        .filter(|line| line.ends_with("(focused)"))
+        .find_map(|line| line.strip_prefix("Output ")?.split_whitespace().next())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_are_named_by_class_or_app_id() {
+        let hyprland = "Window 55d0c0 -> ~:\n\tmapped: 1\n\tclass: foot\n\ttitle: ~\n";
+        assert_eq!(field(hyprland, "class:").as_deref(), Some("foot"));
+        let niri = "Window ID 12: (focused)\n  Title: \"~\"\n  App ID: \"org.gnome.gedit\"\n";
+        assert_eq!(field(niri, "App ID:").as_deref(), Some("org.gnome.gedit"));
+        assert_eq!(field("  App ID: \"\"\n", "App ID:"), None);
+    }
+
+    #[test]
+    fn sway_names_the_focused_window_by_app_id_or_class() {
+        let tree = r#"{"id": 1, "focused": false, "nodes": [{"id": 4, "focused": false,
+            "nodes": [{"id": 9, "focused": false, "app_id": "foot", "nodes": []}],
+            "floating_nodes": [{"id": 12, "focused": true, "app_id": null,
+            "window_properties": {"class": "firefox"}, "nodes": []}]}]}"#;
+        assert_eq!(sway_focused_client(tree).as_deref(), Some("firefox"));
+        let tree = r#"{"id": 1, "focused": false, "nodes": [
+            {"id": 9, "focused": true, "app_id": "foot", "nodes": []}]}"#;
+        assert_eq!(sway_focused_client(tree).as_deref(), Some("foot"));
+        // An empty workspace has the focus
+        let tree = r#"{"id": 1, "focused": false, "nodes": [{"id": 4, "focused": true}]}"#;
+        assert_eq!(sway_focused_client(tree), None);
+    }
+
+    #[test]
+    fn hyprland_names_the_focused_monitor() {
+        let monitors = "Monitor eDP-1 (ID 0):\n\t2256x1504@60.00000 at 0x0\n\tfocused: no\n\n\
+                        Monitor DP-1 (ID 1):\n\t3840x2160@60.00000 at 2256x0\n\tfocused: yes\n";
+        assert_eq!(hyprland_focused_output(monitors).as_deref(), Some("DP-1"));
+    }
+
+    #[test]
+    fn niri_names_the_output_in_parentheses() {
+        let output = "Output \"Dell Inc. DELL U2720Q (1234)\" (DP-1)\n  Current mode: 3840x2160\n";
+        assert_eq!(niri_output(output).as_deref(), Some("DP-1"));
+    }
+
+    #[test]
+    fn sway_names_the_output_marked_focused() {
+        let outputs = "Output eDP-1 'Sharp 0x14F9 0x0' \n  Current mode: 2256x1504\n\
+                       Output DP-1 'Dell U2720Q 1234' (focused)\n  Current mode: 3840x2160\n";
+        assert_eq!(sway_focused_output(outputs).as_deref(), Some("DP-1"));
+    }
+}
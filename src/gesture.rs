@@ -0,0 +1,321 @@
+//! Detects double taps of configured keys in the key event stream, and how
+//! Down is pressed while composing.
+use std::time::{Duration, Instant};
+
+use iced_core::{event::wayland::KeyEvent, keyboard::Key};
+
+use crate::{
+    config::{self, DownPolicy},
+    hotkey::Action,
+};
+
+/// How long Down has to be held to open the popup with [`DownPolicy::Hold`].
+pub const HOLD: Duration = Duration::from_millis(400);
+
+pub struct Gestures {
+    config: config::Gestures,
+    /// How far the binding tapped last has got, cleared by any other key.
+    tap: Option<Tap>,
+}
+
+/// A double tap in progress, by the index of its binding.
+#[derive(Clone, Copy)]
+enum Tap {
+    First(usize),
+    /// Released after the first press, at the instant given.
+    Between(usize, Instant),
+    Second(usize, Instant),
+}
+
+impl Gestures {
+    pub fn new(config: config::Gestures) -> Self {
+        Gestures { config, tap: None }
+    }
+
+    fn binding(&self, event: &KeyEvent, key: &Key) -> Option<usize> {
+        self.config
+            .double_tap
+            .iter()
+            .position(|tap| tap.key.key_matches(event, key))
+    }
+
+    /// Feeds a key press. Any key but the one being tapped, or a second
+    /// press that comes too late, starts over.
+    pub fn press(&mut self, event: &KeyEvent, key: &Key) {
+        let window = Duration::from_millis(self.config.double_tap_ms);
+        let Some(binding) = self.binding(event, key) else {
+            self.tap = None;
+            return;
+        };
+        self.tap = match self.tap.take() {
+            Some(Tap::Between(last, at)) if last == binding && at.elapsed() <= window => {
+                Some(Tap::Second(binding, at))
+            }
+            // Repeats of the held key
+            Some(tap @ (Tap::First(last) | Tap::Second(last, _))) if last == binding => Some(tap),
+            _ => Some(Tap::First(binding)),
+        };
+    }
+
+    /// Feeds a key release, returning the bound action if it ends a double
+    /// tap. Firing on the release, not the press, leaves the key free to
+    /// start a chord such as Shift+A.
+    pub fn release(&mut self, event: &KeyEvent, key: &Key) -> Option<Action> {
+        let window = Duration::from_millis(self.config.double_tap_ms);
+        let binding = self.binding(event, key);
+        match self.tap.take() {
+            Some(Tap::First(first)) if binding == Some(first) => {
+                self.tap = Some(Tap::Between(first, Instant::now()));
+                None
+            }
+            Some(Tap::Second(second, at)) if binding == Some(second) => {
+                (at.elapsed() <= window).then(|| self.config.double_tap[second].action)
+            }
+            // Letting go of another key, such as one held before the tap
+            tap => {
+                self.tap = tap;
+                None
+            }
+        }
+    }
+}
+
+/// What a press of Down while composing should do.
+pub enum Down {
+    OpenPopup,
+    MoveToEnd,
+    /// Check back after [`HOLD`] with [`DownKey::held`].
+    StartHold(u64),
+    Nothing,
+}
+
+/// Tracks Down presses for the configured [`DownPolicy`].
+pub struct DownKey {
+    policy: DownPolicy,
+    double_tap: Duration,
+    /// Bumped on every press so a stale hold timer is ignored.
+    generation: u64,
+    held: bool,
+    opened: bool,
+    last_tap: Option<Instant>,
+}
+
+impl DownKey {
+    pub fn new(policy: DownPolicy, double_tap_ms: u64) -> Self {
+        DownKey {
+            policy,
+            double_tap: Duration::from_millis(double_tap_ms),
+            generation: 0,
+            held: false,
+            opened: false,
+            last_tap: None,
+        }
+    }
+
+    /// Feeds a press of Down, including key repeats.
+    pub fn press(&mut self) -> Down {
+        match self.policy {
+            DownPolicy::Press => Down::OpenPopup,
+            // Repeats of a held key change nothing, the release or the
+            // timer decides
+            DownPolicy::DoubleTap | DownPolicy::Hold if self.held => Down::Nothing,
+            DownPolicy::DoubleTap => {
+                self.held = true;
+                self.opened =
+                    matches!(self.last_tap.take(), Some(at) if at.elapsed() <= self.double_tap);
+                if self.opened {
+                    Down::OpenPopup
+                } else {
+                    Down::MoveToEnd
+                }
+            }
+            DownPolicy::Hold => {
+                self.held = true;
+                self.opened = false;
+                self.generation += 1;
+                Down::StartHold(self.generation)
+            }
+        }
+    }
+
+    /// The hold timer for `generation` ran out, returns whether Down is still
+    /// held and the popup should open.
+    pub fn held(&mut self, generation: u64) -> bool {
+        if self.held && !self.opened && generation == self.generation {
+            self.opened = true;
+            return true;
+        }
+        false
+    }
+
+    /// Feeds a release of Down, returning whether it ends a short press.
+    /// The window for a second tap starts here.
+    pub fn release(&mut self) -> bool {
+        let held = std::mem::take(&mut self.held);
+        match self.policy {
+            DownPolicy::Press => false,
+            DownPolicy::DoubleTap => {
+                // The tap that opened the popup does not start another
+                self.last_tap = (held && !self.opened).then(Instant::now);
+                false
+            }
+            DownPolicy::Hold => held && !self.opened,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use iced::keyboard::key::Named;
+    use xkeysym::Keysym;
+
+    use super::*;
+    use crate::config::DoubleTap;
+
+    fn gestures(double_tap_ms: u64) -> Gestures {
+        Gestures::new(config::Gestures {
+            double_tap_ms,
+            double_tap: vec![DoubleTap {
+                key: "Shift".parse().unwrap(),
+                action: Action::ToggleFullWidth,
+            }],
+        })
+    }
+
+    fn shift() -> (KeyEvent, Key) {
+        let event = KeyEvent {
+            time: 0,
+            raw_code: 42,
+            keysym: Keysym::new(0xffe1),
+            utf8: None,
+        };
+        (event, Key::Named(Named::Shift))
+    }
+
+    fn a() -> (KeyEvent, Key) {
+        let event = KeyEvent {
+            time: 0,
+            raw_code: 30,
+            keysym: Keysym::new(0x61),
+            utf8: Some("a".into()),
+        };
+        (event, Key::Character("a".into()))
+    }
+
+    /// Presses and releases `key`, returning what the release fired.
+    fn tap(gestures: &mut Gestures, (event, key): (KeyEvent, Key)) -> Option<Action> {
+        gestures.press(&event, &key);
+        gestures.release(&event, &key)
+    }
+
+    #[test]
+    fn a_second_tap_in_time_fires_on_its_release() {
+        let mut gestures = gestures(10_000);
+        assert_eq!(tap(&mut gestures, shift()), None);
+        let (event, key) = shift();
+        gestures.press(&event, &key);
+        assert_eq!(
+            gestures.release(&event, &key),
+            Some(Action::ToggleFullWidth)
+        );
+        // The next tap starts over
+        assert_eq!(tap(&mut gestures, shift()), None);
+    }
+
+    #[test]
+    fn a_second_tap_too_late_starts_over() {
+        let mut gestures = gestures(1);
+        assert_eq!(tap(&mut gestures, shift()), None);
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(tap(&mut gestures, shift()), None);
+    }
+
+    #[test]
+    fn another_key_in_between_starts_over() {
+        let mut gestures = gestures(10_000);
+        assert_eq!(tap(&mut gestures, shift()), None);
+        assert_eq!(tap(&mut gestures, a()), None);
+        assert_eq!(tap(&mut gestures, shift()), None);
+        // Pressed during the second tap, as in Shift+A
+        let (event, key) = shift();
+        gestures.press(&event, &key);
+        let (other, other_key) = a();
+        gestures.press(&other, &other_key);
+        assert_eq!(gestures.release(&other, &other_key), None);
+        assert_eq!(gestures.release(&event, &key), None);
+    }
+
+    #[test]
+    fn releasing_a_key_held_before_the_tap_does_not_interrupt_it() {
+        let mut gestures = gestures(10_000);
+        let (other, other_key) = a();
+        gestures.press(&other, &other_key);
+        assert_eq!(tap(&mut gestures, shift()), None);
+        assert_eq!(gestures.release(&other, &other_key), None);
+        assert_eq!(tap(&mut gestures, shift()), Some(Action::ToggleFullWidth));
+    }
+
+    #[test]
+    fn key_repeats_while_held_are_one_tap() {
+        let mut gestures = gestures(10_000);
+        let (event, key) = shift();
+        for _ in 0..3 {
+            gestures.press(&event, &key);
+        }
+        assert_eq!(gestures.release(&event, &key), None);
+        for _ in 0..3 {
+            gestures.press(&event, &key);
+        }
+        assert_eq!(
+            gestures.release(&event, &key),
+            Some(Action::ToggleFullWidth)
+        );
+    }
+
+    #[test]
+    fn double_tapping_down_opens_the_popup() {
+        let mut down = DownKey::new(DownPolicy::DoubleTap, 10_000);
+        assert!(matches!(down.press(), Down::MoveToEnd));
+        down.release();
+        assert!(matches!(down.press(), Down::OpenPopup));
+        assert!(!down.release());
+        assert!(matches!(down.press(), Down::MoveToEnd));
+        let mut down = DownKey::new(DownPolicy::DoubleTap, 1);
+        assert!(matches!(down.press(), Down::MoveToEnd));
+        down.release();
+        thread::sleep(Duration::from_millis(5));
+        assert!(matches!(down.press(), Down::MoveToEnd));
+    }
+
+    #[test]
+    fn holding_down_with_double_tap_only_moves_to_the_end() {
+        let mut down = DownKey::new(DownPolicy::DoubleTap, 10_000);
+        assert!(matches!(down.press(), Down::MoveToEnd));
+        for _ in 0..3 {
+            assert!(matches!(down.press(), Down::Nothing));
+        }
+        assert!(!down.release());
+        // The window runs from that release
+        assert!(matches!(down.press(), Down::OpenPopup));
+    }
+
+    #[test]
+    fn holding_down_opens_the_popup_once() {
+        let mut down = DownKey::new(DownPolicy::Hold, 300);
+        let Down::StartHold(generation) = down.press() else {
+            panic!("no hold timer started");
+        };
+        assert!(matches!(down.press(), Down::Nothing));
+        assert!(down.held(generation));
+        assert!(!down.held(generation));
+        assert!(!down.release());
+        // A short press, its timer running out after the release
+        let Down::StartHold(generation) = down.press() else {
+            panic!("no hold timer started");
+        };
+        assert!(down.release());
+        assert!(!down.held(generation));
+    }
+}
@@ -0,0 +1,132 @@
+//! Opt-in history of rare candidates picked from the popup, so a character
+//! chosen yesterday can be found again. Nothing is read or written unless
+//! `history.enabled` is set, and `chewingwlctl history clear` wipes it.
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use crate::{config, user_data};
+
+const DAY: u64 = 24 * 60 * 60;
+
+pub struct Entry {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub text: String,
+    /// Zhuyin of each character, space separated.
+    pub reading: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Entry> {
+        let mut fields = line.splitn(3, '\t');
+        Some(Entry {
+            at: fields.next()?.parse().ok()?,
+            text: fields.next()?.to_string(),
+            reading: fields.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// How long ago the candidate was picked, in the coarsest fitting unit.
+    pub fn age(&self) -> String {
+        let seconds = now().saturating_sub(self.at);
+        match seconds {
+            0..=59 => "just now".to_string(),
+            60..=3599 => format!("{} min ago", seconds / 60),
+            3600..=86399 => format!("{} h ago", seconds / 3600),
+            _ => format!("{} d ago", seconds / DAY),
+        }
+    }
+}
+
+pub struct History {
+    config: config::History,
+    /// Newest first.
+    entries: VecDeque<Entry>,
+}
+
+impl History {
+    pub fn load(config: &config::History) -> Self {
+        let mut history = History {
+            config: config.clone(),
+            entries: VecDeque::new(),
+        };
+        if !config.enabled {
+            return history;
+        }
+        let Some(path) = path() else {
+            return history;
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => history.entries = text.lines().filter_map(Entry::parse).collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => warn!("failed to read history {}: {error}", path.display()),
+        }
+        history.expire();
+        history
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Records `text` picked at `rank` in the candidate list, if the history
+    /// is enabled and the pick is rare enough.
+    pub fn record(&mut self, text: &str, reading: String, rank: usize) {
+        if !self.config.enabled || rank < self.config.min_rank {
+            return;
+        }
+        self.entries.retain(|entry| entry.text != text);
+        self.entries.push_front(Entry {
+            at: now(),
+            text: text.to_string(),
+            reading,
+        });
+        self.expire();
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        if let Some(path) = path() {
+            user_data::remove_state_file(&path, "history");
+        }
+    }
+
+    fn expire(&mut self) {
+        let oldest = now().saturating_sub(self.config.keep_days * DAY);
+        self.entries.retain(|entry| entry.at >= oldest);
+        self.entries.truncate(self.config.max_entries);
+    }
+
+    fn save(&self) {
+        let Some(path) = path() else {
+            return;
+        };
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{}\t{}\t{}\n", entry.at, entry.text, entry.reading))
+            .collect();
+        user_data::write_later("history", path, contents.into_bytes());
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("history"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
@@ -0,0 +1,137 @@
+//! A panel listing the selection history, opened with `chewingwlctl history`.
+//! Clicking an entry types it into the focused application.
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+        },
+    },
+    widget::{button, column, container, row, scrollable, text},
+    window, Alignment, Command, Element, Length,
+};
+
+use crate::{
+    config::Surface,
+    history::History,
+    surface,
+    tone::{self, ToneMarks},
+    CustomTheme, Message,
+};
+
+const WIDTH: u32 = 360;
+const HEIGHT: u32 = 480;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Type the entry at this index.
+    Pick(usize),
+    Clear,
+    Close,
+}
+
+pub struct HistoryPanel {
+    id: window::Id,
+    open: bool,
+}
+
+impl Default for HistoryPanel {
+    fn default() -> Self {
+        HistoryPanel {
+            id: window::Id::unique(),
+            open: false,
+        }
+    }
+}
+
+impl HistoryPanel {
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    pub fn open(&mut self, surface: Surface) -> Command<Message> {
+        if std::mem::replace(&mut self.open, true) {
+            return Command::none();
+        }
+        get_layer_surface(surface::configured(
+            surface,
+            SctkLayerSurfaceSettings {
+                id: self.id,
+                layer: Layer::Top,
+                keyboard_interactivity: KeyboardInteractivity::None,
+                anchor: Anchor::TOP | Anchor::RIGHT,
+                size: Some((Some(WIDTH), Some(HEIGHT))),
+                namespace: "chewingwl-history".into(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    pub fn close(&mut self) -> Command<Message> {
+        if std::mem::replace(&mut self.open, false) {
+            destroy_layer_surface(self.id)
+        } else {
+            Command::none()
+        }
+    }
+
+    pub fn view<'a>(&self, history: &'a History, tone_marks: ToneMarks) -> Element<'a, Message> {
+        let entries: Vec<Element<Message>> = history
+            .entries()
+            .enumerate()
+            .map(|(index, entry)| {
+                button(
+                    row(vec![
+                        text(&entry.text).size(28).into(),
+                        column(vec![
+                            text(tone::present(&entry.reading, tone_marks))
+                                .size(14)
+                                .into(),
+                            text(entry.age()).size(11).into(),
+                        ])
+                        .into(),
+                    ])
+                    .spacing(12.0)
+                    .align_items(Alignment::Center),
+                )
+                .width(Length::Fill)
+                .style(iced::theme::Button::Text)
+                .on_press(Message::History(Event::Pick(index)))
+                .into()
+            })
+            .collect();
+        let list: Element<Message> = if entries.is_empty() {
+            text("Nothing picked yet, or history.enabled is off")
+                .size(14)
+                .into()
+        } else {
+            scrollable(column(entries).spacing(2.0))
+                .height(Length::Fill)
+                .into()
+        };
+        container(
+            column(vec![
+                text("Recently picked").size(20).into(),
+                list,
+                row(vec![
+                    button("Clear")
+                        .on_press(Message::History(Event::Clear))
+                        .into(),
+                    button("Close")
+                        .on_press(Message::History(Event::Close))
+                        .into(),
+                ])
+                .spacing(8.0)
+                .into(),
+            ])
+            .spacing(12.0),
+        )
+        .padding(16.0)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+            Box::new(CustomTheme::default()),
+        ))
+        .into()
+    }
+}
@@ -0,0 +1,795 @@
+//! Key combinations bound to input method actions, written in config as
+//! e.g. `"Ctrl+Alt+F12"`. Modifiers can be sided, as in `"RightAlt+S"`, or
+//! a sided modifier can be the key itself, as in `"RightAlt"`.
+use std::{fmt, str::FromStr};
+
+use iced::keyboard::key::Named;
+use iced_core::{
+    event::wayland::{KeyEvent, Modifiers},
+    keyboard::Key,
+};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Something a key binding can trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Switch between Chinese and English input.
+    ToggleMode,
+    /// Switch between half-width and full-width characters.
+    ToggleFullWidth,
+    ToggleLowLatency,
+    /// Keep the candidate popup open across selections.
+    PinPopup,
+    /// Close the candidate popup without touching the composition.
+    CancelSelection,
+    /// Save the composition or the last commit as a snippet.
+    AddSnippet,
+    /// Commit the composition and return to a clean Chinese mode.
+    ForceReset,
+    /// Show or hide the strokes and radical of the highlighted candidate.
+    CandidateInfo,
+    /// Browse the CJK blocks of Unicode in the popup.
+    CharacterMap,
+    /// Type a stroke sequence or four-corner code to find a character.
+    ShapeLookup,
+}
+
+impl Action {
+    /// A short description, as printed by `chewingwl keymap`.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Action::ToggleMode => "switch between Chinese and English",
+            Action::ToggleFullWidth => "switch between half-width and full-width",
+            Action::ToggleLowLatency => "forward every key untouched",
+            Action::PinPopup => "keep the popup open across selections",
+            Action::CancelSelection => "close the popup, keeping the composition",
+            Action::AddSnippet => "save the composition as a snippet",
+            Action::ForceReset => "commit and reset to Chinese mode",
+            Action::CandidateInfo => "strokes and radical of the candidate",
+            Action::CharacterMap => "browse the CJK blocks of Unicode",
+            Action::ShapeLookup => "find a character by strokes or four-corner code",
+        }
+    }
+}
+
+/// When a binding is looked at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Always,
+    /// Only while the candidate popup is shown.
+    Popup,
+    /// Pressing the key twice in quick succession.
+    DoubleTap,
+}
+
+/// A key combination from the config and what it does.
+pub struct Binding<'a> {
+    /// Where it is set in the config.
+    pub name: String,
+    pub hotkey: &'a Hotkey,
+    pub action: Action,
+    pub scope: Scope,
+}
+
+/// Every binding set in `config`, in the order the key handler tries them
+/// within each scope.
+pub fn bindings(config: &Config) -> Vec<Binding<'_>> {
+    let hotkeys = &config.hotkeys;
+    let mut bindings = Vec::new();
+    let always = [
+        ("force_reset", &hotkeys.force_reset, Action::ForceReset),
+        (
+            "low_latency",
+            &hotkeys.low_latency,
+            Action::ToggleLowLatency,
+        ),
+        ("shape_lookup", &hotkeys.shape_lookup, Action::ShapeLookup),
+        ("add_snippet", &hotkeys.add_snippet, Action::AddSnippet),
+        ("toggle_mode", &hotkeys.toggle_mode, Action::ToggleMode),
+    ];
+    let popup = [
+        (
+            "candidate_info",
+            &hotkeys.candidate_info,
+            Action::CandidateInfo,
+        ),
+        (
+            "character_map",
+            &hotkeys.character_map,
+            Action::CharacterMap,
+        ),
+        ("pin_popup", &hotkeys.pin_popup, Action::PinPopup),
+        (
+            "cancel_selection",
+            &hotkeys.cancel_selection,
+            Action::CancelSelection,
+        ),
+    ];
+    for (scope, list) in [(Scope::Always, &always[..]), (Scope::Popup, &popup[..])] {
+        for &(name, hotkey, action) in list {
+            if let Some(hotkey) = hotkey {
+                bindings.push(Binding {
+                    name: format!("hotkeys.{name}"),
+                    hotkey,
+                    action,
+                    scope,
+                });
+            }
+        }
+    }
+    for (i, tap) in config.gestures.double_tap.iter().enumerate() {
+        bindings.push(Binding {
+            name: format!("gestures.double_tap[{i}]"),
+            hotkey: &tap.key,
+            action: tap.action,
+            scope: Scope::DoubleTap,
+        });
+    }
+    bindings
+}
+
+/// Evdev keycodes of the left and right key of each modifier family, which
+/// the generic modifier state cannot tell apart.
+const CTRL: (u32, u32) = (29, 97);
+const ALT: (u32, u32) = (56, 100);
+const SHIFT: (u32, u32) = (42, 54);
+const LOGO: (u32, u32) = (125, 126);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum KeyName {
+    Named(Named),
+    Character(String),
+    /// A key identified by its keycode, for the sided modifiers.
+    Code(u32),
+}
+
+/// Which key of a modifier family must be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Held {
+    No,
+    Either,
+    Left,
+    Right,
+}
+
+impl Held {
+    fn matches(self, active: bool, (left, right): (u32, u32), held: &HeldKeys) -> bool {
+        match self {
+            Held::No => !active,
+            Held::Either => active,
+            Held::Left => held.contains(left),
+            // Right Alt is often AltGr, which does not set the Alt modifier.
+            Held::Right => held.contains(right),
+        }
+    }
+
+    /// Whether one set of held keys can satisfy both requirements.
+    fn overlaps(self, other: Held) -> bool {
+        !matches!(
+            (self, other),
+            (Held::No, Held::Either | Held::Left | Held::Right)
+                | (Held::Either | Held::Left | Held::Right, Held::No)
+                | (Held::Left, Held::Right)
+                | (Held::Right, Held::Left)
+        )
+    }
+}
+
+/// Keycodes of the keys currently held down.
+#[derive(Debug, Default)]
+pub struct HeldKeys(Vec<u32>);
+
+impl HeldKeys {
+    pub fn press(&mut self, code: u32) {
+        if !self.contains(code) {
+            self.0.push(code);
+        }
+    }
+
+    pub fn release(&mut self, code: u32) {
+        self.0.retain(|held| *held != code);
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn contains(&self, code: u32) -> bool {
+        self.0.contains(&code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Hotkey {
+    ctrl: Held,
+    alt: Held,
+    shift: Held,
+    logo: Held,
+    key: KeyName,
+}
+
+/// Parses a modifier name into its family and side.
+fn modifier(name: &str) -> Option<(&'static str, Held)> {
+    let modifier = match name.to_ascii_lowercase().as_str() {
+        "ctrl" | "control" => ("ctrl", Held::Either),
+        "leftctrl" | "lctrl" => ("ctrl", Held::Left),
+        "rightctrl" | "rctrl" => ("ctrl", Held::Right),
+        "alt" => ("alt", Held::Either),
+        "leftalt" | "lalt" => ("alt", Held::Left),
+        "rightalt" | "ralt" | "altgr" => ("alt", Held::Right),
+        "shift" => ("shift", Held::Either),
+        "leftshift" | "lshift" => ("shift", Held::Left),
+        "rightshift" | "rshift" => ("shift", Held::Right),
+        "super" | "logo" | "meta" => ("logo", Held::Either),
+        "leftsuper" | "lsuper" => ("logo", Held::Left),
+        "rightsuper" | "rsuper" => ("logo", Held::Right),
+        _ => return None,
+    };
+    Some(modifier)
+}
+
+/// A sided modifier pressed on its own, e.g. a binding to just `RightAlt`.
+fn sided_key(name: &str) -> Option<u32> {
+    let (family, held) = modifier(name)?;
+    let (left, right) = match family {
+        "ctrl" => CTRL,
+        "alt" => ALT,
+        "shift" => SHIFT,
+        _ => LOGO,
+    };
+    match held {
+        Held::Left => Some(left),
+        Held::Right => Some(right),
+        _ => None,
+    }
+}
+
+fn named(name: &str) -> Option<Named> {
+    let named = match name.to_ascii_lowercase().as_str() {
+        "escape" | "esc" => Named::Escape,
+        "space" => Named::Space,
+        "enter" | "return" => Named::Enter,
+        "tab" => Named::Tab,
+        "backspace" => Named::Backspace,
+        "delete" | "del" => Named::Delete,
+        "insert" => Named::Insert,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "up" => Named::ArrowUp,
+        "down" => Named::ArrowDown,
+        "left" => Named::ArrowLeft,
+        "right" => Named::ArrowRight,
+        "shift" => Named::Shift,
+        "control" => Named::Control,
+        "alt" => Named::Alt,
+        "super" => Named::Super,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ => return None,
+    };
+    Some(named)
+}
+
+/// The name [`named`] takes for `named`.
+fn name_of(named: Named) -> String {
+    let name = match named {
+        Named::Escape => "Escape",
+        Named::Space => "Space",
+        Named::Enter => "Enter",
+        Named::Tab => "Tab",
+        Named::Backspace => "Backspace",
+        Named::Delete => "Delete",
+        Named::Insert => "Insert",
+        Named::Home => "Home",
+        Named::End => "End",
+        Named::PageUp => "PageUp",
+        Named::PageDown => "PageDown",
+        Named::ArrowUp => "Up",
+        Named::ArrowDown => "Down",
+        Named::ArrowLeft => "Left",
+        Named::ArrowRight => "Right",
+        Named::Shift => "Shift",
+        Named::Control => "Control",
+        Named::Alt => "Alt",
+        Named::Super => "Super",
+        Named::F1 => "F1",
+        Named::F2 => "F2",
+        Named::F3 => "F3",
+        Named::F4 => "F4",
+        Named::F5 => "F5",
+        Named::F6 => "F6",
+        Named::F7 => "F7",
+        Named::F8 => "F8",
+        Named::F9 => "F9",
+        Named::F10 => "F10",
+        Named::F11 => "F11",
+        Named::F12 => "F12",
+        other => return format!("{other:?}"),
+    };
+    name.to_string()
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hotkey = Hotkey::bare(KeyName::Character(String::new()));
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty());
+        for name in parts {
+            let family = match modifier(name) {
+                Some(("ctrl", held)) => (&mut hotkey.ctrl, held),
+                Some(("alt", held)) => (&mut hotkey.alt, held),
+                Some(("shift", held)) => (&mut hotkey.shift, held),
+                Some((_, held)) => (&mut hotkey.logo, held),
+                None => return Err(format!("unknown modifier `{name}` in `{s}`")),
+            };
+            *family.0 = family.1;
+        }
+        hotkey.key = match key {
+            Some(key) if key.chars().count() == 1 => KeyName::Character(key.to_lowercase()),
+            Some(key) => match (sided_key(key), named(key)) {
+                (Some(code), _) => KeyName::Code(code),
+                (None, Some(named)) => KeyName::Named(named),
+                (None, None) => return Err(format!("unknown key `{key}` in `{s}`")),
+            },
+            None => return Err(format!("missing key in `{s}`")),
+        };
+        Ok(hotkey)
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+fn side_name(held: Held, name: &str) -> String {
+    match held {
+        Held::No => String::new(),
+        Held::Either => format!("{name}+"),
+        Held::Left => format!("Left{name}+"),
+        Held::Right => format!("Right{name}+"),
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&side_name(self.ctrl, "Ctrl"))?;
+        f.write_str(&side_name(self.alt, "Alt"))?;
+        f.write_str(&side_name(self.shift, "Shift"))?;
+        f.write_str(&side_name(self.logo, "Super"))?;
+        match &self.key {
+            KeyName::Named(named) => f.write_str(&name_of(*named)),
+            KeyName::Character(c) => f.write_str(&c.to_uppercase()),
+            KeyName::Code(code) => {
+                let name = match *code {
+                    c if c == CTRL.0 => "LeftCtrl",
+                    c if c == CTRL.1 => "RightCtrl",
+                    c if c == ALT.0 => "LeftAlt",
+                    c if c == ALT.1 => "RightAlt",
+                    c if c == SHIFT.0 => "LeftShift",
+                    c if c == SHIFT.1 => "RightShift",
+                    c if c == LOGO.0 => "LeftSuper",
+                    _ => "RightSuper",
+                };
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl Hotkey {
+    /// Whether the pressed key is this combination's key, regardless of
+    /// modifiers.
+    pub fn key_matches(&self, event: &KeyEvent, key: &Key) -> bool {
+        match (&self.key, key.as_ref()) {
+            (KeyName::Code(code), _) => *code == event.raw_code,
+            (KeyName::Named(expected), Key::Named(named)) => *expected == named,
+            (KeyName::Character(expected), Key::Character(c)) => *expected == c.to_lowercase(),
+            _ => false,
+        }
+    }
+
+    /// Whether the pressed key with `modifiers` and the `held` keys down is
+    /// this combination.
+    pub fn matches(
+        &self,
+        event: &KeyEvent,
+        key: &Key,
+        modifiers: &Modifiers,
+        held: &HeldKeys,
+    ) -> bool {
+        // Pressing a sided modifier bound on its own may already set the
+        // modifier of its family, so that family is not checked.
+        let check = |codes: (u32, u32), wanted: Held, active: bool| {
+            matches!(self.key, KeyName::Code(code) if code == codes.0 || code == codes.1)
+                || wanted.matches(active, codes, held)
+        };
+        self.key_matches(event, key)
+            && check(CTRL, self.ctrl, modifiers.ctrl)
+            && check(ALT, self.alt, modifiers.alt)
+            && check(SHIFT, self.shift, modifiers.shift)
+            && check(LOGO, self.logo, modifiers.logo)
+    }
+}
+
+impl Hotkey {
+    fn bare(key: KeyName) -> Hotkey {
+        Hotkey {
+            ctrl: Held::No,
+            alt: Held::No,
+            shift: Held::No,
+            logo: Held::No,
+            key,
+        }
+    }
+
+    /// Whether one key press triggers both this combination and `other`.
+    pub fn overlaps(&self, other: &Hotkey) -> bool {
+        self.key == other.key
+            && self.ctrl.overlaps(other.ctrl)
+            && self.alt.overlaps(other.alt)
+            && self.shift.overlaps(other.shift)
+            && self.logo.overlaps(other.logo)
+    }
+
+    /// What the key already does while composing, if the combination is
+    /// plain enough to reach the editor.
+    fn builtin_use(&self, selection_keys: &[char]) -> Option<&'static str> {
+        if self.ctrl != Held::No || self.alt != Held::No || self.logo != Held::No {
+            return None;
+        }
+        let shifted = self.shift != Held::No;
+        match &self.key {
+            KeyName::Named(Named::Space) if shifted => Some("toggles full-width characters"),
+            KeyName::Named(Named::Shift) => Some("toggles Chinese and English when tapped"),
+            _ if shifted => None,
+            KeyName::Character(c) if c.chars().all(|c| selection_keys.contains(&c)) => {
+                Some("selects candidates in the popup")
+            }
+            KeyName::Character(_) => Some("types zhuyin"),
+            KeyName::Named(Named::Space) => Some("converts and opens the candidate popup"),
+            KeyName::Named(Named::Enter) => Some("commits the composition"),
+            KeyName::Named(Named::Escape) => Some("closes the candidate popup"),
+            KeyName::Named(
+                Named::Backspace
+                | Named::Delete
+                | Named::Tab
+                | Named::ArrowUp
+                | Named::ArrowDown
+                | Named::ArrowLeft
+                | Named::ArrowRight,
+            ) => Some("edits the composition"),
+            _ => None,
+        }
+    }
+
+    /// What the key already does while the candidate popup is open.
+    fn popup_use(&self, selection_keys: &[char]) -> Option<&'static str> {
+        if self.ctrl != Held::No || self.alt != Held::No || self.logo != Held::No {
+            return None;
+        }
+        match &self.key {
+            KeyName::Character(c) if c.chars().all(|c| selection_keys.contains(&c)) => {
+                Some("selects candidates in the popup")
+            }
+            KeyName::Named(Named::Escape) => Some("closes the candidate popup"),
+            KeyName::Named(Named::Enter) => Some("picks the highlighted candidate"),
+            KeyName::Named(
+                Named::ArrowUp | Named::ArrowDown | Named::ArrowLeft | Named::ArrowRight,
+            ) => Some("moves through the candidates"),
+            _ => None,
+        }
+    }
+
+    /// Why the compositor or the system likely keeps this combination from
+    /// ever reaching the input method.
+    fn reserved(&self) -> Option<&'static str> {
+        let ctrl_alt = self.ctrl != Held::No && self.alt != Held::No;
+        match &self.key {
+            _ if self.logo != Held::No => {
+                Some("Super combinations are usually compositor bindings")
+            }
+            KeyName::Named(
+                Named::F1 | Named::F2 | Named::F3 | Named::F4 | Named::F5 | Named::F6,
+            ) if ctrl_alt => Some("switches virtual terminals"),
+            KeyName::Named(Named::Delete | Named::Backspace) if ctrl_alt => {
+                Some("is usually handled by the system")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Describes bindings in `config` that shadow each other, shadow keys used
+/// while composing, or are likely taken by the compositor.
+pub fn conflicts(config: &Config) -> Vec<String> {
+    let bindings = bindings(config);
+    let selection_keys = config.popup.selection_keys();
+    let mut conflicts = Vec::new();
+    for (i, binding) in bindings.iter().enumerate() {
+        let (name, key) = (&binding.name, binding.hotkey);
+        for other in &bindings[i + 1..] {
+            if key.overlaps(other.hotkey) {
+                conflicts.push(format!(
+                    "{name} `{key}` is also {} `{}`",
+                    other.name, other.hotkey
+                ));
+            }
+        }
+        // Double taps only look at the key, and its first tap still does
+        // whatever the key does on its own
+        let bare;
+        let effective = if binding.scope == Scope::DoubleTap {
+            bare = Hotkey::bare(key.key.clone());
+            &bare
+        } else {
+            key
+        };
+        let shift_tap = matches!(key.key, KeyName::Named(Named::Shift));
+        let builtin = if binding.scope == Scope::Popup {
+            effective.popup_use(&selection_keys)
+        } else {
+            effective.builtin_use(&selection_keys)
+        };
+        match builtin {
+            // Tapping Shift only toggles while no toggle key is configured
+            Some(_) if shift_tap && config.hotkeys.toggle_mode.is_some() => {}
+            Some(builtin) => {
+                conflicts.push(format!("{name} `{key}` {builtin} as well"));
+            }
+            None => {}
+        }
+        if let Some(reason) = effective.reserved() {
+            conflicts.push(format!("{name} `{key}` may never arrive: {reason}"));
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use xkeysym::Keysym;
+
+    use super::*;
+    use crate::config::{DoubleTap, Hotkeys};
+
+    fn hotkey(s: &str) -> Hotkey {
+        s.parse().unwrap()
+    }
+
+    /// A config with only the given bindings.
+    fn bound(hotkeys: Hotkeys) -> Config {
+        Config {
+            hotkeys,
+            ..Config::default()
+        }
+    }
+
+    fn unbound() -> Hotkeys {
+        Hotkeys {
+            toggle_mode: None,
+            low_latency: None,
+            pin_popup: None,
+            cancel_selection: None,
+            add_snippet: None,
+            force_reset: None,
+            candidate_info: None,
+            character_map: None,
+            shape_lookup: None,
+        }
+    }
+
+    fn key_event(raw_code: u32, keysym: u32) -> KeyEvent {
+        KeyEvent {
+            time: 0,
+            raw_code,
+            keysym: Keysym::new(keysym),
+            utf8: None,
+        }
+    }
+
+    /// The modifiers with Alt set, as Left Alt does and AltGr does not.
+    fn alt() -> Modifiers {
+        Modifiers {
+            alt: true,
+            ..Modifiers::default()
+        }
+    }
+
+    #[test]
+    fn sided_modifiers_parse() {
+        let right_alt_s = hotkey("RightAlt+S");
+        assert_eq!(right_alt_s.alt, Held::Right);
+        assert_eq!(right_alt_s.key, KeyName::Character("s".into()));
+        assert_eq!(hotkey("AltGr+S"), right_alt_s);
+        assert_eq!(hotkey("lctrl+S").ctrl, Held::Left);
+        assert_eq!(hotkey("RightAlt").key, KeyName::Code(ALT.1));
+        assert_eq!(hotkey("RightAlt").alt, Held::No);
+        assert_eq!(hotkey("Alt").key, KeyName::Named(Named::Alt));
+        assert!("RightAlt+".parse::<Hotkey>().is_err());
+    }
+
+    #[test]
+    fn right_alt_matches_though_altgr_does_not_set_alt() {
+        let (s, key) = (key_event(31, 0x73), Key::Character("s".into()));
+        let mut held = HeldKeys::default();
+        held.press(ALT.1);
+        let altgr = Modifiers::default();
+        assert!(hotkey("RightAlt+S").matches(&s, &key, &altgr, &held));
+        assert!(!hotkey("Alt+S").matches(&s, &key, &altgr, &held));
+        assert!(!hotkey("LeftAlt+S").matches(&s, &key, &altgr, &held));
+        held.release(ALT.1);
+        held.press(ALT.0);
+        assert!(hotkey("Alt+S").matches(&s, &key, &alt(), &held));
+        assert!(hotkey("LeftAlt+S").matches(&s, &key, &alt(), &held));
+        assert!(!hotkey("RightAlt+S").matches(&s, &key, &alt(), &held));
+    }
+
+    #[test]
+    fn a_sided_modifier_alone_matches_its_own_key() {
+        let right_alt = hotkey("RightAlt");
+        let key = Key::Named(Named::Alt);
+        let mut held = HeldKeys::default();
+        held.press(ALT.1);
+        for modifiers in [Modifiers::default(), alt()] {
+            assert!(right_alt.matches(&key_event(ALT.1, 0xffea), &key, &modifiers, &held));
+        }
+        held.clear();
+        held.press(ALT.0);
+        assert!(!right_alt.matches(&key_event(ALT.0, 0xffe9), &key, &alt(), &held));
+    }
+
+    #[test]
+    fn hotkeys_print_as_they_parse() {
+        for s in [
+            "Ctrl+Alt+F12",
+            "Shift+Space",
+            "Ctrl+Up",
+            "LeftCtrl+PageDown",
+            "RightAlt+S",
+            "RightAlt",
+            "Super+Escape",
+            "Shift",
+            "`",
+        ] {
+            assert_eq!(hotkey(s).to_string(), s);
+            assert_eq!(hotkey(&hotkey(s).to_string()), hotkey(s));
+        }
+    }
+
+    #[test]
+    fn overlapping_hotkeys() {
+        assert!(hotkey("Ctrl+K").overlaps(&hotkey("Control+k")));
+        assert!(hotkey("LeftCtrl+K").overlaps(&hotkey("Ctrl+K")));
+        assert!(!hotkey("LeftCtrl+K").overlaps(&hotkey("RightCtrl+K")));
+        assert!(!hotkey("Ctrl+K").overlaps(&hotkey("Ctrl+Shift+K")));
+        assert!(!hotkey("Ctrl+K").overlaps(&hotkey("Alt+K")));
+        assert!(!hotkey("Ctrl+K").overlaps(&hotkey("Ctrl+J")));
+    }
+
+    #[test]
+    fn the_default_bindings_do_not_conflict() {
+        assert_eq!(conflicts(&Config::default()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn bindings_shadowing_each_other_conflict() {
+        let conflicts = conflicts(&bound(Hotkeys {
+            add_snippet: Some(hotkey("Ctrl+Alt+S")),
+            force_reset: Some(hotkey("Ctrl+Alt+S")),
+            ..unbound()
+        }));
+        assert_eq!(
+            conflicts,
+            ["hotkeys.force_reset `Ctrl+Alt+S` is also hotkeys.add_snippet `Ctrl+Alt+S`"]
+        );
+    }
+
+    #[test]
+    fn bindings_shadowing_keys_used_while_composing_conflict() {
+        let conflicts = conflicts(&bound(Hotkeys {
+            toggle_mode: Some(hotkey("Shift+Space")),
+            cancel_selection: Some(hotkey("Escape")),
+            add_snippet: Some(hotkey("Ctrl+S")),
+            ..unbound()
+        }));
+        assert_eq!(
+            conflicts,
+            [
+                "hotkeys.toggle_mode `Shift+Space` toggles full-width characters as well",
+                "hotkeys.cancel_selection `Escape` closes the candidate popup as well",
+            ]
+        );
+    }
+
+    #[test]
+    fn tapping_shift_only_conflicts_without_a_toggle_key() {
+        let mut config = bound(unbound());
+        config.gestures.double_tap.push(DoubleTap {
+            key: hotkey("Shift"),
+            action: Action::ToggleFullWidth,
+        });
+        assert_eq!(
+            conflicts(&config),
+            ["gestures.double_tap[0] `Shift` toggles Chinese and English when tapped as well"]
+        );
+        config.hotkeys.toggle_mode = Some(hotkey("Ctrl+Space"));
+        assert_eq!(conflicts(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reserved_combinations_may_never_arrive() {
+        let conflicts = conflicts(&bound(Hotkeys {
+            low_latency: Some(hotkey("Ctrl+Alt+F2")),
+            add_snippet: Some(hotkey("Super+S")),
+            force_reset: Some(hotkey("Ctrl+Alt+Backspace")),
+            ..unbound()
+        }));
+        assert_eq!(
+            conflicts,
+            [
+                "hotkeys.force_reset `Ctrl+Alt+Backspace` may never arrive: is usually handled by the system",
+                "hotkeys.low_latency `Ctrl+Alt+F2` may never arrive: switches virtual terminals",
+                "hotkeys.add_snippet `Super+S` may never arrive: Super combinations are usually compositor bindings",
+            ]
+        );
+    }
+
+    #[test]
+    fn bindings_are_listed_in_the_order_the_handler_tries_them() {
+        let key: Option<Hotkey> = "Ctrl+Alt+K".parse().ok();
+        let config = Config {
+            hotkeys: Hotkeys {
+                toggle_mode: key.clone(),
+                low_latency: key.clone(),
+                pin_popup: key.clone(),
+                cancel_selection: key.clone(),
+                add_snippet: key.clone(),
+                force_reset: key.clone(),
+                candidate_info: key.clone(),
+                character_map: key.clone(),
+                shape_lookup: key,
+            },
+            ..Config::default()
+        };
+        let actions: Vec<Action> = bindings(&config)
+            .iter()
+            .map(|binding| binding.action)
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                Action::ForceReset,
+                Action::ToggleLowLatency,
+                Action::ShapeLookup,
+                Action::AddSnippet,
+                Action::ToggleMode,
+                Action::CandidateInfo,
+                Action::CharacterMap,
+                Action::PinPopup,
+                Action::CancelSelection,
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Keeps the screen from blanking in the middle of a composition with the
+//! idle-inhibit protocol, while something is composed and a key was typed
+//! within `session.inhibit_idle_timeout` seconds. The protocol inhibits
+//! idle while a given surface is visible, and the popup's surface belongs to
+//! the toolkit, so a surface of one transparent pixel is shown for it on a
+//! connection of its own.
+use std::{
+    env, fs,
+    io::{self, Write},
+    os::fd::AsFd,
+    sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_compositor::WlCompositor,
+        wl_region::WlRegion,
+        wl_registry::WlRegistry,
+        wl_shm::{Format, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, QueueHandle,
+};
+use wayland_protocols::wp::idle_inhibit::zv1::client::{
+    zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
+};
+
+struct Activity {
+    composing: bool,
+    last_key: Instant,
+    timeout: Duration,
+}
+
+static ACTIVITY: OnceLock<(Mutex<Activity>, Condvar)> = OnceLock::new();
+
+fn activity() -> &'static (Mutex<Activity>, Condvar) {
+    ACTIVITY.get_or_init(|| {
+        let activity = Activity {
+            composing: false,
+            last_key: Instant::now(),
+            timeout: Duration::ZERO,
+        };
+        // Started with the first composition, it waits for changes
+        // rather than holding up the UI with Wayland roundtrips
+        if let Err(error) = thread::Builder::new()
+            .name("idle-inhibit".into())
+            .spawn(inhibit_while_composing)
+        {
+            warn!("cannot inhibit idle: {error}");
+        }
+        (Mutex::new(activity), Condvar::new())
+    })
+}
+
+fn lock() -> MutexGuard<'static, Activity> {
+    activity().0.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Inhibits idle while `composing`, until `timeout` has passed since the
+/// last key.
+pub fn set_composing(composing: bool, timeout: Duration) {
+    if !composing && ACTIVITY.get().is_none() {
+        return;
+    }
+    let mut activity = lock();
+    if activity.composing != composing || activity.timeout != timeout {
+        activity.composing = composing;
+        activity.timeout = timeout;
+        activity.last_key = Instant::now();
+        drop(activity);
+        self::activity().1.notify_one();
+    }
+}
+
+/// A key was typed, so the timeout starts over.
+pub fn key() {
+    if ACTIVITY.get().is_none() {
+        return;
+    }
+    let mut activity = lock();
+    activity.last_key = Instant::now();
+    if activity.composing {
+        drop(activity);
+        self::activity().1.notify_one();
+    }
+}
+
+fn inhibit_while_composing() {
+    let mut wayland = None;
+    let mut inhibitor: Option<Inhibitor> = None;
+    let mut activity = lock();
+    loop {
+        let until = activity.last_key + activity.timeout;
+        let now = Instant::now();
+        let wanted = activity.composing && now < until;
+        if wanted != inhibitor.is_some() {
+            drop(activity);
+            if wanted {
+                let inhibited = match &mut wayland {
+                    Some(wayland) => Inhibitor::new(wayland),
+                    None => Wayland::connect()
+                        .and_then(|connected| Inhibitor::new(wayland.insert(connected))),
+                };
+                match inhibited {
+                    Ok(inhibited) => inhibitor = Some(inhibited),
+                    Err(error) => {
+                        warn!("cannot inhibit idle: {error}");
+                        return;
+                    }
+                }
+            } else if let (Some(released), Some(wayland)) = (inhibitor.take(), &mut wayland) {
+                released.release(wayland);
+            }
+            activity = lock();
+            continue;
+        }
+        let (cvar, wait) = (&self::activity().1, until.saturating_duration_since(now));
+        activity = if wanted {
+            cvar.wait_timeout(activity, wait)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0
+        } else {
+            cvar.wait(activity).unwrap_or_else(PoisonError::into_inner)
+        };
+    }
+}
+
+/// The globals the inhibitor needs, on a connection of its own.
+struct Wayland {
+    queue: EventQueue<State>,
+    compositor: WlCompositor,
+    shm: WlShm,
+    layer_shell: ZwlrLayerShellV1,
+    manager: ZwpIdleInhibitManagerV1,
+}
+
+#[derive(Default)]
+struct State {
+    configured: bool,
+}
+
+impl Wayland {
+    fn connect() -> Result<Wayland, String> {
+        let connection = Connection::connect_to_env().map_err(|error| error.to_string())?;
+        let (globals, queue) =
+            registry_queue_init::<State>(&connection).map_err(|error| error.to_string())?;
+        let qh = queue.handle();
+        Ok(Wayland {
+            compositor: globals
+                .bind(&qh, 1..=4, ())
+                .map_err(|_| missing("wl_compositor"))?,
+            shm: globals
+                .bind(&qh, 1..=1, ())
+                .map_err(|_| missing("wl_shm"))?,
+            layer_shell: globals
+                .bind(&qh, 1..=1, ())
+                .map_err(|_| missing("zwlr_layer_shell_v1"))?,
+            manager: globals
+                .bind(&qh, 1..=1, ())
+                .map_err(|_| missing("zwp_idle_inhibit_manager_v1"))?,
+            queue,
+        })
+    }
+}
+
+fn missing(global: &str) -> String {
+    format!("the compositor has no {global}")
+}
+
+/// A transparent pixel in a corner of the screen, not taking any input,
+/// with idle inhibited while it is shown.
+struct Inhibitor {
+    surface: WlSurface,
+    layer: ZwlrLayerSurfaceV1,
+    buffer: WlBuffer,
+    inhibitor: ZwpIdleInhibitorV1,
+}
+
+impl Inhibitor {
+    fn new(wayland: &mut Wayland) -> Result<Inhibitor, String> {
+        let qh = wayland.queue.handle();
+        let surface = wayland.compositor.create_surface(&qh, ());
+        let region = wayland.compositor.create_region(&qh, ());
+        surface.set_input_region(Some(&region));
+        region.destroy();
+        let layer = wayland.layer_shell.get_layer_surface(
+            &surface,
+            None,
+            Layer::Overlay,
+            "chewingwl-idle".into(),
+            &qh,
+            (),
+        );
+        layer.set_size(1, 1);
+        layer.set_anchor(Anchor::Top | Anchor::Left);
+        surface.commit();
+        let mut state = State::default();
+        while !state.configured {
+            wayland
+                .queue
+                .blocking_dispatch(&mut state)
+                .map_err(|error| error.to_string())?;
+        }
+        let buffer = transparent_pixel(&wayland.shm, &qh).map_err(|error| error.to_string())?;
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage(0, 0, 1, 1);
+        let inhibitor = wayland.manager.create_inhibitor(&surface, &qh, ());
+        surface.commit();
+        wayland
+            .queue
+            .roundtrip(&mut state)
+            .map_err(|error| error.to_string())?;
+        Ok(Inhibitor {
+            surface,
+            layer,
+            buffer,
+            inhibitor,
+        })
+    }
+
+    fn release(self, wayland: &mut Wayland) {
+        self.inhibitor.destroy();
+        self.layer.destroy();
+        self.surface.destroy();
+        self.buffer.destroy();
+        if let Err(error) = wayland.queue.roundtrip(&mut State::default()) {
+            warn!("releasing the idle inhibitor: {error}");
+        }
+    }
+}
+
+/// A buffer of one fully transparent ARGB pixel, in a file removed as soon
+/// as the compositor has it.
+fn transparent_pixel(shm: &WlShm, qh: &QueueHandle<State>) -> io::Result<WlBuffer> {
+    let dir = env::var_os("XDG_RUNTIME_DIR").map_or_else(env::temp_dir, Into::into);
+    let path = dir.join(format!("chewingwl-idle-{}", std::process::id()));
+    let mut file = fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    file.write_all(&[0; 4])?;
+    let pool = shm.create_pool(file.as_fd(), 4, qh, ());
+    let buffer = pool.create_buffer(0, 1, 1, 4, Format::Argb8888, qh, ());
+    pool.destroy();
+    Ok(buffer)
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut State,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
+    fn event(
+        state: &mut State,
+        layer: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        if let zwlr_layer_surface_v1::Event::Configure { serial, .. } = event {
+            layer.ack_configure(serial);
+            state.configured = true;
+        }
+    }
+}
+
+delegate_noop!(State: WlCompositor);
+delegate_noop!(State: WlRegion);
+delegate_noop!(State: WlShmPool);
+delegate_noop!(State: ZwlrLayerShellV1);
+delegate_noop!(State: ZwpIdleInhibitManagerV1);
+delegate_noop!(State: ZwpIdleInhibitorV1);
+delegate_noop!(State: ignore WlShm);
+delegate_noop!(State: ignore WlBuffer);
+delegate_noop!(State: ignore WlSurface);
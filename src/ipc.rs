@@ -0,0 +1,225 @@
+//! A line based control socket at `$XDG_RUNTIME_DIR/chewingwl.sock`, driven
+//! by `chewingwlctl`. A client sends one command line and reads the reply
+//! until the connection closes. `watch-candidates` instead keeps the
+//! connection open and streams the candidate list as it changes, if
+//! `popup.export_candidates` is set.
+use std::{
+    any::TypeId,
+    env, fmt, fs,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+use log::warn;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{unix::OwnedWriteHalf, UnixListener},
+    sync::{oneshot, watch},
+};
+
+use crate::Message;
+
+pub fn socket_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("chewingwl.sock"))
+}
+
+/// Whether an input method is running and listening on the socket.
+pub fn is_running() -> bool {
+    socket_path().is_some_and(|path| UnixStream::connect(path).is_ok())
+}
+
+/// Sends `command` to the running input method and returns its reply.
+pub fn request(command: &str) -> io::Result<String> {
+    let path = socket_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set"))?;
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{command}")?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// Where the answer to a request goes. Cloning shares the same connection;
+/// only the first reply is sent.
+#[derive(Clone)]
+pub struct Reply(Arc<Mutex<Option<oneshot::Sender<String>>>>);
+
+impl Reply {
+    pub fn send(&self, text: impl Into<String>) {
+        if let Some(sender) = self.0.lock().unwrap_or_else(PoisonError::into_inner).take() {
+            let _ = sender.send(text.into());
+        }
+    }
+}
+
+impl fmt::Debug for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reply")
+    }
+}
+
+/// The candidate list as shown to the user, for screen readers and overlays.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Candidates {
+    /// Index into `list` of the highlighted candidate, if the popup is open.
+    pub highlighted: Option<usize>,
+    pub list: Vec<String>,
+}
+
+impl Candidates {
+    /// One tab separated line: the highlighted index or `-`, then the
+    /// candidates.
+    pub fn line(&self) -> String {
+        let mut line = self
+            .highlighted
+            .map_or("-".to_string(), |index| index.to_string());
+        for candidate in &self.list {
+            line.push('\t');
+            line.push_str(candidate);
+        }
+        line
+    }
+}
+
+/// Whether `popup.export_candidates` lets the candidate list be watched.
+static EXPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Follows `popup.export_candidates`.
+pub fn set_exported(exported: bool) {
+    EXPORTED.store(exported, Ordering::Relaxed);
+}
+
+pub fn is_exported() -> bool {
+    EXPORTED.load(Ordering::Relaxed)
+}
+
+fn candidates_channel() -> &'static watch::Sender<Candidates> {
+    static CHANNEL: OnceLock<watch::Sender<Candidates>> = OnceLock::new();
+    CHANNEL.get_or_init(|| watch::channel(Candidates::default()).0)
+}
+
+/// The candidate list as last published.
+pub fn candidates() -> Candidates {
+    candidates_channel().borrow().clone()
+}
+
+/// Publishes the candidate list to watchers if it changed.
+pub fn publish_candidates(highlighted: Option<usize>, list: &[String]) {
+    candidates_channel().send_if_modified(|current| {
+        if current.highlighted == highlighted && current.list == list {
+            return false;
+        }
+        // Moving the highlight leaves the list as it is
+        if current.list != list {
+            current.list = list.to_vec();
+        }
+        current.highlighted = highlighted;
+        true
+    });
+}
+
+async fn watch_candidates(mut write: OwnedWriteHalf) {
+    if !is_exported() {
+        let _ = write.write_all(NOT_EXPORTED.as_bytes()).await;
+        return;
+    }
+    let mut candidates = candidates_channel().subscribe();
+    loop {
+        let line = format!("{}\n", candidates.borrow_and_update().line());
+        if write.write_all(line.as_bytes()).await.is_err() || candidates.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// The reply to watchers while `popup.export_candidates` is off.
+pub const NOT_EXPORTED: &str = "candidates are not exported, set popup.export_candidates\n";
+
+#[cfg(feature = "dbus")]
+async fn signal_candidates() -> zbus::Result<()> {
+    let connection = zbus::Connection::session().await?;
+    let mut candidates = candidates_channel().subscribe();
+    while candidates.changed().await.is_ok() {
+        let current = candidates.borrow_and_update().clone();
+        let highlighted = current.highlighted.map_or(-1, |index| index as i32);
+        connection
+            .emit_signal(
+                None::<&str>,
+                "/org/chewingwl/Candidates",
+                "org.chewingwl.Candidates",
+                "Changed",
+                &(highlighted, current.list),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dbus")]
+struct CandidateSignal;
+
+/// Emits the `org.chewingwl.Candidates` D-Bus signal as the candidate list
+/// changes.
+#[cfg(feature = "dbus")]
+pub fn candidate_signal() -> Subscription<Message> {
+    subscription::channel(TypeId::of::<CandidateSignal>(), 1, |_| async {
+        if let Err(error) = signal_candidates().await {
+            warn!("D-Bus candidate signal unavailable: {error}");
+        }
+        std::future::pending().await
+    })
+}
+
+#[cfg(not(feature = "dbus"))]
+pub fn candidate_signal() -> Subscription<Message> {
+    Subscription::none()
+}
+
+struct Ipc;
+
+/// Listens on the control socket, turning each command into
+/// [`Message::Ipc`].
+pub fn subscription() -> Subscription<Message> {
+    subscription::channel(TypeId::of::<Ipc>(), 16, |output| async move {
+        let listener = socket_path().and_then(|path| {
+            let _ = fs::remove_file(&path);
+            UnixListener::bind(&path)
+                .map_err(|error| warn!("cannot listen on {}: {error}", path.display()))
+                .ok()
+        });
+        let Some(listener) = listener else {
+            return std::future::pending().await;
+        };
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let mut output = output.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut command = String::new();
+                if BufReader::new(read).read_line(&mut command).await.is_err() {
+                    return;
+                }
+                let command = command.trim().to_string();
+                if command == "watch-candidates" {
+                    return watch_candidates(write).await;
+                }
+                let (sender, receiver) = oneshot::channel();
+                let reply = Reply(Arc::new(Mutex::new(Some(sender))));
+                if output.send(Message::Ipc(command, reply)).await.is_err() {
+                    return;
+                }
+                if let Ok(text) = receiver.await {
+                    let _ = write.write_all(text.as_bytes()).await;
+                }
+            });
+        }
+    })
+}
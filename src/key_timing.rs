@@ -0,0 +1,212 @@
+//! Opt-in timing of the zhuyin keys, to see which keys of a layout slow the
+//! typing down and whether another layout might suit better. Nothing is
+//! timed unless `stats.key_timing` is set; the figures stay in
+//! `$XDG_STATE_HOME/chewingwl/key-timing`, read with `chewingwlctl
+//! key-timing` and wiped with `chewingwlctl key-timing clear`.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+use crate::{
+    config::{self, Layout},
+    latency::Stats,
+    layout, user_data,
+};
+
+/// Longer gaps between two keys are pauses rather than reaching for a key.
+const PAUSE: Duration = Duration::from_secs(2);
+/// Keys timed between two saves.
+const SAVE_EVERY: u32 = 200;
+/// Presses a key needs before the report weighs it.
+const MIN_COUNT: u32 = 20;
+/// Keys listed as the slowest.
+const SLOWEST: usize = 8;
+
+#[derive(Default)]
+pub struct KeyTiming {
+    /// By layout name and key as labeled on a US keyboard.
+    keys: BTreeMap<(String, u8), Stats>,
+    last: Option<Instant>,
+    unsaved: u32,
+}
+
+impl KeyTiming {
+    pub fn load() -> Self {
+        let mut timing = KeyTiming::default();
+        let Some(path) = path() else {
+            return timing;
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return timing,
+            Err(error) => {
+                warn!("failed to read key timing {}: {error}", path.display());
+                return timing;
+            }
+        };
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [layout, key, count, total, max] = fields[..] else {
+                continue;
+            };
+            let (Ok(key), Ok(count), Ok(total), Ok(max)) =
+                (key.parse(), count.parse(), total.parse(), max.parse())
+            else {
+                continue;
+            };
+            timing.keys.insert(
+                (layout.to_string(), key),
+                Stats {
+                    count,
+                    total: Duration::from_millis(total),
+                    max: Duration::from_millis(max),
+                },
+            );
+        }
+        timing
+    }
+
+    /// Times `key`, typed in `layout`, from the key before it.
+    pub fn key(&mut self, layout: Layout, key: u8) {
+        let now = Instant::now();
+        if let Some(gap) = self.last.map(|last| now - last).filter(|gap| *gap < PAUSE) {
+            self.keys
+                .entry((layout.name().to_string(), key))
+                .or_default()
+                .record(gap);
+            self.unsaved += 1;
+            if self.unsaved >= SAVE_EVERY {
+                self.save();
+            }
+        }
+        self.last = Some(now);
+    }
+
+    pub fn save(&mut self) {
+        if self.unsaved == 0 {
+            return;
+        }
+        self.unsaved = 0;
+        let Some(path) = path() else {
+            return;
+        };
+        let contents: String = self
+            .keys
+            .iter()
+            .map(|((layout, key), stats)| {
+                format!(
+                    "{layout}\t{key}\t{}\t{}\t{}\n",
+                    stats.count,
+                    stats.total.as_millis(),
+                    stats.max.as_millis()
+                )
+            })
+            .collect();
+        user_data::write_later("key timing", path, contents.into_bytes());
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.last = None;
+        self.unsaved = 0;
+        if let Some(path) = path() {
+            user_data::remove_state_file(&path, "key timing");
+        }
+    }
+
+    /// The slowest keys of `layout`, how each layout timed compares, and
+    /// what might be faster.
+    pub fn report(&self, layout: Layout) -> String {
+        let mut report = String::new();
+        let symbols: BTreeMap<u8, String> = layout::preview(layout)
+            .into_iter()
+            .flatten()
+            .map(|(key, symbol)| (key as u8, symbol))
+            .collect();
+        let mut keys: Vec<(u8, &Stats)> = self
+            .keys
+            .iter()
+            .filter(|((name, _), stats)| name == layout.name() && stats.count >= MIN_COUNT)
+            .map(|((_, key), stats)| (*key, stats))
+            .collect();
+        if keys.is_empty() {
+            let _ = writeln!(report, "Not enough typing timed in {layout} yet.");
+        } else {
+            keys.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.average()));
+            let _ = writeln!(report, "Slowest keys in {layout}:");
+            for (key, stats) in keys.iter().take(SLOWEST) {
+                let _ = writeln!(
+                    report,
+                    "  {} {:<4} {:>5} ms  ({} presses)",
+                    *key as char,
+                    symbols.get(key).map_or("", String::as_str),
+                    stats.average().as_millis(),
+                    stats.count
+                );
+            }
+        }
+        let mut layouts = Vec::new();
+        for other in Layout::ALL {
+            let (count, total) = self
+                .keys
+                .iter()
+                .filter(|((name, _), _)| name == other.name())
+                .fold((0, Duration::ZERO), |(count, total), (_, stats)| {
+                    (count + stats.count, total + stats.total)
+                });
+            if count > 0 {
+                layouts.push((*other, total / count, count));
+            }
+        }
+        if layouts.len() > 1 {
+            let _ = writeln!(report, "\nBy layout:");
+            for (other, average, count) in &layouts {
+                let _ = writeln!(
+                    report,
+                    "  {:<16} {:>5} ms a key  ({count} keys)",
+                    other.to_string(),
+                    average.as_millis()
+                );
+            }
+        }
+        if let Some(advice) = advice(layout, &keys) {
+            let _ = writeln!(report, "\n{advice}");
+        }
+        report
+    }
+}
+
+/// Suggests the 26-key layouts when the keys off the letter rows of the
+/// standard layout are markedly slower than the letters.
+fn advice(layout: Layout, keys: &[(u8, &Stats)]) -> Option<String> {
+    if layout != Layout::Standard {
+        return None;
+    }
+    let average = |letters: bool| {
+        let (count, total) = keys
+            .iter()
+            .filter(|(key, _)| key.is_ascii_alphabetic() == letters)
+            .fold((0, Duration::ZERO), |(count, total), (_, stats)| {
+                (count + stats.count, total + stats.total)
+            });
+        (count > 0).then(|| total.as_secs_f64() / count as f64)
+    };
+    let (letters, others) = (average(true)?, average(false)?);
+    let slower = (others / letters - 1.0) * 100.0;
+    (slower >= 25.0).then(|| {
+        format!(
+            "The number row and punctuation keys take {slower:.0}% longer than the letters. \
+             Hsu, ET26 and Dachen CP26 put every zhuyin symbol on the letter keys."
+        )
+    })
+}
+
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("key-timing"))
+}
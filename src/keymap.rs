@@ -0,0 +1,182 @@
+//! `chewingwl keymap`: a printable cheat sheet of the key bindings and the
+//! zhuyin layout in use, built from the same bindings the key handler reads.
+use std::fmt::Write;
+
+use crate::{
+    config::Config,
+    hotkey::{self, Action, Scope},
+    layout,
+};
+
+enum Format {
+    Text,
+    Svg,
+}
+
+/// Key and what it does, by section.
+type Sections = Vec<(&'static str, Vec<(String, String)>)>;
+
+pub fn run(args: &[String]) -> i32 {
+    let format = match args {
+        [] => Format::Text,
+        [flag, format] if flag == "--format" && format == "txt" => Format::Text,
+        [flag, format] if flag == "--format" && format == "svg" => Format::Svg,
+        _ => {
+            eprintln!("usage: chewingwl keymap [--format svg|txt]");
+            return 2;
+        }
+    };
+    let config = Config::load();
+    match format {
+        Format::Text => print!("{}", text(&config)),
+        Format::Svg => print!("{}", svg(&config)),
+    }
+    0
+}
+
+fn sections(config: &Config) -> Sections {
+    let bindings = hotkey::bindings(config);
+    let mut sections: Sections = Vec::new();
+    for (title, scope) in [
+        ("Anywhere", Scope::Always),
+        ("While the candidate popup is shown", Scope::Popup),
+        ("Pressed twice", Scope::DoubleTap),
+    ] {
+        let mut keys: Vec<(String, String)> = bindings
+            .iter()
+            .filter(|binding| binding.scope == scope)
+            .map(|binding| {
+                let key = binding.hotkey.to_string();
+                (key, binding.action.describe().to_string())
+            })
+            .collect();
+        if scope == Scope::Always && config.hotkeys.toggle_mode.is_none() {
+            let toggle = Action::ToggleMode.describe();
+            keys.insert(0, ("Shift, tapped".into(), toggle.into()));
+        }
+        if scope == Scope::Popup {
+            let selection_keys: String = config.popup.selection_keys().iter().collect();
+            keys.push((
+                selection_keys,
+                "pick a candidate of the highlighted page".into(),
+            ));
+            let mut prefixes = String::new();
+            for label in config.popup.selection_labels() {
+                match label.chars().next() {
+                    Some(prefix) if label.chars().count() > 1 && !prefixes.contains(prefix) => {
+                        prefixes.push(prefix)
+                    }
+                    _ => {}
+                }
+            }
+            if !prefixes.is_empty() {
+                keys.push((
+                    format!("{prefixes}, then a selection key"),
+                    "pick a candidate further down the page".into(),
+                ));
+            }
+            keys.extend(
+                [
+                    ("Enter", "pick the highlighted candidate"),
+                    ("Arrows", "move through the candidates"),
+                    ("PageUp, PageDown", "turn the page"),
+                    ("Home, End", "go to the first or last candidate"),
+                    ("Escape", "close the popup"),
+                ]
+                .map(|(key, action)| (key.to_string(), action.to_string())),
+            );
+        }
+        if !keys.is_empty() {
+            sections.push((title, keys));
+        }
+    }
+    sections
+}
+
+fn text(config: &Config) -> String {
+    let mut text = String::new();
+    for (title, keys) in sections(config) {
+        let _ = writeln!(text, "{title}");
+        let width = keys
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, action) in keys {
+            let _ = writeln!(text, "  {key:width$}  {action}");
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "Layout: {}", config.keyboard.layout);
+    for row in layout::preview(config.keyboard.layout) {
+        let keys: Vec<String> = row
+            .iter()
+            .map(|(key, symbol)| format!("{key} {symbol:<2}"))
+            .collect();
+        let _ = writeln!(text, "  {}", keys.join(" ").trim_end());
+    }
+    text
+}
+
+/// Side of a key in the layout diagram, in pixels.
+const KEY: u32 = 44;
+/// Height of a line of text.
+const LINE: u32 = 22;
+const WIDTH: u32 = 620;
+
+fn svg(config: &Config) -> String {
+    let mut body = String::new();
+    let mut y = LINE * 2;
+    for (title, keys) in sections(config) {
+        let _ = writeln!(
+            body,
+            r#"<text x="20" y="{y}" font-weight="bold">{}</text>"#,
+            escape(title)
+        );
+        y += LINE;
+        for (key, action) in keys {
+            let _ = writeln!(
+                body,
+                r#"<text x="30" y="{y}" font-family="monospace">{}</text><text x="220" y="{y}">{}</text>"#,
+                escape(&key),
+                escape(&action)
+            );
+            y += LINE;
+        }
+        y += LINE / 2;
+    }
+    let _ = writeln!(
+        body,
+        r#"<text x="20" y="{y}" font-weight="bold">Layout: {}</text>"#,
+        escape(&config.keyboard.layout.to_string())
+    );
+    y += LINE / 2;
+    for (row, keys) in layout::preview(config.keyboard.layout).iter().enumerate() {
+        // Staggered like the rows of a keyboard
+        let x = 20 + row as u32 * KEY / 3;
+        for (column, (key, symbol)) in keys.iter().enumerate() {
+            let left = x + column as u32 * (KEY + 4);
+            let _ = writeln!(
+                body,
+                r#"<rect x="{left}" y="{y}" width="{KEY}" height="{KEY}" rx="4" fill="none" stroke="black"/><text x="{}" y="{}" font-size="11">{}</text><text x="{}" y="{}" font-size="20" text-anchor="middle">{}</text>"#,
+                left + 4,
+                y + 13,
+                escape(&key.to_string()),
+                left + KEY / 2,
+                y + KEY - 8,
+                escape(symbol)
+            );
+        }
+        y += KEY + 4;
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"14\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n{body}</svg>\n",
+        y + LINE
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,83 @@
+//! Opt-in measurement of key handling latency, broken down by the state the
+//! key arrived in, plus the round trip until the client acknowledges the
+//! preedit with Done.
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+/// Key events between two log reports.
+const REPORT_EVERY: u32 = 100;
+
+/// Count, sum and maximum of a series of durations.
+#[derive(Default)]
+pub struct Stats {
+    pub count: u32,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    pub fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    pub fn average(&self) -> Duration {
+        self.total / self.count.max(1)
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "n={} avg={:?} max={:?}",
+            self.count,
+            self.average(),
+            self.max
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct Latency {
+    by_state: BTreeMap<String, Stats>,
+    done: Stats,
+    awaiting_done: Option<Instant>,
+    unreported: u32,
+}
+
+impl Latency {
+    /// Records the time from receiving a key in `state` to dispatching the
+    /// resulting actions.
+    pub fn key_handled(&mut self, state: String, elapsed: Duration) {
+        self.by_state.entry(state).or_default().record(elapsed);
+        self.unreported += 1;
+        if self.unreported >= REPORT_EVERY {
+            self.report();
+        }
+    }
+
+    /// Starts timing the round trip of a preedit update sent at `sent`.
+    pub fn awaiting_done(&mut self, sent: Instant) {
+        self.awaiting_done.get_or_insert(sent);
+    }
+
+    pub fn done_received(&mut self) {
+        if let Some(sent) = self.awaiting_done.take() {
+            self.done.record(sent.elapsed());
+        }
+    }
+
+    fn report(&mut self) {
+        for (state, stats) in &self.by_state {
+            info!("key latency in {state}: {}", stats.summary());
+        }
+        info!("preedit to Done round trip: {}", self.done.summary());
+        *self = Latency {
+            awaiting_done: self.awaiting_done,
+            ..Latency::default()
+        };
+    }
+}
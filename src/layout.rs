@@ -0,0 +1,182 @@
+//! Zhuyin keyboard layouts: the syllable editor for each, and a preview of
+//! what each key types, generated by feeding the keys through that editor.
+use chewing::editor::{
+    keyboard::{KeyboardLayout, Qwerty},
+    zhuyin_layout::{DaiChien26, Et26, Hsu, Standard, SyllableEditor},
+};
+
+use crate::config::{Layout, Remap};
+
+/// The keyboard rows shown in previews, as the keys are labeled on a US
+/// keyboard.
+pub const ROWS: [&str; 4] = ["1234567890-", "qwertyuiop", "asdfghjkl;", "zxcvbnm,./"];
+
+pub fn syllable_editor(layout: Layout) -> Box<dyn SyllableEditor> {
+    match layout {
+        Layout::Standard => Box::new(Standard::new()),
+        Layout::Hsu => Box::new(Hsu::new()),
+        Layout::Et26 => Box::new(Et26::new()),
+        Layout::DachenCp26 => Box::new(DaiChien26::new()),
+    }
+}
+
+/// Every printable key of a US keyboard but the backtick and backslash,
+/// unshifted then shifted, in the order of [`DVORAK`] and [`COLEMAK`].
+const QWERTY: &[u8] = b"1234567890-=qwertyuiop[]asdfghjkl;'zxcvbnm,./\
+    !@#$%^&*()_+QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>?";
+const DVORAK: &[u8] = b"1234567890[]',.pyfgcrl/=aoeuidhtns-;qjkxbmwvz\
+    !@#$%^&*(){}\"<>PYFGCRL?+AOEUIDHTNS_:QJKXBMWVZ";
+const COLEMAK: &[u8] = b"1234567890-=qwfpgjluy;[]arstdhneio'zxcvbkm,./\
+    !@#$%^&*()_+QWFPGJLUY:{}ARSTDHNEIO\"ZXCVBKM<>?";
+
+/// The first evdev key code of each row of a US keyboard, in the order of
+/// [`QWERTY`].
+const ROW_CODES: [(u32, usize); 4] = [(2, 12), (16, 12), (30, 11), (44, 10)];
+
+/// The key of a US QWERTY keyboard that types `ascii` in the system
+/// keymap. [`Remap::Physical`] goes by [`physical`] instead, and leaves
+/// the keys it does not know as they are.
+pub fn remap(remap: Remap, ascii: u8) -> u8 {
+    let table = match remap {
+        Remap::None | Remap::Physical => return ascii,
+        Remap::Dvorak => DVORAK,
+        Remap::Colemak => COLEMAK,
+    };
+    table
+        .iter()
+        .position(|&key| key == ascii)
+        .map_or(ascii, |index| QWERTY[index])
+}
+
+/// The key of a US QWERTY keyboard where the key with evdev code
+/// `raw_code` sits, which typed `typed` in the system keymap.
+///
+/// A heuristic: the toolkit keeps the compositor's keymap to itself, so the
+/// position comes from a fixed table of the US key codes and the shift
+/// level from `typed`. Keys typed with AltGr are not translated.
+pub fn physical(raw_code: u32, typed: char, shift: bool) -> Option<u8> {
+    let mut start = 0;
+    let mut position = None;
+    for (first, len) in ROW_CODES {
+        if (first..first + len as u32).contains(&raw_code) {
+            position = Some(start + (raw_code - first) as usize);
+        }
+        start += len;
+    }
+    let unshifted = QWERTY[position?];
+    // Caps Lock shows in the case of a typed letter, not in Shift
+    let shift = if typed.is_alphabetic() && unshifted.is_ascii_alphabetic() {
+        typed.is_uppercase()
+    } else {
+        shift
+    };
+    Some(if shift {
+        QWERTY[position? + QWERTY.len() / 2]
+    } else {
+        unshifted
+    })
+}
+
+/// Whether `typed` is a letter of another script than Latin, as typed by
+/// Cyrillic, Greek or Hebrew keymaps, guessing the keymap from that. Such keys mean nothing to zhuyin
+/// where they are labeled, unlike the accented Latin letters of AltGr.
+pub fn other_script(typed: char) -> bool {
+    typed.is_alphabetic() && !matches!(typed, '\0'..='\u{24f}' | '\u{1e00}'..='\u{1eff}')
+}
+
+fn typed(editor: &mut dyn SyllableEditor, keys: &[u8]) -> String {
+    editor.clear();
+    for key in keys {
+        editor.key_press(Qwerty.map_ascii(*key));
+    }
+    editor.read().to_string()
+}
+
+/// What each key of [`ROWS`] types in `layout`, empty for keys typing
+/// nothing. A key typing another symbol when pressed again, as in the
+/// 26-key layouts, shows both.
+pub fn preview(layout: Layout) -> Vec<Vec<(char, String)>> {
+    let mut editor = syllable_editor(layout);
+    // Tone keys type nothing on their own, so they are pressed after the
+    // first key that does
+    let seed = ROWS
+        .iter()
+        .flat_map(|row| row.bytes())
+        .find(|key| !typed(editor.as_mut(), &[*key]).is_empty());
+    ROWS.iter()
+        .map(|row| {
+            row.bytes()
+                .map(|key| {
+                    let mut symbol = typed(editor.as_mut(), &[key]);
+                    if let (true, Some(seed)) = (symbol.is_empty(), seed) {
+                        let alone = typed(editor.as_mut(), &[seed]);
+                        let toned = typed(editor.as_mut(), &[seed, key]);
+                        symbol = toned.strip_prefix(&alone).unwrap_or_default().to_string();
+                    } else if symbol.chars().count() == 1 {
+                        let again = typed(editor.as_mut(), &[key, key]);
+                        if again.chars().count() == 1 && again != symbol {
+                            symbol.push_str(&again);
+                        }
+                    }
+                    (key as char, symbol)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_line_up() {
+        assert_eq!(QWERTY.len(), 90);
+        assert_eq!(DVORAK.len(), QWERTY.len());
+        assert_eq!(COLEMAK.len(), QWERTY.len());
+    }
+
+    #[test]
+    fn remaps_to_qwerty() {
+        // Keys typed where h and j are on QWERTY
+        assert_eq!(remap(Remap::Dvorak, b'd'), b'h');
+        assert_eq!(remap(Remap::Colemak, b'h'), b'h');
+        assert_eq!(remap(Remap::Colemak, b'n'), b'j');
+        assert_eq!(remap(Remap::Dvorak, b'1'), b'1');
+        assert_eq!(remap(Remap::Dvorak, b'`'), b'`');
+        assert_eq!(remap(Remap::None, b'd'), b'd');
+    }
+
+    #[test]
+    fn remaps_by_position() {
+        // KEY_Q on an AZERTY keymap
+        assert_eq!(physical(16, 'a', false), Some(b'q'));
+        assert_eq!(physical(16, 'A', false), Some(b'Q'));
+        assert_eq!(physical(2, '&', false), Some(b'1'));
+        assert_eq!(physical(53, '/', false), Some(b'/'));
+        assert_eq!(physical(53, '?', true), Some(b'?'));
+        assert_eq!(physical(57, ' ', false), None);
+        assert_eq!(remap(Remap::Physical, b' '), b' ');
+    }
+
+    #[test]
+    fn other_scripts_map_by_position() {
+        // KEY_Q and KEY_SEMICOLON on a Russian keymap, Caps Lock on or not
+        assert_eq!(physical(16, 'й', false), Some(b'q'));
+        assert_eq!(physical(16, 'Й', false), Some(b'Q'));
+        assert_eq!(physical(39, 'ж', false), Some(b';'));
+        assert_eq!(physical(39, 'Ж', true), Some(b':'));
+        assert_eq!(physical(57, ' ', false), None);
+    }
+
+    #[test]
+    fn only_letters_of_other_scripts_go_by_position() {
+        assert!(other_script('й'));
+        assert!(other_script('λ'));
+        assert!(other_script('ש'));
+        assert!(!other_script('q'));
+        assert!(!other_script('é'));
+        assert!(!other_script('ł'));
+        assert!(!other_script('€'));
+    }
+}
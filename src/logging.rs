@@ -0,0 +1,109 @@
+//! The process logger. Its filter, in `RUST_LOG` syntax, can be swapped while
+//! running, so `chewingwlctl log-level debug` captures an intermittent issue
+//! without restarting and losing the broken state.
+use std::{
+    env,
+    sync::{OnceLock, PoisonError, RwLock, RwLockReadGuard},
+    time::{Duration, Instant},
+};
+
+use env_logger::{Builder, Logger};
+use log::{Log, Metadata, Record};
+
+struct Swappable(RwLock<Option<Logger>>);
+
+impl Swappable {
+    /// The current logger. One swapped in by a thread that then panicked
+    /// is still whole, so a poisoned lock is no reason to stop logging.
+    fn logger(&self) -> RwLockReadGuard<'_, Option<Logger>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Log for Swappable {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.logger()
+            .as_ref()
+            .is_some_and(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(logger) = self.logger().as_ref() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = self.logger().as_ref() {
+            logger.flush();
+        }
+    }
+}
+
+static LOGGER: Swappable = Swappable(RwLock::new(None));
+/// The filter from `RUST_LOG` at startup.
+static STARTUP: OnceLock<String> = OnceLock::new();
+
+pub fn init() {
+    let filter = STARTUP.get_or_init(|| env::var("RUST_LOG").unwrap_or_default());
+    set_filter(filter);
+    let _ = log::set_logger(&LOGGER);
+}
+
+/// Replaces the filter, e.g. with `debug` or `chewingwl=trace`.
+pub fn set_filter(filter: &str) {
+    let style = env::var("RUST_LOG_STYLE").unwrap_or_default();
+    let logger = Builder::new()
+        .parse_filters(filter)
+        .parse_write_style(&style)
+        .build();
+    log::set_max_level(logger.filter());
+    *LOGGER.0.write().unwrap_or_else(PoisonError::into_inner) = Some(logger);
+}
+
+/// Goes back to the startup filter.
+pub fn reset() {
+    set_filter(STARTUP.get().map_or("", String::as_str));
+}
+
+/// Lets through at most `limit` records a second, so a held key or a
+/// runaway loop cannot flood the journal.
+pub struct RateLimit {
+    limit: u32,
+    second: Option<Instant>,
+    count: u32,
+    dropped: u32,
+}
+
+impl RateLimit {
+    pub const fn new(limit: u32) -> Self {
+        RateLimit {
+            limit,
+            second: None,
+            count: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Whether a record may be written now. On the first one let through
+    /// after a flood, also logs how many were held back.
+    pub fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .second
+            .map_or(true, |start| now - start >= Duration::from_secs(1))
+        {
+            self.second = Some(now);
+            self.count = 0;
+        }
+        if self.count == self.limit {
+            self.dropped += 1;
+            return false;
+        }
+        self.count += 1;
+        if self.dropped > 0 {
+            log::debug!("{} records not logged", std::mem::take(&mut self.dropped));
+        }
+        true
+    }
+}
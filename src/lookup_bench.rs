@@ -0,0 +1,110 @@
+//! `chewingwl bench-lookup`: times the phrase lookups a conversion of
+//! typical sentences makes, querying the dictionary layers one after another
+//! as the editor does and then one thread per layer, to tell whether
+//! parallel lookup would pay off on this machine and dictionary set.
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chewing::{
+    dictionary::{Dictionary, Trie},
+    zhuyin::Syllable,
+};
+
+use crate::{config::Config, dictionary};
+
+/// Sentences in zhuyin, one syllable per word.
+const SENTENCES: [&str; 4] = [
+    "ㄐㄧㄣ ㄊㄧㄢ ㄊㄧㄢ ㄑㄧˋ ㄏㄣˇ ㄏㄠˇ",
+    "ㄨㄛˇ ㄇㄣ˙ ㄇㄧㄥˊ ㄊㄧㄢ ㄒㄧㄚˋ ㄨˇ ㄧˋ ㄑㄧˇ ㄑㄩˋ ㄎㄞ ㄏㄨㄟˋ",
+    "ㄓㄜˋ ㄍㄜ˙ ㄨㄣˋ ㄊㄧˊ ㄒㄩ ㄧㄠˋ ㄗㄞˋ ㄧㄢˊ ㄐㄧㄡˋ ㄧˊ ㄒㄧㄚˋ",
+    "ㄑㄧㄥˇ ㄅㄤ ㄨㄛˇ ㄅㄚˇ ㄓㄜˋ ㄈㄣˋ ㄅㄠˋ ㄍㄠˋ ㄐㄧˋ ㄍㄟˇ ㄌㄠˇ ㄕ",
+];
+/// Longest phrase the conversion looks up, in syllables.
+const MAX_PHRASE: usize = 6;
+const ROUNDS: u32 = 200;
+
+pub fn run() -> i32 {
+    let sentences: Option<Vec<Vec<Syllable>>> = SENTENCES
+        .iter()
+        .map(|sentence| {
+            sentence
+                .split(' ')
+                .map(|syllable| Syllable::from_str(syllable).ok())
+                .collect()
+        })
+        .collect();
+    let Some(sentences) = sentences else {
+        eprintln!("cannot parse the sample sentences");
+        return 1;
+    };
+    let paths = dictionary::select(&Config::load().dictionaries);
+    let layers: Vec<(PathBuf, Trie)> = paths
+        .into_iter()
+        .filter_map(|path| Trie::open(&path).ok().map(|trie| (path, trie)))
+        .collect();
+    if layers.is_empty() {
+        eprintln!("no system dictionary could be opened, see chewingwl dict-info");
+        return 1;
+    }
+    let serial = time_serial(&layers, &sentences);
+    let parallel = time_parallel(&layers, &sentences);
+    println!(
+        "{} layers, {ROUNDS} rounds of {} sentences",
+        layers.len(),
+        sentences.len()
+    );
+    println!("  serial:   {:?} per round", serial / ROUNDS);
+    println!("  parallel: {:?} per round", parallel / ROUNDS);
+    0
+}
+
+/// Every lookup of up to `MAX_PHRASE` syllables starting at each syllable,
+/// returning how many phrases were found.
+fn lookup_all(dictionary: &dyn Dictionary, sentences: &[Vec<Syllable>]) -> usize {
+    let mut found = 0;
+    for sentence in sentences {
+        for start in 0..sentence.len() {
+            for end in start + 1..=sentence.len().min(start + MAX_PHRASE) {
+                found += dictionary.lookup_all_phrases(&sentence[start..end]).len();
+            }
+        }
+    }
+    found
+}
+
+fn time_serial(layers: &[(PathBuf, Trie)], sentences: &[Vec<Syllable>]) -> Duration {
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        for (_, layer) in layers {
+            lookup_all(layer, sentences);
+        }
+    }
+    started.elapsed()
+}
+
+/// Each thread opens its own copy of a layer, only the lookups are timed.
+fn time_parallel(layers: &[(PathBuf, Trie)], sentences: &[Vec<Syllable>]) -> Duration {
+    let barrier = Barrier::new(layers.len() + 1);
+    thread::scope(|scope| {
+        for (path, _) in layers {
+            let barrier = &barrier;
+            scope.spawn(move || {
+                let layer = Trie::open(path).ok();
+                barrier.wait();
+                if let Some(layer) = layer {
+                    for _ in 0..ROUNDS {
+                        lookup_all(&layer, sentences);
+                    }
+                }
+            });
+        }
+        barrier.wait();
+        Instant::now()
+    })
+    .elapsed()
+}
@@ -1,16 +1,19 @@
-#[cfg(feature = "pinyin")]
-use chewing::editor::zhuyin_layout::Pinyin;
-use chewing::{
-    conversion::ChewingEngine,
-    dictionary::{Layered, SystemDictionaryLoader, UserDictionaryLoader},
-    editor::{
-        keyboard::{self, AnyKeyboardLayout, KeyboardLayout, Modifiers as Mods, Qwerty},
-        BasicEditor, Editor, LaxUserFreqEstimate,
-    },
-};
+use backend::Backend;
+use char_map::CharMap;
+use chewing::editor::keyboard::KeyCode;
+use completion::Completions;
+use compose::ComposeState;
+use config::{Config, EscapePolicy, LockPolicy, PopupTheme, Remap, Writing};
+use dictionary::Sources;
+use error_window::ErrorWindow;
+use gesture::{Down, DownKey, Gestures};
+use history::History;
+use history_panel::HistoryPanel;
+use hotkey::{Action, HeldKeys, Hotkey, Scope};
 
 use iced::{
     event::{self, listen_raw, wayland::InputMethodEvent},
+    font,
     keyboard::key::Named,
     wayland::{
         actions::{
@@ -21,144 +24,569 @@ use iced::{
         virtual_keyboard::virtual_keyboard_action,
         InitialSurface,
     },
-    widget::{column, container, row, text},
-    window, Alignment, Application, Color, Command, Element, Event, Settings, Subscription, Theme,
+    widget::{column, container, mouse_area, row, text},
+    window, Alignment, Application, Color, Command, Element, Event, Font, Settings, Subscription,
+    Theme,
 };
 use iced_core::{
-    event::wayland::{InputMethodKeyboardEvent, KeyEvent, Modifiers, RawModifiers},
+    event::wayland::{InputMethodKeyboardEvent, KeyEvent, Modifiers, OutputEvent, RawModifiers},
     keyboard::Key,
     window::Id,
     Border,
 };
 use iced_style::application;
-use selection_field::widget::selection_field;
-use std::{char, cmp::min, fmt::Debug};
+use key_timing::KeyTiming;
+use latency::Latency;
+use logging::RateLimit;
+use memstats::MemStats;
+use osd::Osd;
+use outputs::Outputs;
+use passthrough::Forwarded;
+use policy::Step;
+use selection_field::grid::{self, CandidateGrid, GridStyle};
+use snippet_dialog::SnippetDialog;
+use snippets::Snippets;
+use speech::Speaker;
+use state_overlay::StateOverlay;
+use std::{
+    char,
+    collections::HashSet,
+    fmt::Debug,
+    ops::Range,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use user_data::FlushSchedule;
+use watchdog::Watchdog;
+use wayland_client::protocol::wl_output::WlOutput;
+use wizard::Wizard;
+mod appearance;
+mod backend;
+mod bundle;
+mod char_map;
+mod cli;
+mod completion;
+mod compose;
+mod config;
+mod config_watch;
+mod dictionary;
+mod dnd;
+mod error;
+mod error_window;
+mod focus;
+mod gesture;
+mod history;
+mod history_panel;
+mod hotkey;
+mod idle;
+mod ipc;
+mod key_timing;
+mod keymap;
+mod latency;
+mod layout;
+mod logging;
+mod lookup_bench;
+mod memstats;
+mod osd;
+mod outputs;
+mod passthrough;
+mod policy;
+mod quirks;
+mod screencast;
+mod screenshot;
 mod selection_field;
+mod session;
+mod shape;
+mod snippet_dialog;
+mod snippets;
+mod soak;
+mod speech;
+mod state_overlay;
+mod surface;
+#[cfg(feature = "sync")]
+mod sync;
+mod text_index;
+mod tone;
+mod unihan;
+mod user_data;
+mod variants;
+mod watchdog;
+mod wizard;
 
 fn main() -> iced::Result {
+    logging::init();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+    let profile = args
+        .iter()
+        .position(|arg| arg == "--profile")
+        .and_then(|flag| args.get(flag + 1));
+    let mut config = match profile {
+        Some(name) => Config::load_profile(Some(name)).unwrap_or_else(|error| {
+            eprintln!("{error}");
+            std::process::exit(2);
+        }),
+        None => Config::load(),
+    };
+    config.debug.state_overlay |= args.iter().any(|arg| arg == "--debug-state-overlay");
+    config.debug.log_key_text_flag = args.iter().any(|arg| arg == "--debug-log-key-text");
+    config.debug.log_key_text |= config.debug.log_key_text_flag;
+    if config.debug.log_key_text {
+        log::warn!("the text of keys is logged, passwords included");
+    }
+    // The compositor places the popup at the text cursor and keeps it on
+    // the output, flipping it above the line where it would not fit;
+    // input-method-unstable-v2 has no request to move it, and only tells
+    // the popup where the cursor is relative to itself
     let initial_surface = InputMethodPopupSettings::default();
     let settings = Settings {
+        flags: config,
         initial_surface: InitialSurface::InputMethodPopup(initial_surface),
         ..Settings::default()
     };
-    InputMethod::run(settings)
+    let result = InputMethod::run(settings);
+    if let Err(error) = &result {
+        let error = error::ChewingWlError::Compositor(error.to_string());
+        log::error!("{error}. {}", error.hint());
+    }
+    result
 }
 
-struct Chewing {
-    // kb_compat: KeyboardLayoutCompat,
-    editor: Editor,
-    keyboard: AnyKeyboardLayout,
-}
+/// How long the compact popup takes to fade in a new page.
+const PAGE_FADE: Duration = Duration::from_millis(120);
 
-impl Chewing {
-    fn new() -> Self {
-        let sys_loader = SystemDictionaryLoader::new();
-        let dictionaries = sys_loader.load().expect("System dictionary not found");
-        let user_dictionary = UserDictionaryLoader::new()
-            .load()
-            .expect("User dictionary not found");
-        let abbrev = sys_loader
-            .load_abbrev()
-            .expect("Failed to load abbreviation table");
-        let estimate = LaxUserFreqEstimate::max_from(user_dictionary.as_ref());
-        let dict = Layered::new(dictionaries, user_dictionary);
-        let conversion_engine = Box::new(ChewingEngine::new());
-        let sym_sel = sys_loader
-            .load_symbol_selector()
-            .expect("Failed to load symbol table");
-        let keyboard = AnyKeyboardLayout::Qwerty(Qwerty);
-        #[cfg(feature = "pinyin")]
-        let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
-        #[cfg(feature = "pinyin")]
-        editor.set_syllable_editor(Box::new(Pinyin::hanyu()));
-        #[cfg(not(feature = "pinyin"))]
-        let editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
-        Chewing {
-            // kb_compat,
-            editor,
-            keyboard,
-        }
-    }
-
-    fn preedit(&self) -> String {
-        format!(
-            "{}{}",
-            self.editor.display(),
-            self.editor.syllable_buffer_display()
-        )
-    }
-}
+/// How long the pointer rests on a candidate before its tooltip shows.
+const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+
+/// How long a config file stays unchanged before it is reloaded, as editors
+/// often write one in several steps.
+const RELOAD_DELAY: Duration = Duration::from_millis(300);
+
+/// How long `chewingwlctl log-level` lasts unless given a duration.
+const LOG_LEVEL_MINUTES: u64 = 10;
+
+/// How long the composition rests before its candidates are prefetched.
+/// The editor lives on this thread, so the lookup waits for a pause in
+/// typing instead of following every syllable.
+const PREFETCH_DELAY: Duration = Duration::from_millis(250);
+
+/// Keys logged a second at most, at the debug level.
+const KEY_LOG_RATE: u32 = 30;
 
 struct InputMethod {
-    page: usize,
-    index: usize,
-    chewing: Chewing,
+    chewing: Box<dyn Backend>,
     state: State,
     candidates: Vec<String>,
+    /// The candidates in the editor's order, `candidates` being them as
+    /// arranged by `popup.variants`.
+    listed: Vec<String>,
+    /// The editor's index of each candidate shown.
+    order: Vec<usize>,
+    /// Phrases whose folded variants were unfolded, by [`variants::key`].
+    unfolded: HashSet<String>,
     current_preedit: String,
     cursor_position: usize,
     preedit_len: usize,
-    pages: Vec<Vec<String>>,
-    max_candidates: usize,
-    max_pages: usize,
+    grid: CandidateGrid,
+    /// Shown in the popup instead of candidates, see [`CharMap`].
+    char_map: Option<CharMap>,
+    /// The shape code being typed, see [`shape`].
+    shape: Option<shape::Query>,
+    /// Read on the first shape lookup.
+    shapes: Option<shape::Index>,
+    /// Longer phrases starting with the composition, listed under the
+    /// candidates when `popup.completions` is set.
+    completions: Vec<String>,
+    /// Read when completions are first looked for.
+    completion_index: Option<Completions>,
     popup: bool,
     shift_set: bool,
     passthrough_mode: bool,
+    /// The font of `popup.font.family`.
+    font: Font,
+    /// Whether the desktop prefers dark windows, for `popup.theme =
+    /// "system"`.
+    dark_mode: bool,
+    /// Whether the screen is being shared, watched with
+    /// `popup.hide_while_sharing` only.
+    screen_shared: bool,
+    /// Whether English was on before the session locked, to be restored on
+    /// unlock.
+    mode_before_lock: Option<bool>,
+    flush: FlushSchedule,
+    restored: Option<String>,
+    low_latency: bool,
+    /// Keys pressed in low-latency mode, whose releases are forwarded too.
+    forwarded: Forwarded,
+    pending_preedit: bool,
+    /// Picking a candidate moves on to the next phrase instead of closing
+    /// the popup.
+    pinned: bool,
+    /// Tab changed the segmentation, so the preedit shows phrase separators
+    /// until the next key.
+    show_segments: bool,
+    gestures: Gestures,
+    down: DownKey,
+    /// When the compact popup last changed page, while it fades in.
+    page_changed: Option<Instant>,
+    tooltip_generation: u64,
+    /// Opened for the first candidate tooltip.
+    sources: Option<Sources>,
+    history: History,
+    history_panel: HistoryPanel,
+    snippets: Snippets,
+    snippet_dialog: SnippetDialog,
+    error_window: ErrorWindow,
+    /// The text committed last, for saving it as a snippet.
+    last_commit: String,
+    held: HeldKeys,
+    compose: ComposeState,
+    active: bool,
+    /// Whether the client without text input support was told about since
+    /// the focus or the mode last changed.
+    unsupported_noticed: bool,
+    osd: Osd,
+    outputs: Outputs,
+    latency: Option<Latency>,
+    key_timing: Option<KeyTiming>,
+    state_overlay: Option<StateOverlay>,
+    key_log: RateLimit,
+    /// Bumped on every log level change so an older expiry cannot revert a
+    /// newer level.
+    log_level_generation: u64,
+    /// Bumped on every change of a config file, so only the last of a
+    /// burst reloads it.
+    config_generation: u64,
+    /// A reload that waits for the composition to end.
+    reload_pending: bool,
+    /// When the last preedit update went out, for `preedit.max_rate`.
+    preedit_sent: Option<Instant>,
+    prefetched: Option<Prefetched>,
+    /// Bumped on every preedit update the client is done with, so only the
+    /// last prefetch timer of a burst of keys runs.
+    prefetch_generation: u64,
+    /// Keys typed while the popup was open, see
+    /// [`config::StrayKeyPolicy::Buffer`].
+    buffered_keys: Vec<u8>,
+    /// Enter and Escape typed while waiting on the client, handled once it
+    /// is done.
+    queued_keys: Vec<(KeyEvent, Key, Modifiers)>,
+    /// A label prefix typed in the popup, waiting for the selection key.
+    label_prefix: Option<char>,
+    watchdog: Watchdog,
+    /// Drawn by `--screenshot-theme`, see [`InputMethod::with_backend`].
+    offscreen: bool,
+    wizard: Wizard,
+    /// Says picked candidates aloud when `speech.enabled` is set.
+    speaker: Option<Speaker>,
+    config: Config,
+}
+
+/// Candidates listed ahead of opening the popup, for the preedit and cursor
+/// they were listed at.
+struct Prefetched {
+    preedit: String,
+    cursor: usize,
+    candidates: Vec<String>,
 }
 
 impl InputMethod {
     fn set_cursor_position(&mut self) {
-        let chars: Vec<char> = self.current_preedit.chars().collect();
-        self.cursor_position = chars[..self.chewing.editor.cursor()]
-            .iter()
-            .collect::<String>()
-            .len()
+        self.cursor_position =
+            text_index::byte_offset(&self.current_preedit, self.chewing.cursor());
     }
 
     fn preedit_string(&mut self) -> Command<Message> {
-        let preedit = self.chewing.preedit();
+        let too_long = self
+            .watchdog
+            .max_preedit()
+            .is_some_and(|max| self.chewing.preedit().len() > max);
+        if too_long && !self.popup && !self.chewing.composing_syllable() {
+            // The client would not take it, so what is composed goes in as
+            // a chunk of its own
+            return self.commit_string();
+        }
+        self.preedit_sent = Some(Instant::now());
+        let preedit = variants::localize(&self.chewing.preedit(), self.config.region);
+        let preedit = tone::present(&preedit, self.config.preedit.tone_marks);
         self.preedit_len = preedit.len();
         self.current_preedit = preedit.clone();
         self.state = State::WaitingForDone;
         self.set_cursor_position();
+        let separator = &self.config.preedit.segment_separator;
+        let (string, cursor) = if self.show_segments && !separator.is_empty() {
+            let starts: Vec<usize> = self.chewing.segments().iter().map(|s| s.start).collect();
+            text_index::with_separators(&preedit, &starts, self.chewing.cursor(), separator)
+        } else {
+            (preedit, self.cursor_position)
+        };
         Command::batch(vec![
             input_method_action(ActionInner::SetPreeditString {
-                string: preedit,
-                cursor_begin: self.cursor_position as i32,
-                cursor_end: self.cursor_position as i32,
+                string,
+                cursor_begin: cursor as i32,
+                cursor_end: cursor as i32,
             }),
             input_method_action(ActionInner::Commit),
         ])
     }
 
+    /// Handles the keys queued while waiting on the client, in order.
+    fn replay_queued_keys(&mut self) -> Command<Message> {
+        let queued = std::mem::take(&mut self.queued_keys);
+        let commands = queued
+            .into_iter()
+            .map(|(key_event, key, modifiers)| {
+                self.handle(Message::KeyPressed(key_event, key, modifiers))
+            })
+            .collect();
+        Command::batch(commands)
+    }
+
+    /// Sends the preedit held back while the client caught up, or schedules
+    /// it so updates stay within `preedit.max_rate` or the focused
+    /// client's `preedit.client_max_rate`.
+    fn send_pending_preedit(&mut self) -> Command<Message> {
+        let rate = self
+            .watchdog
+            .client()
+            .and_then(|client| self.config.preedit.client_max_rate.get(client))
+            .copied()
+            .unwrap_or(self.config.preedit.max_rate);
+        let interval = match rate {
+            // Done is answered at once for a degraded client, so a held back
+            // preedit would keep the state waiting for good
+            _ if self.watchdog.is_degraded() => Duration::ZERO,
+            0 => Duration::ZERO,
+            rate => Duration::from_secs(1) / rate,
+        };
+        let since = self.preedit_sent.map_or(interval, |sent| sent.elapsed());
+        if since >= interval {
+            self.pending_preedit = false;
+            return self.preedit_string();
+        }
+        Command::perform(tokio::time::sleep(interval - since), |_| {
+            Message::PreeditDue
+        })
+    }
+
     fn commit_string(&mut self) -> Command<Message> {
-        let commit_string = self.chewing.preedit();
+        let commit_string = variants::localize(&self.chewing.preedit(), self.config.region);
+        self.last_commit.clone_from(&commit_string);
         self.state = State::PassThrough;
-        self.chewing
-            .editor
-            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Enter));
+        self.chewing.process_key(KeyCode::Enter);
+        // A one-off timer rather than a ticking subscription, so an idle
+        // daemon does not wake up and redraw every second
+        let flush = if self.flush.committed() {
+            // Queued behind any keys that already arrived
+            Command::perform(async {}, |_| Message::FlushDue)
+        } else {
+            self.flush.idle_delay().map_or_else(Command::none, |delay| {
+                Command::perform(tokio::time::sleep(delay), |_| Message::FlushTick)
+            })
+        };
         Command::batch(vec![
             input_method_action(ActionInner::CommitString(commit_string)),
             input_method_action(ActionInner::Commit),
+            flush,
+        ])
+    }
+
+    /// Forwards a press, so that its release follows wherever the state
+    /// has moved on to by then.
+    fn forward_press(&mut self, key_event: KeyEvent) -> Command<Message> {
+        self.forwarded.press(key_event.raw_code);
+        virtual_keyboard_action(VKActionInner::KeyPressed(key_event))
+    }
+
+    /// Carries out `steps` for the key of `key_event`, in order.
+    fn carry_out(&mut self, steps: &[Step], key_event: KeyEvent) -> Command<Message> {
+        let mut commands = Vec::new();
+        for step in steps {
+            commands.push(match *step {
+                Step::LeavePopup => {
+                    self.leave_popup();
+                    hide_input_method_popup()
+                }
+                Step::Commit => self.commit_string(),
+                Step::Cancel => {
+                    self.chewing.clear();
+                    self.compose.reset();
+                    self.preedit_string()
+                }
+                Step::Reset => {
+                    self.chewing.clear();
+                    self.compose.reset();
+                    Command::none()
+                }
+                Step::FullWidthSpace => Command::batch(vec![
+                    input_method_action(ActionInner::CommitString("\u{3000}".to_string())),
+                    input_method_action(ActionInner::Commit),
+                ]),
+                Step::Type(ascii) => {
+                    self.chewing.process_ascii(ascii);
+                    self.preedit_string()
+                }
+                Step::Buffer(ascii) => {
+                    self.buffered_keys.push(ascii);
+                    Command::none()
+                }
+                Step::Forward => self.forward_press(key_event.clone()),
+            });
+        }
+        Command::batch(commands)
+    }
+
+    /// A Ctrl or Alt shortcut, sent to the application once the composition
+    /// is settled as [`config::ShortcutPolicy`] says.
+    fn shortcut(&mut self, key_event: KeyEvent) -> Command<Message> {
+        let steps = policy::shortcut(
+            self.config.keys.shortcut_while_composing,
+            !self.chewing.preedit().is_empty(),
+            self.popup,
+        );
+        self.carry_out(&steps, key_event)
+    }
+
+    /// A key of a dead key or Compose sequence typed while composing. The
+    /// client composes the sequence, so what is composed goes in first and
+    /// the key after it.
+    fn compose_key(&mut self, key_event: KeyEvent) -> Command<Message> {
+        let forward = self.forward_press(key_event);
+        if self.chewing.preedit().is_empty() {
+            return forward;
+        }
+        Command::batch(vec![self.commit_string(), forward])
+    }
+
+    /// Enter in Chinese mode with nothing composed, see [`config::EnterPolicy`].
+    fn enter_when_empty(&mut self, key_event: KeyEvent) -> Command<Message> {
+        let steps = policy::enter_when_empty(self.config.keys.enter_when_empty);
+        self.carry_out(&steps, key_event)
+    }
+
+    fn flush_user_data(&mut self) {
+        self.chewing.flush();
+        self.flush.flushed();
+    }
+
+    /// Saves the unfinished composition and everything learned, and waits
+    /// for the files to be written.
+    fn save_for_exit(&mut self) {
+        if self.config.session.restore {
+            let unfinished = self
+                .restored
+                .take()
+                .unwrap_or_else(|| variants::localize(&self.chewing.preedit(), self.config.region));
+            if !unfinished.is_empty() {
+                session::save(&unfinished);
+            }
+        }
+        self.flush_user_data();
+        if let Some(timing) = &mut self.key_timing {
+            timing.save();
+        }
+        user_data::finish_writes();
+    }
+
+    /// What `hotkeys.add_snippet` saves: the composition, or the last
+    /// commit if nothing is being composed.
+    fn snippet_text(&self) -> String {
+        if matches!(self.state, State::PreEdit) {
+            variants::localize(&self.chewing.preedit(), self.config.region)
+        } else {
+            self.last_commit.clone()
+        }
+    }
+
+    /// Shows the composition saved by the previous run as preedit.
+    fn offer_restored(&mut self, text: String) -> Command<Message> {
+        self.current_preedit = text.clone();
+        self.cursor_position = text.len();
+        self.state = State::WaitingForDone;
+        Command::batch(vec![
+            input_method_action(ActionInner::SetPreeditString {
+                string: text,
+                cursor_begin: self.cursor_position as i32,
+                cursor_end: self.cursor_position as i32,
+            }),
+            input_method_action(ActionInner::Commit),
+        ])
+    }
+
+    /// Enter commits the restored composition, any other key discards it.
+    fn resolve_restored(&mut self, key: &Key) -> Command<Message> {
+        let restored = self.restored.take().unwrap_or_default();
+        self.current_preedit.clear();
+        self.state = State::WaitingForDone;
+        let action = if *key == Key::Named(Named::Enter) {
+            ActionInner::CommitString(restored)
+        } else {
+            ActionInner::SetPreeditString {
+                string: String::new(),
+                cursor_begin: 0,
+                cursor_end: 0,
+            }
+        };
+        Command::batch(vec![
+            input_method_action(action),
+            input_method_action(ActionInner::Commit),
         ])
     }
 
+    /// Entering low-latency mode commits any composition and leaves every
+    /// key to the virtual keyboard until toggled off.
+    fn toggle_low_latency(&mut self) -> Command<Message> {
+        self.low_latency = !self.low_latency;
+        if !self.low_latency {
+            return Command::none();
+        }
+        self.popup = false;
+        let commit = if self.chewing.preedit().is_empty() {
+            Command::none()
+        } else {
+            self.commit_string()
+        };
+        self.chewing.clear();
+        self.current_preedit.clear();
+        self.state = State::PassThrough;
+        Command::batch(vec![commit, hide_input_method_popup()])
+    }
+
+    fn open_popup_from_preedit(&mut self) -> Command<Message> {
+        let batch = self.config.popup.batch_selection;
+        let len = self.chewing.preedit().chars().count();
+        if policy::batch_from_start(batch, self.chewing.cursor(), len) {
+            self.chewing.process_key(KeyCode::Home);
+        }
+        self.open_popup()
+    }
+
     fn open_popup(&mut self) -> Command<Message> {
         let preedit = self.chewing.preedit();
-        self.chewing
-            .editor
-            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Down));
-        self.candidates = self.chewing.editor.all_candidates().unwrap_or_default();
+        let prefetched = self
+            .prefetched
+            .take()
+            .filter(|p| p.preedit == preedit && p.cursor == self.chewing.cursor());
+        self.chewing.process_key(KeyCode::Down);
+        let candidates = match prefetched {
+            Some(prefetched) => prefetched.candidates,
+            None => self.chewing.candidates(),
+        };
         self.state = State::WaitingForDone;
         self.popup = true;
         self.set_cursor_position();
-        self.index = 0;
-        self.page = 0;
-        self.pages =
-            vec![self.candidates[0..min(self.max_candidates, self.candidates.len())].to_vec()];
+        self.list_candidates(candidates);
+        self.completions = if self.config.popup.completions {
+            self.find_completions()
+        } else {
+            Vec::new()
+        };
         Command::batch(vec![
             input_method_action(ActionInner::SetPreeditString {
-                string: preedit,
+                string: variants::localize(&preedit, self.config.region),
                 cursor_begin: self.cursor_position as i32,
                 cursor_end: self.cursor_position as i32,
             }),
@@ -166,18 +594,504 @@ impl InputMethod {
         ])
     }
 
+    /// Walks the editor's cursor onto the character at `index`.
+    fn move_cursor(&mut self, index: usize) {
+        let cursor = self.chewing.cursor();
+        let key = if index < cursor {
+            KeyCode::Left
+        } else {
+            KeyCode::Right
+        };
+        for _ in 0..index.abs_diff(cursor) {
+            self.chewing.process_key(key);
+        }
+    }
+
+    /// Starts the timer for [`Self::prefetch`], replacing any earlier one.
+    fn schedule_prefetch(&mut self) -> Command<Message> {
+        self.prefetch_generation += 1;
+        let generation = self.prefetch_generation;
+        Command::perform(tokio::time::sleep(PREFETCH_DELAY), move |_| {
+            Message::Prefetch(generation)
+        })
+    }
+
+    /// Lists the candidates at the cursor while the user is still looking at
+    /// the preedit, so the popup opens without the dictionary lookup; the
+    /// list is taken as is by [`Self::open_popup`].
+    fn prefetch(&mut self) {
+        // A shape code is typed outside the editor
+        if !matches!(self.state, State::PreEdit)
+            || self.chewing.composing_syllable()
+            || self.shape.is_some()
+        {
+            return;
+        }
+        let preedit = self.chewing.preedit();
+        let cursor = self.chewing.cursor();
+        if self
+            .prefetched
+            .as_ref()
+            .is_some_and(|p| p.preedit == preedit && p.cursor == cursor)
+        {
+            return;
+        }
+        self.chewing.process_key(KeyCode::Down);
+        let candidates = self.chewing.candidates();
+        self.chewing.process_key(KeyCode::Esc);
+        self.prefetched = Some(Prefetched {
+            preedit,
+            cursor,
+            candidates,
+        });
+    }
+
+    /// Backs out of candidate selection, keeping the composition.
+    fn leave_popup(&mut self) {
+        self.chewing.process_key(KeyCode::Esc);
+        self.char_map = None;
+        self.shape = None;
+        self.popup = false;
+        self.pinned = false;
+        self.buffered_keys.clear();
+    }
+
+    /// A printable key that picks no candidate, see [`config::StrayKeyPolicy`].
+    fn stray_in_popup(&mut self, key_event: KeyEvent, ascii: u8) -> Command<Message> {
+        let steps = policy::stray_in_popup(self.config.keys.stray_in_popup, ascii);
+        self.carry_out(&steps, key_event)
+    }
+
+    /// Where the just picked `text` sits in the buffer: its occurrence
+    /// nearest the cursor.
+    fn picked_range(&self, text: &str) -> Range<usize> {
+        let chars: Vec<char> = self.chewing.preedit().chars().collect();
+        let picked: Vec<char> = text.chars().collect();
+        let cursor = self.chewing.cursor();
+        (0..=chars.len().saturating_sub(picked.len()))
+            .filter(|&start| chars[start..].starts_with(&picked))
+            .min_by_key(|&start| start.abs_diff(cursor))
+            .map_or(0..0, |start| start..start + picked.len())
+    }
+
+    /// Opacity of the active page while the compact popup fades it in.
+    fn page_fade(&self) -> f32 {
+        self.page_changed.map_or(1.0, |changed| {
+            (changed.elapsed().as_secs_f32() / PAGE_FADE.as_secs_f32()).min(1.0)
+        })
+    }
+
+    /// The highlighted candidate in full, with its reading and the
+    /// dictionary it was found in.
+    fn tooltip(&mut self) -> String {
+        let Some(text) = self.candidates.get(self.grid.position()) else {
+            return String::new();
+        };
+        if self.char_map.is_some() || self.shape.is_some() {
+            return unihan::describe(text);
+        }
+        let cursor = self.chewing.cursor();
+        let reading = self.chewing.reading(cursor..cursor + text.chars().count());
+        let config = &self.config.dictionaries;
+        let sources = self.sources.get_or_insert_with(|| Sources::open(config));
+        let source = sources
+            .find(&reading, text)
+            .unwrap_or("user dictionary or symbols");
+        let reading = tone::present(&reading, self.config.preedit.tone_marks);
+        format!("{text}\n{reading}\n{source}")
+    }
+
+    /// Lists the characters of the character map's current block.
+    fn show_block(&mut self) {
+        if let Some(char_map) = &self.char_map {
+            self.candidates = char_map.characters();
+            self.grid.reset(self.candidates.len());
+        }
+    }
+
+    /// `key` as it would be in columns: a candidate bar turns the arrows
+    /// a quarter, moving the highlight with Left and Right, and vertical
+    /// writing mirrors that, the candidates running right to left.
+    fn oriented(&self, key: &Key) -> Key {
+        let vertical = self.config.popup.writing == Writing::Vertical;
+        if !self.config.popup.bar && !vertical {
+            return key.clone();
+        }
+        let (previous, next) = if vertical {
+            (Named::ArrowRight, Named::ArrowLeft)
+        } else {
+            (Named::ArrowLeft, Named::ArrowRight)
+        };
+        match key {
+            Key::Named(named) if *named == previous => Key::Named(Named::ArrowUp),
+            Key::Named(named) if *named == next => Key::Named(Named::ArrowDown),
+            Key::Named(Named::ArrowUp) => Key::Named(Named::ArrowLeft),
+            Key::Named(Named::ArrowDown) => Key::Named(Named::ArrowRight),
+            _ => key.clone(),
+        }
+    }
+
+    /// Moves through the candidates for a key [`is_page_key`] accepts: Left
+    /// and PageUp turn to the previous page, Right and PageDown to the next,
+    /// Home and End go to the first and last candidate.
+    fn page_key(&mut self, key: Named) {
+        let len = self.candidates.len();
+        match key {
+            Named::ArrowLeft | Named::PageUp => self.grid.previous_page(len),
+            Named::ArrowRight | Named::PageDown => self.grid.next_page(len),
+            Named::Home => self.grid.first(len),
+            Named::End => self.grid.last(len),
+            _ => {}
+        }
+    }
+
+    /// Keys while the popup lists characters from the character map or a
+    /// shape lookup rather than the editor.
+    fn browse_key(&mut self, key: &Key, modifiers: &Modifiers) -> Command<Message> {
+        let len = self.candidates.len();
+        let total_pages = self.grid.total_pages(len);
+        let prefix = self.label_prefix.take();
+        match self.oriented(key).as_ref() {
+            Key::Character(typed) if prefix.is_none() && self.is_label_prefix(typed) => {
+                self.label_prefix = single_char(typed);
+                Command::none()
+            }
+            Key::Character(typed) => match self.selection_index(prefix, typed) {
+                Some(index) => self.pick_character(index),
+                None => Command::none(),
+            },
+            Key::Named(Named::Enter) => self.pick_character(self.grid.index),
+            Key::Named(Named::ArrowDown) => {
+                if self.grid.index + 1 < self.grid.page_len(len) {
+                    self.grid.index += 1;
+                } else if self.grid.page + 1 < total_pages {
+                    self.grid.next_page(len);
+                    self.grid.index = 0;
+                }
+                Command::none()
+            }
+            Key::Named(Named::ArrowUp) => {
+                self.grid.index = self.grid.index.saturating_sub(1);
+                Command::none()
+            }
+            Key::Named(named) if is_page_key(named) => {
+                self.page_key(named);
+                Command::none()
+            }
+            Key::Named(Named::Tab) => {
+                if let Some(char_map) = &mut self.char_map {
+                    if modifiers.shift {
+                        char_map.previous_block();
+                    } else {
+                        char_map.next_block();
+                    }
+                }
+                self.show_block();
+                Command::none()
+            }
+            // Back to the candidates the map was opened from
+            Key::Named(Named::Escape) if self.char_map.is_some() => {
+                self.char_map = None;
+                let candidates = self.chewing.candidates();
+                self.list_candidates(candidates);
+                Command::none()
+            }
+            // Back to typing the shape code
+            Key::Named(Named::Escape) => {
+                self.popup = false;
+                Command::batch(vec![self.shape_preedit(), hide_input_method_popup()])
+            }
+            _ => Command::none(),
+        }
+    }
+
+    /// Keys while a shape code is typed.
+    fn shape_key(&mut self, key: &Key) -> Command<Message> {
+        let Some(query) = &mut self.shape else {
+            return Command::none();
+        };
+        match key.as_ref() {
+            Key::Character(typed) => {
+                let mut chars = typed.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(typed), None) if query.push(typed) => self.shape_preedit(),
+                    _ => Command::none(),
+                }
+            }
+            Key::Named(Named::Backspace) => match query.keys.pop() {
+                Some(_) => self.shape_preedit(),
+                None => self.end_shape(),
+            },
+            Key::Named(Named::Tab) => {
+                query.switch();
+                self.shape_preedit()
+            }
+            Key::Named(Named::Escape) => self.end_shape(),
+            Key::Named(Named::Space | Named::Enter | Named::ArrowDown) => self.open_shape_results(),
+            _ => Command::none(),
+        }
+    }
+
+    /// Shows the shape code typed so far in place of a composition.
+    fn shape_preedit(&mut self) -> Command<Message> {
+        let preedit = self
+            .shape
+            .as_ref()
+            .map(shape::Query::preedit)
+            .unwrap_or_default();
+        let cursor = preedit.len() as i32;
+        self.current_preedit.clone_from(&preedit);
+        self.state = State::WaitingForDone;
+        Command::batch(vec![
+            input_method_action(ActionInner::SetPreeditString {
+                string: preedit,
+                cursor_begin: cursor,
+                cursor_end: cursor,
+            }),
+            input_method_action(ActionInner::Commit),
+        ])
+    }
+
+    fn end_shape(&mut self) -> Command<Message> {
+        self.shape = None;
+        self.current_preedit.clear();
+        self.state = State::WaitingForDone;
+        Command::batch(vec![
+            input_method_action(ActionInner::SetPreeditString {
+                string: String::new(),
+                cursor_begin: 0,
+                cursor_end: 0,
+            }),
+            input_method_action(ActionInner::Commit),
+        ])
+    }
+
+    /// Lists the characters matching the shape code in the popup.
+    fn open_shape_results(&mut self) -> Command<Message> {
+        let (Some(query), Some(index)) = (&self.shape, &self.shapes) else {
+            return Command::none();
+        };
+        let found = index.find(query);
+        if query.keys.is_empty() || found.is_empty() {
+            return Command::none();
+        }
+        self.candidates = found;
+        self.grid.reset(self.candidates.len());
+        self.popup = true;
+        // Shown once the client acknowledges the preedit
+        self.shape_preedit()
+    }
+
+    /// Commits the composition with the character at `index` on the
+    /// character map's page appended.
+    fn pick_character(&mut self, index: usize) -> Command<Message> {
+        if index >= self.grid.page_len(self.candidates.len()) {
+            return Command::none();
+        }
+        let character = self.candidates[self.grid.position_of(index)].clone();
+        self.leave_popup();
+        let text = variants::localize(&self.chewing.preedit(), self.config.region) + &character;
+        self.commit_instead(text)
+    }
+
+    /// Longer phrases starting with the whole composition, for a
+    /// composition of syllables only.
+    fn find_completions(&mut self) -> Vec<String> {
+        if self.masked() {
+            return Vec::new();
+        }
+        let length = self.chewing.preedit().chars().count();
+        let reading = self.chewing.reading(0..length);
+        if reading.is_empty() || reading.split(' ').count() != length {
+            return Vec::new();
+        }
+        let config = &self.config.dictionaries;
+        let index = self
+            .completion_index
+            .get_or_insert_with(|| Completions::open(config));
+        let chewing = &mut self.chewing;
+        let region = self.config.region;
+        index
+            .find(&reading, |reading, phrase| {
+                chewing.user_frequency(reading, phrase)
+            })
+            .iter()
+            .map(|phrase| variants::localize(phrase, region))
+            .collect()
+    }
+
+    /// Shows the completions of the composition while typing, alone in the
+    /// popup surface, and hides it again once there are none.
+    fn show_completions(&mut self) -> Command<Message> {
+        if !self.config.popup.completions {
+            return Command::none();
+        }
+        let shown = !self.completions.is_empty();
+        self.completions = self.find_completions();
+        match (shown, self.completions.is_empty()) {
+            (_, false) => show_input_method_popup(),
+            (true, true) => hide_input_method_popup(),
+            (false, true) => Command::none(),
+        }
+    }
+
+    /// The frame of the popup, styled by `popup.style`.
+    fn popup_container<'a>(&self, content: Element<'a, Message>) -> Element<'a, Message> {
+        container(content)
+            .padding(5.0)
+            .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+                Box::new(CustomTheme(self.config.popup.style.clone())),
+            ))
+            .into()
+    }
+
+    /// The completions, numbered for Ctrl and their number.
+    fn completion_row(&self) -> Element<Message> {
+        let completions = self.completions.iter().enumerate().map(|(n, phrase)| {
+            text(format!("{} {phrase}", n + 1))
+                .size(24)
+                .font(self.font)
+                .into()
+        });
+        row(std::iter::once(text("Ctrl+").size(16).into())
+            .chain(completions)
+            .collect::<Vec<_>>())
+        .spacing(12.0)
+        .padding(4.0)
+        .align_items(Alignment::Center)
+        .into()
+    }
+
+    /// Commits the completion at `index` in place of the composition.
+    fn pick_completion(&mut self, index: usize) -> Command<Message> {
+        let Some(text) = self.completions.get(index).cloned() else {
+            return Command::none();
+        };
+        if self.popup {
+            self.leave_popup();
+        }
+        self.completions.clear();
+        self.commit_instead(text)
+    }
+
+    /// Commits `text` rather than what the editor holds, dropping the
+    /// composition.
+    fn commit_instead(&mut self, text: String) -> Command<Message> {
+        self.chewing.clear();
+        self.last_commit.clone_from(&text);
+        self.state = State::PassThrough;
+        Command::batch(vec![
+            input_method_action(ActionInner::CommitString(text)),
+            input_method_action(ActionInner::Commit),
+            hide_input_method_popup(),
+        ])
+    }
+
+    /// Shows the editor's `candidates`, arranged per `popup.variants`, from
+    /// the first page.
+    fn list_candidates(&mut self, candidates: Vec<String>) {
+        let region = self.config.region;
+        self.listed = candidates
+            .iter()
+            .map(|candidate| variants::localize(candidate, region))
+            .collect();
+        self.unfolded.clear();
+        let folded = self.arrange_candidates();
+        self.grid.reset(self.candidates.len());
+        self.grid.fold(folded);
+        self.annotate_candidates();
+        self.label_prefix = None;
+    }
+
+    fn arrange_candidates(&mut self) -> Vec<usize> {
+        let arranged = variants::arrange(&self.listed, self.config.popup.variants, &self.unfolded);
+        self.candidates = arranged
+            .order
+            .iter()
+            .map(|&index| self.listed[index].clone())
+            .collect();
+        self.order = arranged.order;
+        arranged.folded
+    }
+
+    /// Lists the variants folded under the highlighted candidate after it.
+    fn unfold_variants(&mut self) {
+        let position = self.grid.position();
+        if self.grid.folded_at(position) == 0 {
+            return;
+        }
+        self.unfolded
+            .insert(variants::key(&self.candidates[position]));
+        let folded = self.arrange_candidates();
+        self.grid.fold(folded);
+        self.annotate_candidates();
+    }
+
+    /// Hands the grid the reading of each candidate for
+    /// `popup.annotations`: the syllables at the cursor it would replace.
+    fn annotate_candidates(&mut self) {
+        if !self.config.popup.annotations {
+            return;
+        }
+        let cursor = self.chewing.cursor();
+        let readings = self
+            .candidates
+            .iter()
+            .map(|candidate| {
+                let reading = self
+                    .chewing
+                    .reading(cursor..cursor + candidate.chars().count());
+                tone::present(&reading, self.config.preedit.tone_marks)
+            })
+            .collect();
+        self.grid.annotate(readings);
+    }
+
+    /// Which candidate of the page the selection key `typed` picks, after
+    /// the label `prefix` if one was typed.
+    fn selection_index(&self, prefix: Option<char>, typed: &str) -> Option<usize> {
+        let key = single_char(typed)?;
+        let label: String = prefix.into_iter().chain([key]).collect();
+        self.grid.labels().iter().position(|other| *other == label)
+    }
+
+    /// Whether `typed` starts a two-key label on the page.
+    fn is_label_prefix(&self, typed: &str) -> bool {
+        single_char(typed).is_some_and(|key| {
+            self.grid
+                .labels()
+                .iter()
+                .any(|label| label.chars().count() == 2 && label.starts_with(key))
+        })
+    }
+
     fn num_select(&mut self, index: usize) -> Command<Message> {
-        let _ = self
-            .chewing
-            .editor
-            .select(self.page * self.max_candidates + index);
-        self.current_preedit = self.chewing.preedit();
+        let index = self.grid.position_of(index);
+        self.chewing
+            .select(self.order.get(index).copied().unwrap_or(index));
+        if let Some(text) = self.candidates.get(index) {
+            let reading = self.chewing.reading(self.picked_range(text));
+            self.history.record(text, reading, index);
+            if let Some(speaker) = &mut self.speaker {
+                speaker.say(text);
+            }
+        }
+        if self.pinned || self.config.popup.batch_selection {
+            let phrase_len = self.candidates.get(index).map_or(0, |c| c.chars().count());
+            if let Some(command) = self.advance_selection(phrase_len) {
+                return command;
+            }
+        }
+        for ascii in std::mem::take(&mut self.buffered_keys) {
+            self.chewing.process_ascii(ascii);
+        }
+        self.current_preedit = variants::localize(&self.chewing.preedit(), self.config.region);
         self.state = State::WaitingForDone;
         self.popup = false;
         self.set_cursor_position();
         Command::batch(vec![
             input_method_action(ActionInner::SetPreeditString {
-                string: self.chewing.preedit(),
+                string: self.current_preedit.clone(),
                 cursor_begin: self.cursor_position as i32,
                 cursor_end: self.cursor_position as i32,
             }),
@@ -185,6 +1099,1437 @@ impl InputMethod {
             hide_input_method_popup(),
         ])
     }
+
+    /// Moves past the phrase just picked and lists the candidates of the
+    /// next one, keeping the popup up. Unpins at the end of the buffer.
+    fn advance_selection(&mut self, phrase_len: usize) -> Option<Command<Message>> {
+        let preedit = self.chewing.preedit();
+        let len = preedit.chars().count();
+        let cursor = self.chewing.cursor();
+        let auto_shift = self.config.preedit.auto_shift_cursor;
+        let next = policy::next_phrase(cursor, phrase_len, auto_shift, len);
+        // Past the last phrase the cursor is left at the end
+        for _ in cursor..next.unwrap_or(len) {
+            self.chewing.process_key(KeyCode::Right);
+        }
+        if next.is_none() {
+            self.pinned = false;
+            return None;
+        }
+        self.current_preedit = variants::localize(&preedit, self.config.region);
+        Some(self.open_popup())
+    }
+
+    fn is_hotkey(
+        &self,
+        hotkey: &Option<Hotkey>,
+        key_event: &KeyEvent,
+        key: &Key,
+        modifiers: &Modifiers,
+    ) -> bool {
+        hotkey
+            .as_ref()
+            .is_some_and(|h| h.matches(key_event, key, modifiers, &self.held))
+    }
+
+    /// Whether a hotkey that applies right now is bound to the key, which
+    /// then is not forwarded as a function or media key.
+    fn bound_hotkey(&self, key_event: &KeyEvent, key: &Key, modifiers: &Modifiers) -> bool {
+        hotkey::bindings(&self.config).iter().any(|binding| {
+            let applies = match binding.scope {
+                Scope::Always => true,
+                Scope::Popup => matches!(self.state, State::Popup),
+                Scope::DoubleTap => false,
+            };
+            applies
+                && binding
+                    .hotkey
+                    .matches(key_event, key, modifiers, &self.held)
+        })
+    }
+
+    fn perform(&mut self, action: Action) -> Command<Message> {
+        match action {
+            Action::ToggleMode => {
+                let commit = if matches!(self.state, State::PreEdit) {
+                    self.commit_string()
+                } else {
+                    Command::none()
+                };
+                self.passthrough_mode = !self.passthrough_mode;
+                self.last_commit.clear();
+                commit
+            }
+            Action::ToggleFullWidth => {
+                self.chewing.process_shift_key(KeyCode::Space);
+                Command::none()
+            }
+            Action::ToggleLowLatency => self.toggle_low_latency(),
+            Action::PinPopup => {
+                self.pinned = !self.pinned;
+                Command::none()
+            }
+            Action::CancelSelection if self.popup => {
+                self.leave_popup();
+                Command::batch(vec![self.preedit_string(), hide_input_method_popup()])
+            }
+            Action::CancelSelection => Command::none(),
+            Action::AddSnippet => {
+                let text = self.snippet_text();
+                if text.is_empty() {
+                    return Command::none();
+                }
+                self.snippet_dialog.open(text, self.config.surfaces.snippet)
+            }
+            Action::ForceReset => self.force_reset(),
+            Action::CandidateInfo => {
+                self.grid.details = self.popup && !self.grid.details;
+                Command::none()
+            }
+            Action::CharacterMap if self.popup => {
+                self.char_map = Some(CharMap::default());
+                self.show_block();
+                Command::none()
+            }
+            Action::CharacterMap => Command::none(),
+            Action::ShapeLookup => {
+                // Not kept while empty, so data installed later is found
+                if self.shapes.as_ref().map_or(true, shape::Index::is_empty) {
+                    self.shapes = Some(shape::Index::load());
+                }
+                if self.shapes.as_ref().map_or(true, shape::Index::is_empty) {
+                    return self.osd.show(
+                        "Install ucs-strokes.txt or Unihan_DictionaryLikeData.txt in \
+                         ~/.local/share/chewingwl to look up characters by shape",
+                    );
+                }
+                self.shape = Some(shape::Query::default());
+                self.shape_preedit()
+            }
+        }
+    }
+
+    /// Forgets everything composed or pending, back to passing keys
+    /// through.
+    fn drop_composition(&mut self) {
+        self.chewing.clear();
+        self.compose.reset();
+        self.char_map = None;
+        self.shape = None;
+        self.popup = false;
+        self.pinned = false;
+        self.buffered_keys.clear();
+        self.queued_keys.clear();
+        self.pending_preedit = false;
+        self.prefetched = None;
+        self.restored = None;
+        self.grid.reset(0);
+        self.state = State::PassThrough;
+    }
+
+    /// Commits the composition as shown and drops every other piece of
+    /// state, without going through the editor in case it is what got stuck.
+    fn force_reset(&mut self) -> Command<Message> {
+        let preedit = variants::localize(&self.chewing.preedit(), self.config.region);
+        self.drop_composition();
+        self.passthrough_mode = false;
+        let commit = if self.active && !preedit.is_empty() {
+            self.last_commit.clone_from(&preedit);
+            Command::batch(vec![
+                input_method_action(ActionInner::CommitString(preedit)),
+                input_method_action(ActionInner::Commit),
+            ])
+        } else {
+            Command::none()
+        };
+        log::info!("state reset from hotkeys.force_reset");
+        Command::batch(vec![
+            commit,
+            hide_input_method_popup(),
+            self.osd.show("Input method reset"),
+        ])
+    }
+
+    /// Discards the composition unlearned and switches to English, so the
+    /// password field of the lock screen gets the keys as typed.
+    fn lock(&mut self) -> Command<Message> {
+        self.mode_before_lock.get_or_insert(self.passthrough_mode);
+        let composing = !self.current_preedit.is_empty();
+        self.drop_composition();
+        self.current_preedit.clear();
+        self.last_commit.clear();
+        self.passthrough_mode = true;
+        log::info!("composition discarded as the session locked");
+        let clear = if self.active && composing {
+            Command::batch(vec![
+                input_method_action(ActionInner::SetPreeditString {
+                    string: String::new(),
+                    cursor_begin: 0,
+                    cursor_end: 0,
+                }),
+                input_method_action(ActionInner::Commit),
+            ])
+        } else {
+            Command::none()
+        };
+        Command::batch(vec![clear, hide_input_method_popup()])
+    }
+
+    fn handle(&mut self, message: Message) -> Command<Message> {
+        match &message {
+            Message::KeyPressed(key_event, key, modifiers) => {
+                self.held.press(key_event.raw_code);
+                idle::key();
+                self.show_segments = false;
+                self.gestures.press(key_event, key);
+            }
+            Message::KeyReleased(key_event, key, modifiers) => {
+                self.held.release(key_event.raw_code);
+                if let Some(action) = self
+                    .gestures
+                    .release(key_event, key)
+                    .filter(|_| !self.low_latency && !passthrough::super_shortcut(key, modifiers))
+                {
+                    // The release itself goes where it would have gone,
+                    // the gesture is over so it is not seen again
+                    let perform = self.perform(action);
+                    return Command::batch(vec![perform, self.handle(message)]);
+                }
+                if self.forwarded.release(key_event.raw_code) {
+                    return virtual_keyboard_action(VKActionInner::KeyReleased(key_event.clone()));
+                }
+            }
+            Message::Deactivate => {
+                self.held.clear();
+                self.compose.reset();
+            }
+            _ => {}
+        }
+        match message {
+            Message::Activate => {
+                self.active = true;
+                self.unsupported_noticed = false;
+                let identify = !self.config.preedit.client_max_rate.is_empty();
+                let client = self.watchdog.activate(identify);
+                let output = if self.config.popup.output_scale.is_empty() {
+                    client
+                } else {
+                    Command::batch(vec![
+                        client,
+                        Command::perform(focus::focused_output(), Message::FocusedOutput),
+                    ])
+                };
+                if let Some(text) = self.restored.clone() {
+                    return Command::batch(vec![output, self.offer_restored(text)]);
+                }
+                if !self.snippet_dialog.is_open() && !self.chewing.preedit().is_empty() {
+                    // Kept while the snippet dialog had the keyboard
+                    return Command::batch(vec![output, self.preedit_string()]);
+                }
+                self.state = State::PassThrough;
+                output
+            }
+            Message::Deactivate => {
+                self.active = false;
+                self.unsupported_noticed = false;
+                self.char_map = None;
+                self.shape = None;
+                self.popup = false;
+                self.pinned = false;
+                self.buffered_keys.clear();
+                self.queued_keys.clear();
+                self.completions.clear();
+                // Saved as a snippet only in the field it went to
+                self.last_commit.clear();
+                if !self.snippet_dialog.is_open() {
+                    // Otherwise the dialog took the keyboard, and the
+                    // composition is offered again once it gives it back
+                    self.chewing.clear();
+                }
+                self.pending_preedit = false;
+                if self.flush.deactivate_due() {
+                    self.flush_user_data();
+                }
+                self.state = State::PassThrough;
+                hide_input_method_popup()
+            }
+            Message::KeyPressed(key_event, key, modifiers) if self.low_latency => {
+                if self.is_hotkey(
+                    &self.config.hotkeys.low_latency,
+                    &key_event,
+                    &key,
+                    &modifiers,
+                ) {
+                    self.toggle_low_latency()
+                } else {
+                    self.forward_press(key_event)
+                }
+            }
+            // The release of the hotkey that turned the mode on included
+            Message::KeyReleased(..) if self.low_latency => Command::none(),
+            // Super combinations belong to the compositor, whatever is going on
+            Message::KeyPressed(key_event, key, modifiers)
+                if passthrough::super_shortcut(&key, &modifiers) =>
+            {
+                self.forward_press(key_event)
+            }
+            // Forwarded presses were released above, these went elsewhere
+            Message::KeyReleased(_, key, modifiers)
+                if passthrough::super_shortcut(&key, &modifiers) =>
+            {
+                Command::none()
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if self.is_hotkey(&self.config.hotkeys.force_reset, key_event, key, modifiers) =>
+            {
+                self.perform(Action::ForceReset)
+            }
+            // F1 by default, which would otherwise be forwarded
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::Popup)
+                    && self.is_hotkey(
+                        &self.config.hotkeys.candidate_info,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::CandidateInfo)
+            }
+            Message::KeyReleased(ref key_event, ref key, _)
+                if matches!(self.state, State::Popup)
+                    && self
+                        .config
+                        .hotkeys
+                        .candidate_info
+                        .as_ref()
+                        .is_some_and(|hotkey| hotkey.key_matches(key_event, key)) =>
+            {
+                Command::none()
+            }
+            Message::KeyPressed(key_event, key, modifiers)
+                if passthrough::always(
+                    key_event.keysym.raw(),
+                    &self.config.keys.always_forward,
+                ) && !self.bound_hotkey(&key_event, &key, &modifiers) =>
+            {
+                self.forward_press(key_event)
+            }
+            // Taken by a hotkey, as a forwarded press was released above
+            Message::KeyReleased(key_event, ..)
+                if passthrough::always(
+                    key_event.keysym.raw(),
+                    &self.config.keys.always_forward,
+                ) =>
+            {
+                Command::none()
+            }
+            // Keys arriving without an activated text input belong to a client
+            // without input method support, they can only be forwarded
+            Message::KeyPressed(key_event, ..) if !self.active && key_event.utf8.is_some() => {
+                let forward = self.forward_press(key_event);
+                if self.passthrough_mode {
+                    // Told again once Chinese input is turned back on
+                    self.unsupported_noticed = false;
+                    forward
+                } else if !self.config.osd.unsupported_client || self.unsupported_noticed {
+                    forward
+                } else {
+                    self.unsupported_noticed = true;
+                    Command::batch(vec![
+                        forward,
+                        self.osd.show(
+                            "Chinese input unavailable: this window has no text input support",
+                        ),
+                    ])
+                }
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if self.is_hotkey(&self.config.hotkeys.low_latency, key_event, key, modifiers) =>
+            {
+                self.toggle_low_latency()
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::Popup)
+                    && self.char_map.is_none()
+                    && self.is_hotkey(
+                        &self.config.hotkeys.character_map,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::CharacterMap)
+            }
+            Message::KeyPressed(_, ref key, ref modifiers)
+                if (self.char_map.is_some() || self.shape.is_some())
+                    && matches!(self.state, State::Popup) =>
+            {
+                self.browse_key(key, modifiers)
+            }
+            Message::KeyPressed(_, ref key, _) if self.shape.is_some() && !self.popup => {
+                self.shape_key(key)
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::Popup)
+                    && self.is_hotkey(
+                        &self.config.hotkeys.pin_popup,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::PinPopup)
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::Popup)
+                    && self.is_hotkey(
+                        &self.config.hotkeys.cancel_selection,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::CancelSelection)
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::PassThrough)
+                    && !self.passthrough_mode
+                    && self.shape.is_none()
+                    && self.chewing.preedit().is_empty()
+                    && self.is_hotkey(
+                        &self.config.hotkeys.shape_lookup,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::ShapeLookup)
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::PreEdit | State::PassThrough)
+                    && !self.passthrough_mode
+                    && self.is_hotkey(
+                        &self.config.hotkeys.add_snippet,
+                        key_event,
+                        key,
+                        modifiers,
+                    )
+                    && !self.snippet_text().is_empty() =>
+            {
+                self.perform(Action::AddSnippet)
+            }
+            Message::KeyPressed(ref key_event, ref key, ref modifiers)
+                if matches!(self.state, State::PreEdit | State::PassThrough)
+                    && self.is_hotkey(
+                        &self.config.hotkeys.toggle_mode,
+                        key_event,
+                        key,
+                        modifiers,
+                    ) =>
+            {
+                self.perform(Action::ToggleMode)
+            }
+            Message::KeyPressed(_, Key::Character(digit), modifiers)
+                if modifiers.ctrl
+                    && matches!(self.state, State::Popup | State::PreEdit)
+                    && self.char_map.is_none()
+                    && self.shape.is_none()
+                    && digit
+                        .parse::<usize>()
+                        .is_ok_and(|n| (1..=self.completions.len()).contains(&n)) =>
+            {
+                self.pick_completion(digit.parse::<usize>().unwrap_or_default() - 1)
+            }
+            // The control character a shortcut produces would otherwise be
+            // fed to the editor as a zhuyin key
+            Message::KeyPressed(key_event, key, modifiers)
+                if (modifiers.ctrl || modifiers.alt) && !is_modifier(&key) =>
+            {
+                self.shortcut(key_event)
+            }
+            Message::KeyPressed(_, key, _)
+                if self.restored.is_some() && matches!(self.state, State::PreEdit) =>
+            {
+                self.resolve_restored(&key)
+            }
+            Message::KeyPressed(key_event, key, modifiers) => match self.state {
+                State::PreEdit => match key {
+                    Key::Named(Named::Backspace) => {
+                        self.chewing.process_key(KeyCode::Backspace);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::Space) => {
+                        if modifiers.shift {
+                            self.chewing.process_shift_key(KeyCode::Space);
+                            Command::none()
+                        } else {
+                            self.chewing.process_key(KeyCode::Space);
+                            self.preedit_string()
+                        }
+                    }
+                    Key::Named(Named::Enter) => self.commit_string(),
+                    Key::Named(Named::Escape) => {
+                        self.chewing.clear();
+                        self.chewing.process_key(KeyCode::Esc);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::Delete) => {
+                        self.chewing.process_key(KeyCode::Del);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::ArrowLeft) => {
+                        self.chewing.process_key(KeyCode::Left);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::ArrowRight) => {
+                        self.chewing.process_key(KeyCode::Right);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::ArrowDown) => match self.down.press() {
+                        Down::OpenPopup => self.open_popup_from_preedit(),
+                        Down::MoveToEnd => {
+                            self.chewing.process_key(KeyCode::End);
+                            self.preedit_string()
+                        }
+                        Down::StartHold(generation) => {
+                            Command::perform(tokio::time::sleep(gesture::HOLD), move |_| {
+                                Message::DownHeld(generation)
+                            })
+                        }
+                        Down::Nothing => Command::none(),
+                    },
+                    Key::Named(Named::ArrowUp) => {
+                        self.chewing.process_key(KeyCode::Up);
+                        self.preedit_string()
+                    }
+                    Key::Named(Named::Tab) => {
+                        self.chewing.process_key(KeyCode::Tab);
+                        self.show_segments = true;
+                        self.preedit_string()
+                    }
+                    _ => {
+                        if self.compose.forward(key_event.keysym.raw()) {
+                            // A dead key or Compose sequence is never fed to the editor
+                            self.compose_key(key_event)
+                        } else if let Some(ascii) = self.zhuyin_key(&key_event, &modifiers) {
+                            self.chewing.process_ascii(ascii);
+                            self.preedit_string()
+                        } else {
+                            Command::none()
+                        }
+                    }
+                },
+                State::Popup => {
+                    let prefix = self.label_prefix.take();
+                    match self.oriented(&key).as_ref() {
+                        Key::Character(typed)
+                            if prefix.is_none() && self.is_label_prefix(typed) =>
+                        {
+                            self.label_prefix = single_char(typed);
+                            Command::none()
+                        }
+                        Key::Character(typed) if self.selection_index(prefix, typed).is_some() => {
+                            let index = self.selection_index(prefix, typed).unwrap_or_default();
+                            self.num_select(index)
+                        }
+                        Key::Named(Named::ArrowDown)
+                            if self.config.popup.wrap_selection
+                                && self.grid.index + 1
+                                    >= self.grid.page_len(self.candidates.len()) =>
+                        {
+                            let total_pages = self.grid.total_pages(self.candidates.len());
+                            if self.grid.page + 1 < total_pages {
+                                self.grid.next_page(self.candidates.len());
+                            } else {
+                                self.grid.reset(self.candidates.len());
+                            }
+                            self.grid.index = 0;
+                            Command::none()
+                        }
+                        Key::Named(Named::ArrowDown) => {
+                            if self.grid.at_last(self.candidates.len()) {
+                                self.chewing.process_key(KeyCode::Down);
+                                let candidates = self.chewing.candidates();
+                                self.list_candidates(candidates);
+                            } else {
+                                self.grid.next_index(self.candidates.len());
+                            }
+                            Command::none()
+                        }
+                        Key::Named(Named::ArrowUp) => {
+                            self.grid.index =
+                                if self.grid.index == 0 && self.config.popup.wrap_selection {
+                                    self.grid.page_len(self.candidates.len()).saturating_sub(1)
+                                } else {
+                                    self.grid.index.saturating_sub(1)
+                                };
+                            Command::none()
+                        }
+                        Key::Named(named) if is_page_key(named) => {
+                            self.page_key(named);
+                            Command::none()
+                        }
+                        Key::Named(Named::Enter) => self.num_select(self.grid.index),
+                        Key::Named(Named::Tab) => {
+                            self.unfold_variants();
+                            Command::none()
+                        }
+                        Key::Named(Named::Escape) => {
+                            self.leave_popup();
+                            if self.config.keys.escape_in_popup == EscapePolicy::Clear {
+                                self.chewing.clear();
+                            }
+                            Command::batch(vec![self.preedit_string(), hide_input_method_popup()])
+                        }
+                        _ => match self.zhuyin_key(&key_event, &modifiers) {
+                            Some(ascii) => self.stray_in_popup(key_event, ascii),
+                            None => Command::none(),
+                        },
+                    }
+                }
+                State::WaitingForDone => match editing_key(&key) {
+                    // Held editing keys are applied right away and their result is
+                    // sent in one preedit update once the client catches up
+                    Some(key_code) if !self.popup && !self.current_preedit.is_empty() => {
+                        self.chewing.process_key(key_code);
+                        self.pending_preedit = true;
+                        Command::none()
+                    }
+                    None if !self.popup && self.compose.forward(key_event.keysym.raw()) => {
+                        self.compose_key(key_event)
+                    }
+                    // Enter and Escape wait for the client too, as they end
+                    // the composition and must not be lost
+                    None if !self.popup
+                        && !self.current_preedit.is_empty()
+                        && matches!(key, Key::Named(Named::Enter | Named::Escape)) =>
+                    {
+                        self.queued_keys.push((key_event, key, modifiers));
+                        Command::none()
+                    }
+                    // So is typing, as a slow client would otherwise lose keys
+                    None if !self.popup && !self.current_preedit.is_empty() => {
+                        if let Some(ascii) = self.zhuyin_key(&key_event, &modifiers) {
+                            self.chewing.process_ascii(ascii);
+                            self.pending_preedit = true;
+                        }
+                        Command::none()
+                    }
+                    // Do nothing else if text input client is not ready, the
+                    // watchdog gives up on it after a while
+                    _ => Command::none(),
+                },
+                State::PassThrough => {
+                    if self.passthrough_mode {
+                        if key == Key::Named(Named::Shift) {
+                            self.shift_set = true;
+                            Command::none()
+                        } else {
+                            self.shift_set = false;
+                            self.forward_press(key_event)
+                        }
+                    } else if key == Key::Named(Named::Shift) {
+                        self.shift_set = true;
+                        Command::none()
+                    } else if key == Key::Named(Named::Space) {
+                        self.shift_set = false;
+                        if modifiers.shift {
+                            self.chewing.process_shift_key(KeyCode::Space);
+                            Command::none()
+                        } else {
+                            self.forward_press(key_event)
+                        }
+                    } else if key == Key::Named(Named::Enter) && self.chewing.preedit().is_empty() {
+                        self.shift_set = false;
+                        self.enter_when_empty(key_event)
+                    } else if self.compose.forward(key_event.keysym.raw()) {
+                        self.shift_set = false;
+                        self.forward_press(key_event)
+                    } else if let Some(ascii) = self.zhuyin_key(&key_event, &modifiers) {
+                        self.shift_set = false;
+                        self.chewing.process_ascii(ascii);
+                        if self.chewing.preedit().is_empty() {
+                            self.forward_press(key_event)
+                        } else {
+                            self.preedit_string()
+                        }
+                    } else {
+                        self.shift_set = false;
+                        self.forward_press(key_event)
+                    }
+                }
+            },
+            // A short press of Down that did not open the popup by holding
+            Message::KeyReleased(_, Key::Named(Named::ArrowDown), _)
+                if self.down.release() && matches!(self.state, State::PreEdit) =>
+            {
+                self.chewing.process_key(KeyCode::End);
+                self.preedit_string()
+            }
+            Message::KeyReleased(key_event, key, _modifiers) => match self.state {
+                State::PassThrough => {
+                    if key == Key::Named(Named::Shift) && self.shift_set {
+                        self.shift_set = false;
+                        if self.config.hotkeys.toggle_mode.is_none() {
+                            self.passthrough_mode = !self.passthrough_mode;
+                        }
+                        Command::none()
+                    } else {
+                        virtual_keyboard_action(VKActionInner::KeyReleased(key_event))
+                    }
+                }
+                State::PreEdit | State::Popup | State::WaitingForDone => Command::none(),
+            },
+            Message::Modifiers(_modifiers, raw_modifiers) => {
+                virtual_keyboard_action(VKActionInner::Modifiers(raw_modifiers))
+            }
+            Message::Done => match self.state {
+                State::WaitingForDone => {
+                    self.watchdog.done();
+                    if self.pending_preedit {
+                        self.send_pending_preedit()
+                    } else if self.popup {
+                        self.state = State::Popup;
+                        show_input_method_popup()
+                    } else if !self.current_preedit.is_empty() {
+                        self.state = State::PreEdit;
+                        Command::batch(vec![
+                            self.schedule_prefetch(),
+                            self.show_completions(),
+                            self.replay_queued_keys(),
+                        ])
+                    } else {
+                        self.state = State::PassThrough;
+                        if self.reload_pending {
+                            self.reload_config();
+                        }
+                        let hide = if self.completions.is_empty() {
+                            Command::none()
+                        } else {
+                            self.completions.clear();
+                            hide_input_method_popup()
+                        };
+                        Command::batch(vec![hide, self.replay_queued_keys()])
+                    }
+                }
+                State::PreEdit | State::Popup | State::PassThrough => Command::none(),
+            },
+            Message::Shutdown => {
+                self.save_for_exit();
+                std::process::exit(0)
+            }
+            Message::SessionLocked(true) if self.config.session.on_lock == LockPolicy::English => {
+                self.lock()
+            }
+            Message::SessionLocked(locked) => {
+                if let (false, Some(mode)) = (locked, self.mode_before_lock.take()) {
+                    self.passthrough_mode = mode;
+                }
+                Command::none()
+            }
+            Message::DarkMode(dark) => {
+                self.dark_mode = dark;
+                Command::none()
+            }
+            Message::ScreenShared(shared) => {
+                self.screen_shared = shared;
+                log::info!(
+                    "screen sharing {}",
+                    if shared { "started" } else { "stopped" }
+                );
+                Command::none()
+            }
+            Message::DoNotDisturb(active) => {
+                self.osd.set_do_not_disturb(active);
+                Command::none()
+            }
+            Message::FontLoaded(path, result) => {
+                match result {
+                    Ok(()) => log::info!("font file {} loaded", path.display()),
+                    Err(error) => log::warn!("cannot load the font {}: {error:?}", path.display()),
+                }
+                Command::none()
+            }
+            Message::OsdTimeout(generation) => self.osd.timeout(generation),
+            Message::PageFadeTick => {
+                if self.page_fade() >= 1.0 {
+                    self.page_changed = None;
+                }
+                Command::none()
+            }
+            Message::DownHeld(generation) => {
+                if matches!(self.state, State::PreEdit) && self.down.held(generation) {
+                    self.open_popup_from_preedit()
+                } else {
+                    Command::none()
+                }
+            }
+            Message::Prefetch(generation) => {
+                if generation == self.prefetch_generation {
+                    self.prefetch();
+                }
+                Command::none()
+            }
+            Message::PreeditDue => {
+                if self.pending_preedit && matches!(self.state, State::WaitingForDone) {
+                    self.pending_preedit = false;
+                    self.preedit_string()
+                } else {
+                    Command::none()
+                }
+            }
+            Message::DoneTimeout(generation) => {
+                if matches!(self.state, State::WaitingForDone)
+                    && self.watchdog.timed_out(generation)
+                {
+                    let identify = self.watchdog.identify();
+                    Command::batch(vec![identify, self.handle(Message::Done)])
+                } else {
+                    Command::none()
+                }
+            }
+            Message::Output(event, output) => {
+                self.outputs.update(event, output);
+                Command::none()
+            }
+            Message::FocusedOutput(name) => {
+                self.outputs.set_focused(name);
+                Command::none()
+            }
+            Message::FocusedClient(generation, client) => {
+                self.watchdog.focused(generation, client);
+                Command::none()
+            }
+            Message::Error(event) => self.error_window.update(event),
+            Message::Snippet(event) => {
+                let (command, saved) = self.snippet_dialog.update(event);
+                let Some((name, text)) = saved else {
+                    return command;
+                };
+                let notice = match self.snippets.insert(name.clone(), text) {
+                    Ok(()) => format!("Saved snippet {name}"),
+                    Err(error) => {
+                        log::warn!("failed to save snippet {name}: {error}");
+                        format!("Could not save snippet {name}")
+                    }
+                };
+                Command::batch(vec![command, self.osd.show(notice)])
+            }
+            Message::History(event) => match event {
+                history_panel::Event::Pick(index) => {
+                    let Some(entry) = self.history.get(index) else {
+                        return Command::none();
+                    };
+                    // Only typed into text that is not being composed
+                    if !self.active || !matches!(self.state, State::PassThrough) {
+                        return Command::none();
+                    }
+                    Command::batch(vec![
+                        input_method_action(ActionInner::CommitString(entry.text.clone())),
+                        input_method_action(ActionInner::Commit),
+                    ])
+                }
+                history_panel::Event::Clear => {
+                    self.history.clear();
+                    Command::none()
+                }
+                history_panel::Event::Close => self.history_panel.close(),
+            },
+            Message::Wizard(event) => {
+                let (command, config) = self.wizard.update(event, &self.config);
+                if let Some(config) = config {
+                    self.apply_setup(config);
+                }
+                command
+            }
+            Message::Ipc(command, reply) => self.control(&command, &reply),
+            Message::LogLevelExpired(generation) => {
+                if generation == self.log_level_generation {
+                    logging::reset();
+                }
+                Command::none()
+            }
+            Message::ConfigChanged => {
+                self.config_generation += 1;
+                let generation = self.config_generation;
+                Command::perform(tokio::time::sleep(RELOAD_DELAY), move |_| {
+                    Message::ReloadConfig(generation)
+                })
+            }
+            #[cfg(feature = "sync")]
+            Message::Synced(result) => {
+                if let Err(error) = result {
+                    log::warn!("sync failed: {error}");
+                }
+                self.chewing = backend::new(&self.config);
+                let failed = self.chewing.take_error();
+                self.passthrough_mode = failed.is_some();
+                failed.map_or_else(Command::none, |error| {
+                    self.error_window.open(&error, self.config.surfaces.error)
+                })
+            }
+            Message::ReloadConfig(generation) => {
+                if generation == self.config_generation {
+                    if matches!(self.state, State::PassThrough) {
+                        self.reload_config();
+                    } else {
+                        self.reload_pending = true;
+                    }
+                }
+                Command::none()
+            }
+            Message::FlushTick => {
+                if self.flush.idle_due() {
+                    self.flush_user_data();
+                }
+                Command::none()
+            }
+            Message::FlushDue => {
+                if self.flush.is_dirty() {
+                    self.flush_user_data();
+                }
+                Command::none()
+            }
+            Message::EditAt(index)
+                if matches!(self.state, State::Popup)
+                    && self.char_map.is_none()
+                    && self.shape.is_none() =>
+            {
+                self.leave_popup();
+                self.move_cursor(index);
+                self.open_popup()
+            }
+            Message::EditAt(_) => Command::none(),
+            Message::Grid(grid::Event::Pick) if self.char_map.is_some() || self.shape.is_some() => {
+                self.pick_character(self.grid.index)
+            }
+            Message::Grid(grid::Event::Pick) => self.num_select(self.grid.index),
+            Message::Grid(grid::Event::PreviousPage) => {
+                self.page_key(Named::PageUp);
+                Command::none()
+            }
+            Message::Grid(grid::Event::NextPage) => {
+                self.page_key(Named::PageDown);
+                Command::none()
+            }
+            Message::Grid(event) => {
+                if self.grid.update(event) && self.config.popup.tooltips {
+                    self.tooltip_generation += 1;
+                    let generation = self.tooltip_generation;
+                    Command::perform(tokio::time::sleep(TOOLTIP_DELAY), move |_| {
+                        Message::TooltipDue(generation)
+                    })
+                } else {
+                    Command::none()
+                }
+            }
+            Message::TooltipDue(generation) => {
+                if self.popup && generation == self.tooltip_generation {
+                    let tooltip = self.tooltip();
+                    self.grid.show_tooltip(tooltip);
+                }
+                Command::none()
+            }
+        }
+    }
+
+    /// Switches to the config chosen in the first-run wizard or to another
+    /// profile.
+    fn apply_setup(&mut self, mut config: Config) {
+        // What was turned on from the command line stays on
+        config.debug.state_overlay = self.config.debug.state_overlay;
+        config.debug.log_key_text_flag = self.config.debug.log_key_text_flag;
+        config.debug.log_key_text |= config.debug.log_key_text_flag;
+        self.grid = candidate_grid(&config.popup);
+        if config.popup.font.family != self.config.popup.font.family {
+            self.font = popup_font(&config.popup.font);
+        }
+        if config.keyboard.layout != self.config.keyboard.layout {
+            // The new backend opens the user dictionary afresh, so what the
+            // old one learned has to be on disk first
+            self.flush_user_data();
+            user_data::finish_writes();
+            self.chewing = backend::new(&config);
+        }
+        self.speaker = config.speech.enabled.then(|| Speaker::new(&config.speech));
+        self.gestures = Gestures::new(config.gestures.clone());
+        self.down = DownKey::new(config.keys.down, config.gestures.double_tap_ms);
+        self.osd.configure(&config.osd, config.surfaces.osd);
+        if config.stats.key_timing != self.key_timing.is_some() {
+            if let Some(timing) = &mut self.key_timing {
+                timing.save();
+            }
+            self.key_timing = config.stats.key_timing.then(KeyTiming::load);
+        }
+        self.config = config;
+    }
+
+    /// Applies the config files as they are now, keeping the current
+    /// config if one of them does not parse.
+    fn reload_config(&mut self) {
+        self.reload_pending = false;
+        match Config::reload(self.config.chosen_profile.as_deref()) {
+            Ok(config) => {
+                self.apply_setup(config);
+                log::info!("config reloaded");
+            }
+            Err(error) => log::warn!("keeping the current config: {error}"),
+        }
+    }
+
+    /// The key of a US QWERTY keyboard for the editor, after
+    /// `keyboard.remap`, if the key typed one ascii character. Keys of a
+    /// keymap for another script than Latin go by where they sit, see
+    /// [`layout::physical`]; a character typed with AltGr is taken as is.
+    fn zhuyin_key(&self, key_event: &KeyEvent, modifiers: &Modifiers) -> Option<u8> {
+        let typed = key_event
+            .utf8
+            .as_deref()
+            .and_then(|text| text.chars().next())
+            .filter(|typed| !typed.is_control());
+        let by_position = typed.filter(|typed| {
+            self.config.keyboard.remap == Remap::Physical || layout::other_script(*typed)
+        });
+        if let Some(key) = by_position
+            .and_then(|typed| layout::physical(key_event.raw_code, typed, modifiers.shift))
+        {
+            return Some(key);
+        }
+        compose::ascii(key_event.utf8.as_deref())
+            .map(|ascii| layout::remap(self.config.keyboard.remap, ascii))
+    }
+
+    /// How a key shows in the debug log: its keysym name, unless it types
+    /// text and `debug.log_key_text` is off.
+    fn key_name(&self, key_event: &KeyEvent, key: &Key) -> String {
+        let types_text = key_event
+            .utf8
+            .as_deref()
+            .is_some_and(|text| !text.chars().all(char::is_control));
+        if types_text && !self.config.debug.log_key_text {
+            return match key {
+                Key::Named(named) => format!("{named:?}"),
+                _ => "<text>".into(),
+            };
+        }
+        key_event.keysym.name().map_or_else(
+            || format!("{:#x}", key_event.keysym.raw()),
+            |name| name.trim_start_matches("XK_").to_string(),
+        )
+    }
+
+    /// Whether the popup keeps its candidates to itself, the screen being
+    /// shared.
+    fn masked(&self) -> bool {
+        self.screen_shared && self.config.popup.hide_while_sharing
+    }
+
+    /// The state and mode flags, as shown by the debug state overlay.
+    fn state_summary(&self) -> String {
+        let mut summary = format!(
+            "{:?} {}",
+            self.state,
+            if self.passthrough_mode {
+                "english"
+            } else {
+                "chinese"
+            }
+        );
+        for (set, flag) in [
+            (!self.active, "inactive"),
+            (self.popup, "popup"),
+            (self.low_latency, "low-latency"),
+            (self.pending_preedit, "pending-preedit"),
+        ] {
+            if set {
+                summary.push(' ');
+                summary.push_str(flag);
+            }
+        }
+        summary
+    }
+
+    /// Everything that decides how the next key is handled, for
+    /// `chewingwlctl dump-state`. With `redact` the texts typed are replaced
+    /// by their length.
+    fn dump_state(&self, redact: bool) -> String {
+        let text = |text: &str| {
+            if redact {
+                format!("<{} chars>", text.chars().count())
+            } else {
+                format!("{text:?}")
+            }
+        };
+        let mut dump = format!("state: {}\n", self.state_summary());
+        if self.pinned {
+            dump.push_str("popup pinned\n");
+        }
+        dump.push_str(&format!(
+            "preedit: {} cursor {}\n",
+            text(&self.chewing.preedit()),
+            self.chewing.cursor()
+        ));
+        dump.push_str(&format!("last commit: {}\n", text(&self.last_commit)));
+        if let Some(restored) = &self.restored {
+            dump.push_str(&format!("restored: {}\n", text(restored)));
+        }
+        if !self.buffered_keys.is_empty() {
+            let keys = String::from_utf8_lossy(&self.buffered_keys);
+            dump.push_str(&format!("buffered keys: {}\n", text(&keys)));
+        }
+        if self.popup {
+            dump.push_str(&format!(
+                "popup: page {} index {} of {} candidates\n",
+                self.grid.page,
+                self.grid.index,
+                self.candidates.len()
+            ));
+        }
+        dump.push_str(&format!("done: {}\n", self.watchdog.summary()));
+        dump
+    }
+
+    /// Mirrors the whole candidate list and the highlight to control socket
+    /// and D-Bus watchers, if the config exports them.
+    fn publish_candidates(&self) {
+        ipc::set_exported(self.config.popup.export_candidates);
+        if self.config.popup.export_candidates && self.popup && !self.masked() {
+            ipc::publish_candidates(Some(self.grid.position()), &self.candidates);
+        } else {
+            ipc::publish_candidates(None, &[]);
+        }
+    }
+
+    /// Approximate memory use, for `chewingwlctl memstats`.
+    fn memstats(&self) -> String {
+        let mut stats = MemStats::default();
+        stats.add_dictionaries(&self.config, self.chewing.is_loaded());
+        stats.add("candidates", memstats::strings(&self.candidates));
+        stats.add(
+            "prefetched candidates",
+            self.prefetched
+                .as_ref()
+                .map_or(0, |p| memstats::strings(&p.candidates)),
+        );
+        if self.popup {
+            // Each candidate row is a label and phrase at text size 50
+            let columns = self.grid.columns();
+            let rows = columns.iter().map(|page| page.len()).max().unwrap_or(0);
+            let longest = self.candidates.iter().map(|c| c.chars().count()).max();
+            let width = (longest.unwrap_or(0) + 1) as f64 * 50.0 + 20.0;
+            stats.add_surface(
+                "popup",
+                (width * columns.len() as f64, rows as f64 * 70.0),
+                self.outputs.popup_scale(&self.config.popup),
+            );
+        }
+        if let Some((width, height)) = self.osd.size() {
+            stats.add_surface(
+                "osd",
+                (width as f64, height as f64),
+                self.config.popup.scale,
+            );
+        }
+        if let Some(overlay) = &self.state_overlay {
+            let (width, height) = overlay.size();
+            stats.add_surface(
+                "state overlay",
+                (width as f64, height as f64),
+                self.config.popup.scale,
+            );
+        }
+        stats.report()
+    }
+
+    /// Answers a command from the control socket.
+    fn control(&mut self, command: &str, reply: &ipc::Reply) -> Command<Message> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["candidates"] if !self.config.popup.export_candidates => reply.send(ipc::NOT_EXPORTED),
+            ["candidates"] => reply.send(format!("{}\n", ipc::candidates().line())),
+            ["memstats"] => reply.send(self.memstats()),
+            ["key-timing"] => reply.send(self.key_timing.as_ref().map_or_else(
+                || "key timing is off, set stats.key_timing to start\n".to_string(),
+                |timing| timing.report(self.config.keyboard.layout),
+            )),
+            ["key-timing", "clear"] => {
+                if let Some(timing) = &mut self.key_timing {
+                    timing.clear();
+                }
+                reply.send("key timing cleared\n");
+            }
+            ["low-latency"] => reply.send(format!(
+                "low-latency mode is {}\n",
+                if self.low_latency { "on" } else { "off" }
+            )),
+            ["low-latency", switch @ ("on" | "off" | "toggle")] => {
+                let on = match *switch {
+                    "on" => true,
+                    "off" => false,
+                    _ => !self.low_latency,
+                };
+                reply.send(format!("low-latency mode {switch}\n"));
+                if on != self.low_latency {
+                    return self.toggle_low_latency();
+                }
+            }
+            ["quirks", "clear"] => {
+                self.watchdog.forget();
+                reply.send("client quirks cleared\n");
+            }
+            ["dump-state"] => reply.send(self.dump_state(false)),
+            ["dump-state", "redact"] => reply.send(self.dump_state(true)),
+            ["history"] | ["history", "show"] => {
+                reply.send("history shown\n");
+                return self.history_panel.open(self.config.surfaces.history);
+            }
+            ["snippets"] => {
+                let names: Vec<&str> = self.snippets.names().collect();
+                reply.send(format!("{}\n", names.join("\n")));
+            }
+            ["snippet", name @ ..] if !name.is_empty() => {
+                let name = name.join(" ");
+                let Some(text) = self.snippets.get(&name) else {
+                    reply.send(format!("no snippet named `{name}`\n"));
+                    return Command::none();
+                };
+                if !self.active || !matches!(self.state, State::PassThrough) {
+                    reply.send("no text field ready for input\n");
+                    return Command::none();
+                }
+                reply.send(format!("typed snippet {name}\n"));
+                return Command::batch(vec![
+                    input_method_action(ActionInner::CommitString(text.to_string())),
+                    input_method_action(ActionInner::Commit),
+                ]);
+            }
+            ["profile"] => {
+                let mut names: Vec<&str> =
+                    self.config.profiles.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                let current = self.config.profile.as_deref().unwrap_or("none");
+                reply.send(format!(
+                    "current: {current}\navailable: {}\n",
+                    names.join(" ")
+                ));
+            }
+            ["profile", name] => {
+                if !matches!(self.state, State::PassThrough) {
+                    reply.send("finish composing before switching profiles\n");
+                    return Command::none();
+                }
+                match Config::load_profile(Some(name)) {
+                    Ok(config) => {
+                        self.apply_setup(config);
+                        reply.send(format!("switched to profile {name}\n"));
+                    }
+                    Err(error) => reply.send(format!("{error}\n")),
+                }
+            }
+            ["history", "clear"] => {
+                self.history.clear();
+                reply.send("history cleared\n");
+            }
+            ["transitions"] => reply.send(self.state_overlay.as_ref().map_or_else(
+                || "no transitions recorded, start with --debug-state-overlay\n".to_string(),
+                StateOverlay::dump,
+            )),
+            ["log-level", "reset"] => {
+                self.log_level_generation += 1;
+                logging::reset();
+                reply.send("log level reset\n");
+            }
+            ["log-level", filter, rest @ ..] => {
+                let minutes = match rest {
+                    [] => Some(LOG_LEVEL_MINUTES),
+                    [minutes] => minutes.parse::<u64>().ok(),
+                    _ => None,
+                };
+                let Some(minutes) = minutes else {
+                    reply.send("usage: log-level <filter> [minutes] | log-level reset\n");
+                    return Command::none();
+                };
+                logging::set_filter(filter);
+                reply.send(format!("log level {filter} for {minutes} minutes\n"));
+                self.log_level_generation += 1;
+                let generation = self.log_level_generation;
+                return Command::perform(
+                    tokio::time::sleep(Duration::from_secs(minutes * 60)),
+                    move |_| Message::LogLevelExpired(generation),
+                );
+            }
+            _ => reply.send(format!("unknown command `{command}`\n")),
+        }
+        Command::none()
+    }
+
+    /// [`Self::handle`], recording how long keys take for the latency log.
+    fn handle_measured(&mut self, message: Message) -> Command<Message> {
+        let key = matches!(message, Message::KeyPressed(..));
+        let done = matches!(message, Message::Done);
+        let state = format!("{:?}", self.state);
+        let started = Instant::now();
+        let command = self.handle(message);
+        if let Some(latency) = &mut self.latency {
+            if key {
+                latency.key_handled(state, started.elapsed());
+                if matches!(self.state, State::WaitingForDone) {
+                    latency.awaiting_done(started);
+                }
+            }
+            if done {
+                latency.done_received();
+            }
+        }
+        command
+    }
+
+    /// The input method composing with `chewing`, which the screenshot
+    /// mode replaces so as not to open the user's dictionary. An
+    /// `offscreen` one also leaves the history, snippets, quirks and
+    /// setup state alone, reading and writing none of them.
+    fn with_backend(
+        mut config: Config,
+        mut chewing: Box<dyn Backend>,
+        offscreen: bool,
+    ) -> (InputMethod, Command<Message>) {
+        if offscreen {
+            config.session.restore = false;
+            config.history.enabled = false;
+            config.stats.key_timing = false;
+            config.speech.enabled = false;
+        }
+        let wizard = Wizard::new(&config);
+        let setup = if !offscreen && Wizard::is_due() {
+            wizard.open(config.surfaces.setup)
+        } else {
+            Command::none()
+        };
+        let state_overlay = config.debug.state_overlay.then(StateOverlay::default);
+        let debug = state_overlay
+            .as_ref()
+            .map_or_else(Command::none, |overlay| overlay.open(config.surfaces.debug));
+        let mut osd = Osd::new(&config.osd, config.surfaces.osd);
+        let font = load_font_file(&config.popup.font);
+        let conflicts = hotkey::conflicts(&config);
+        for conflict in &conflicts {
+            log::warn!("{conflict}");
+        }
+        let notice = if conflicts.is_empty() {
+            Command::none()
+        } else {
+            osd.show("Some key bindings conflict, run `chewingwl doctor` for details")
+        };
+        let mut error_window = ErrorWindow::default();
+        let failed = chewing.take_error();
+        let error = failed.as_ref().map_or_else(Command::none, |error| {
+            error_window.open(error, config.surfaces.error)
+        });
+        (
+            InputMethod {
+                chewing,
+                state: State::PassThrough,
+                candidates: Vec::new(),
+                listed: Vec::new(),
+                order: Vec::new(),
+                unfolded: HashSet::new(),
+                current_preedit: String::new(),
+                cursor_position: 0,
+                preedit_len: 0,
+                grid: candidate_grid(&config.popup),
+                char_map: None,
+                shape: None,
+                shapes: None,
+                completions: Vec::new(),
+                completion_index: None,
+                popup: false,
+                shift_set: false,
+                passthrough_mode: failed.is_some(),
+                mode_before_lock: None,
+                font: popup_font(&config.popup.font),
+                dark_mode: true,
+                screen_shared: false,
+                flush: FlushSchedule::new(config.user_dictionary.flush.clone()),
+                restored: config.session.restore.then(session::take).flatten(),
+                low_latency: false,
+                forwarded: Forwarded::default(),
+                pending_preedit: false,
+                show_segments: false,
+                pinned: false,
+                gestures: Gestures::new(config.gestures.clone()),
+                down: DownKey::new(config.keys.down, config.gestures.double_tap_ms),
+                page_changed: None,
+                tooltip_generation: 0,
+                sources: None,
+                history: History::load(&config.history),
+                history_panel: HistoryPanel::default(),
+                snippets: if offscreen {
+                    Snippets::default()
+                } else {
+                    Snippets::load()
+                },
+                snippet_dialog: SnippetDialog::default(),
+                error_window,
+                last_commit: String::new(),
+                held: HeldKeys::default(),
+                compose: ComposeState::default(),
+                active: false,
+                unsupported_noticed: false,
+                osd,
+                outputs: Outputs::default(),
+                latency: config.debug.latency.then(Latency::default),
+                key_timing: config.stats.key_timing.then(KeyTiming::load),
+                state_overlay,
+                key_log: RateLimit::new(KEY_LOG_RATE),
+                log_level_generation: 0,
+                config_generation: 0,
+                reload_pending: false,
+                preedit_sent: None,
+                prefetched: None,
+                prefetch_generation: 0,
+                buffered_keys: Vec::new(),
+                queued_keys: Vec::new(),
+                label_prefix: None,
+                watchdog: if offscreen {
+                    Watchdog::default()
+                } else {
+                    Watchdog::new()
+                },
+                offscreen,
+                wizard,
+                speaker: config.speech.enabled.then(|| Speaker::new(&config.speech)),
+                config,
+            },
+            Command::batch(vec![setup, notice, debug, font, error]),
+        )
+    }
+}
+
+fn is_modifier(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Named(
+            Named::Control
+                | Named::Alt
+                | Named::AltGraph
+                | Named::Shift
+                | Named::Super
+                | Named::Meta
+                | Named::Hyper
+        )
+    )
+}
+
+/// Keys that only move or delete within the buffer and are typically held.
+fn editing_key(key: &Key) -> Option<KeyCode> {
+    match key {
+        Key::Named(Named::Backspace) => Some(KeyCode::Backspace),
+        Key::Named(Named::Delete) => Some(KeyCode::Del),
+        Key::Named(Named::ArrowLeft) => Some(KeyCode::Left),
+        Key::Named(Named::ArrowRight) => Some(KeyCode::Right),
+        _ => None,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -194,9 +2539,50 @@ pub enum Message {
     KeyPressed(KeyEvent, Key, Modifiers),
     KeyReleased(KeyEvent, Key, Modifiers),
     Modifiers(Modifiers, RawModifiers),
-    UpdatePopup { page: usize, index: usize },
-    ClosePopup,
+    Grid(grid::Event),
+    /// The character at this index of the composition shown in the popup
+    /// was clicked.
+    EditAt(usize),
     Done,
+    FlushTick,
+    /// Enough commits piled up for a flush.
+    FlushDue,
+    Shutdown,
+    /// Screen sharing started or stopped.
+    ScreenShared(bool),
+    /// The desktop's do-not-disturb mode was turned on or off.
+    DoNotDisturb(bool),
+    /// The desktop now prefers dark windows, or light ones.
+    DarkMode(bool),
+    /// `popup.font.file` was handed to the renderer, which took it or not.
+    FontLoaded(PathBuf, Result<(), font::Error>),
+    /// The session was locked, or unlocked.
+    SessionLocked(bool),
+    OsdTimeout(u64),
+    DoneTimeout(u64),
+    PreeditDue,
+    Prefetch(u64),
+    DownHeld(u64),
+    /// Redraws the compact popup while a page fades in.
+    PageFadeTick,
+    TooltipDue(u64),
+    Output(OutputEvent, WlOutput),
+    /// The compositor named the focused output, or could not.
+    FocusedOutput(Option<String>),
+    /// The compositor named the client focused in the given generation of
+    /// [`Watchdog`], or could not.
+    FocusedClient(u64, Option<String>),
+    Ipc(String, ipc::Reply),
+    LogLevelExpired(u64),
+    ConfigChanged,
+    ReloadConfig(u64),
+    /// The startup sync of the user dictionary ended, see [`sync::run`].
+    #[cfg(feature = "sync")]
+    Synced(Result<(), String>),
+    Wizard(wizard::Event),
+    History(history_panel::Event),
+    Snippet(snippet_dialog::Event),
+    Error(error_window::Event),
 }
 
 #[derive(Clone, Debug)]
@@ -207,399 +2593,247 @@ enum State {
     PassThrough,
 }
 
+/// The event loop ends without a message when the compositor goes away,
+/// dropping the input method on its way out.
+impl Drop for InputMethod {
+    fn drop(&mut self) {
+        if !self.offscreen {
+            self.save_for_exit();
+        }
+    }
+}
+
 impl Application for InputMethod {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
+    type Flags = Config;
     type Theme = Theme;
 
-    fn new(_flags: ()) -> (InputMethod, Command<Message>) {
-        (
-            InputMethod {
-                page: 0,
-                index: 0,
-                chewing: Chewing::new(),
-                state: State::PassThrough,
-                candidates: Vec::new(),
-                current_preedit: String::new(),
-                cursor_position: 0,
-                preedit_len: 0,
-                pages: Vec::new(),
-                max_candidates: 10,
-                max_pages: 4,
-                popup: false,
-                shift_set: false,
-                passthrough_mode: false,
-            },
-            Command::none(),
-        )
+    fn new(config: Config) -> (InputMethod, Command<Message>) {
+        #[cfg(feature = "sync")]
+        if config.sync.on_startup && !config.sync.url.is_empty() {
+            if ipc::is_running() {
+                // Its next flush would write over the merged phrases
+                log::warn!("chewingwl is already running, not syncing");
+            } else {
+                // The dictionary is opened once the sync is done, keys
+                // going to the application until then
+                let sync = config.sync.clone();
+                let keep = config.user_dictionary.backups;
+                let (mut input_method, command) =
+                    InputMethod::with_backend(config, backend::inert(), false);
+                input_method.passthrough_mode = true;
+                let synced = Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            sync::run(&sync, keep).map_err(|error| error.to_string())
+                        })
+                        .await
+                        .unwrap_or_else(|error| Err(error.to_string()))
+                    },
+                    Message::Synced,
+                );
+                return (input_method, Command::batch(vec![command, synced]));
+            }
+        }
+        let chewing = backend::new(&config);
+        InputMethod::with_backend(config, chewing, false)
     }
 
     fn title(&self, _: Id) -> String {
         String::from("InputMethod")
     }
 
+    // The fork creates the popup's wl_surface and decides its buffer
+    // scale; this only multiplies what it draws on it.
+    // A fractional output scale needs wp-fractional-scale-v1 and a
+    // viewport on that surface, which the fork does not expose
+    fn scale_factor(&self, id: window::Id) -> f64 {
+        let overlay = self.state_overlay.as_ref().map(StateOverlay::id);
+        if id == self.osd.id()
+            || id == self.wizard.id()
+            || id == self.history_panel.id()
+            || id == self.snippet_dialog.id()
+            || id == self.error_window.id()
+            || Some(id) == overlay
+        {
+            self.config.popup.scale
+        } else {
+            self.outputs.popup_scale(&self.config.popup)
+        }
+    }
+
     fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
-            Message::Activate => {
-                self.state = State::PassThrough;
-                Command::none()
-            }
-            Message::Deactivate => {
-                self.chewing.editor.clear();
-                self.state = State::PassThrough;
-                hide_input_method_popup()
-            }
-            Message::KeyPressed(key_event, key, modifiers) => match self.state {
-                State::PreEdit => match key {
-                    Key::Named(Named::Backspace) => {
-                        self.chewing.editor.process_keyevent(
-                            self.chewing.keyboard.map(keyboard::KeyCode::Backspace),
-                        );
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::Space) => {
-                        if modifiers.shift {
-                            self.chewing.editor.process_keyevent(
-                                self.chewing
-                                    .keyboard
-                                    .map_with_mod(keyboard::KeyCode::Space, Mods::shift()),
-                            );
-                            Command::none()
-                        } else {
-                            self.chewing.editor.process_keyevent(
-                                self.chewing.keyboard.map(keyboard::KeyCode::Space),
-                            );
-                            self.preedit_string()
-                        }
-                    }
-                    Key::Named(Named::Enter) => self.commit_string(),
-                    Key::Named(Named::Escape) => {
-                        self.chewing.editor.clear();
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Esc));
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::Delete) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Del));
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::ArrowLeft) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Left));
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::ArrowRight) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Right));
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::ArrowDown) => self.open_popup(),
-                    Key::Named(Named::ArrowUp) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Up));
-                        self.preedit_string()
-                    }
-                    Key::Named(Named::Tab) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Tab));
-                        self.preedit_string()
-                    }
-                    _ => {
-                        if let Some(char) = key_event.utf8.as_ref().and_then(|s| s.chars().last()) {
-                            self.chewing
-                                .editor
-                                .process_keyevent(self.chewing.keyboard.map_ascii(char as u8));
-                            self.preedit_string()
-                        } else {
-                            Command::none()
-                        }
-                    }
-                },
-                State::Popup => match key.as_ref() {
-                    Key::Character("1") => self.num_select(0),
-                    Key::Character("2") => self.num_select(1),
-                    Key::Character("3") => self.num_select(2),
-                    Key::Character("4") => self.num_select(3),
-                    Key::Character("5") => self.num_select(4),
-                    Key::Character("6") => self.num_select(5),
-                    Key::Character("7") => self.num_select(6),
-                    Key::Character("8") => self.num_select(7),
-                    Key::Character("9") => self.num_select(8),
-                    Key::Character("0") => self.num_select(9),
-                    Key::Named(Named::ArrowDown) => {
-                        let total_pages = self.chewing.editor.total_page().unwrap();
-                        if self.index == min(self.candidates.len(), self.max_candidates) - 1
-                            || (self.page == total_pages - 1
-                                && self.index == self.candidates.len() % self.max_candidates - 1)
-                        {
-                            self.chewing.editor.process_keyevent(
-                                self.chewing.keyboard.map(keyboard::KeyCode::Down),
-                            );
-                            self.candidates =
-                                self.chewing.editor.all_candidates().unwrap_or_default();
-                            self.index = 0;
-                            self.page = 0;
-                            self.pages = vec![self.candidates
-                                [0..min(self.max_candidates, self.candidates.len())]
-                                .to_vec()];
-                        } else if self.page == total_pages - 1 {
-                            self.index =
-                                min(self.candidates.len() % self.max_candidates, self.index + 1)
-                        } else {
-                            self.index += 1
-                        }
-                        Command::none()
-                    }
-                    Key::Named(Named::ArrowUp) => {
-                        self.index = self.index.saturating_sub(1);
-                        Command::none()
-                    }
-                    Key::Named(Named::ArrowLeft) => {
-                        if self.page != 0 && self.page % self.max_pages == 0 {
-                            let mut pages = Vec::new();
-                            let page_index = self.page / (self.max_pages - 1) - 1;
-                            let page_size = self.max_candidates * self.max_pages;
-                            for p_i in 0..self.max_pages {
-                                let page = self.candidates[p_i * self.max_candidates
-                                    + page_index * page_size
-                                    ..(p_i + 1) * self.max_candidates + page_index * page_size]
-                                    .to_vec();
-                                pages.push(page);
-                            }
-                            self.pages = pages;
-                        }
-                        self.page = self.page.saturating_sub(1);
-                        Command::none()
-                    }
-                    Key::Named(Named::ArrowRight) => {
-                        let total_pages = self.chewing.editor.total_page().unwrap();
-                        if total_pages > 1 && (self.page == self.max_pages - 1 || self.page == 0) {
-                            let mut pages = Vec::new();
-                            let page_index = self.page / (self.max_pages - 1);
-                            let num_rows =
-                                min(total_pages - self.max_pages * page_index, self.max_pages);
-                            let page_size = self.max_candidates * self.max_pages;
-                            for p_i in 0..num_rows {
-                                let page = self.candidates[p_i * self.max_candidates
-                                    + page_index * page_size
-                                    ..min(
-                                        (p_i + 1) * self.max_candidates + page_index * page_size,
-                                        self.candidates.len(),
-                                    )]
-                                    .to_vec();
-                                pages.push(page);
-                            }
-                            self.pages = pages;
-                        }
-                        self.page = min(self.page + 1, total_pages - 1);
-                        if self.page == total_pages - 1 {
-                            self.index =
-                                min(self.index, self.candidates.len() % self.max_candidates - 1);
-                        }
-                        Command::none()
-                    }
-                    Key::Named(Named::Enter) => {
-                        let _ = self
-                            .chewing
-                            .editor
-                            .select(self.page * self.max_candidates + self.index);
-                        self.current_preedit = self.chewing.preedit();
-                        self.state = State::WaitingForDone;
-                        self.popup = false;
-                        self.set_cursor_position();
-                        Command::batch(vec![
-                            input_method_action(ActionInner::SetPreeditString {
-                                string: self.chewing.preedit(),
-                                cursor_begin: self.cursor_position as i32,
-                                cursor_end: self.cursor_position as i32,
-                            }),
-                            input_method_action(ActionInner::Commit),
-                            hide_input_method_popup(),
-                        ])
-                    }
-                    Key::Named(Named::Escape) => {
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map(keyboard::KeyCode::Esc));
-                        self.state = State::PreEdit;
-                        self.popup = false;
-                        self.set_cursor_position();
-                        Command::batch(vec![
-                            input_method_action(ActionInner::SetPreeditString {
-                                string: self.chewing.preedit(),
-                                cursor_begin: self.cursor_position as i32,
-                                cursor_end: self.cursor_position as i32,
-                            }),
-                            input_method_action(ActionInner::Commit),
-                            hide_input_method_popup(),
-                        ])
-                    }
-                    _ => Command::none(),
-                },
-                State::WaitingForDone => {
-                    // Do nothing if text input client is not ready
-                    // TODO: add timer for misbehaving/slow/laggy clients
-                    Command::none()
-                }
-                State::PassThrough => {
-                    if self.passthrough_mode {
-                        if key == Key::Named(Named::Shift) {
-                            self.shift_set = true;
-                            Command::none()
-                        } else {
-                            self.shift_set = false;
-                            virtual_keyboard_action(VKActionInner::KeyPressed(key_event))
-                        }
-                    } else if key == Key::Named(Named::Shift) {
-                        self.shift_set = true;
-                        Command::none()
-                    } else if key == Key::Named(Named::Space) {
-                        self.shift_set = false;
-                        if modifiers.shift {
-                            self.chewing.editor.process_keyevent(
-                                self.chewing
-                                    .keyboard
-                                    .map_with_mod(keyboard::KeyCode::Space, Mods::shift()),
-                            );
-                            Command::none()
-                        } else {
-                            virtual_keyboard_action(VKActionInner::KeyPressed(key_event))
-                        }
-                    } else if let Some(char) =
-                        key_event.utf8.as_ref().and_then(|s| s.chars().last())
-                    {
-                        self.shift_set = false;
-                        self.chewing
-                            .editor
-                            .process_keyevent(self.chewing.keyboard.map_ascii(char as u8));
-                        if self.chewing.preedit().is_empty() {
-                            virtual_keyboard_action(VKActionInner::KeyPressed(key_event))
-                        } else {
-                            self.preedit_string()
-                        }
-                    } else {
-                        self.shift_set = false;
-                        virtual_keyboard_action(VKActionInner::KeyPressed(key_event))
-                    }
-                }
-            },
-            Message::KeyReleased(key_event, key, _modifiers) => match self.state {
-                State::PassThrough => {
-                    if key == Key::Named(Named::Shift) && self.shift_set {
-                        self.shift_set = false;
-                        self.passthrough_mode = !self.passthrough_mode;
-                        Command::none()
-                    } else {
-                        virtual_keyboard_action(VKActionInner::KeyReleased(key_event))
-                    }
+        if let Message::KeyPressed(key_event, _, modifiers) = &message {
+            let composing = self.active && !self.passthrough_mode && !self.popup;
+            if let Some(key) = self.zhuyin_key(key_event, modifiers).filter(|_| composing) {
+                let layout = self.config.keyboard.layout;
+                if let Some(timing) = &mut self.key_timing {
+                    timing.key(layout, key);
                 }
-                State::PreEdit | State::Popup | State::WaitingForDone => Command::none(),
-            },
-            Message::Modifiers(_modifiers, raw_modifiers) => {
-                virtual_keyboard_action(VKActionInner::Modifiers(raw_modifiers))
             }
-            Message::Done => match self.state {
-                State::WaitingForDone => {
-                    if self.popup {
-                        self.state = State::Popup;
-                        show_input_method_popup()
-                    } else if !self.current_preedit.is_empty() {
-                        self.state = State::PreEdit;
-                        Command::none()
-                    } else {
-                        self.state = State::PassThrough;
-                        Command::none()
-                    }
-                }
-                State::PreEdit | State::Popup | State::PassThrough => Command::none(),
-            },
-            Message::UpdatePopup { page, index } => {
-                self.page = page;
-                self.index = index;
-                Command::none()
+        }
+        if let Message::KeyPressed(key_event, key, modifiers) = &message {
+            if log::log_enabled!(log::Level::Debug) && self.key_log.allow() {
+                log::debug!(
+                    "key {} {modifiers:?} in {}",
+                    self.key_name(key_event, key),
+                    self.state_summary()
+                );
             }
-            Message::ClosePopup => {
-                let _ = self
-                    .chewing
-                    .editor
-                    .select(self.page * self.max_candidates + self.index);
-                self.current_preedit = self.chewing.preedit();
-                self.state = State::WaitingForDone;
-                self.popup = false;
-                self.set_cursor_position();
-                Command::batch(vec![
-                    input_method_action(ActionInner::SetPreeditString {
-                        string: self.chewing.preedit(),
-                        cursor_begin: self.cursor_position as i32,
-                        cursor_end: self.cursor_position as i32,
-                    }),
-                    input_method_action(ActionInner::Commit),
-                    hide_input_method_popup(),
-                ])
+        }
+        // Only the variant name, the key events would flood the overlay
+        let cause = self.state_overlay.as_ref().map(|_| {
+            let message = format!("{message:?}");
+            message
+                .split(['(', ' ', '{'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        let page = (self.popup, self.grid.page);
+        let mut command = if self.latency.is_none() {
+            self.handle(message)
+        } else {
+            self.handle_measured(message)
+        };
+        if let Some(error) = self.chewing.take_error() {
+            // Keys pass through rather than vanish into a missing editor
+            self.passthrough_mode = true;
+            command = Command::batch(vec![
+                command,
+                self.error_window.open(&error, self.config.surfaces.error),
+            ]);
+        }
+        if self.watchdog.is_degraded() {
+            // The client never acknowledges, so do not wait for it
+            while matches!(self.state, State::WaitingForDone) {
+                command = Command::batch(vec![command, self.handle(Message::Done)]);
+            }
+        } else if matches!(self.state, State::WaitingForDone) {
+            command = Command::batch(vec![command, self.watchdog.arm(self.preedit_len)]);
+        }
+        let single_page = self.config.popup.compact || self.config.popup.bar;
+        if single_page && self.popup && page != (true, self.grid.page) {
+            self.page_changed = Some(Instant::now());
+        }
+        if let Some(cause) = cause {
+            let summary = self.state_summary();
+            if let Some(overlay) = &mut self.state_overlay {
+                overlay.record(summary, &cause);
             }
         }
+        self.publish_candidates();
+        idle::set_composing(
+            self.config.session.inhibit_idle
+                && self.active
+                && !matches!(self.state, State::PassThrough),
+            Duration::from_secs(self.config.session.inhibit_idle_timeout),
+        );
+        command
     }
 
-    fn view(&self, _id: window::Id) -> Element<Message> {
-        container(
-            row(self
-                .pages
-                .iter()
-                .enumerate()
-                .map(|(page, list)| {
-                    column(
-                        list.iter()
-                            .enumerate()
-                            .map(|(index, char)| {
-                                selection_field(
-                                    row(vec![
-                                        text((index + 1) % 10)
-                                            .size(50)
-                                            .style(if page != self.page % self.max_pages {
-                                                Color::TRANSPARENT
-                                            } else {
-                                                Color::WHITE
-                                            })
-                                            .into(),
-                                        text(char).size(50).into(),
-                                    ])
-                                    .align_items(Alignment::Center)
-                                    .padding(5.0)
-                                    .spacing(4.0),
-                                )
-                                .set_indexes(page, index)
-                                .selected(self.page % self.max_pages, self.index)
-                                .on_press(Message::ClosePopup)
-                                .on_select(Message::UpdatePopup { page, index })
-                                .into()
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                    .spacing(5.0)
-                    .padding(5.0)
-                    .align_items(Alignment::Center)
+    fn view(&self, id: window::Id) -> Element<Message> {
+        if id == self.osd.id() {
+            return self.osd.view();
+        }
+        if id == self.wizard.id() {
+            return self.wizard.view();
+        }
+        if id == self.history_panel.id() {
+            return self
+                .history_panel
+                .view(&self.history, self.config.preedit.tone_marks);
+        }
+        if id == self.snippet_dialog.id() {
+            return self.snippet_dialog.view();
+        }
+        if id == self.error_window.id() {
+            return self.error_window.view();
+        }
+        if let Some(overlay) = self.state_overlay.as_ref().filter(|o| o.id() == id) {
+            return overlay.view();
+        }
+        if !self.popup {
+            // Only the completions are shown while typing
+            return self.popup_container(self.completion_row());
+        }
+        let popup_style = &self.config.popup.style;
+        let style = GridStyle {
+            compact: self.config.popup.compact,
+            bar: self.config.popup.bar,
+            vertical: self.config.popup.writing == Writing::Vertical,
+            fade: self.page_fade(),
+            highlight: self.config.popup.highlight,
+            selected: popup_style.selected.map(color),
+            selected_text: popup_style.selected_text.map(color),
+            hovered: popup_style.hovered.map(color),
+            hovered_text: popup_style.hovered_text.map(color),
+            radius: popup_style.border_radius,
+            indicator: self.config.popup.page_indicator,
+            indicator_color: popup_style.indicator.map(color),
+            indicator_width: popup_style.indicator_width,
+            inactive_opacity: self.config.popup.inactive_page_opacity,
+            label_color: popup_style
+                .label
+                .or(popup_style.text)
+                .map_or_else(|| foreground(&self.theme(id)), color),
+            font: self.font,
+            text_size: self.config.popup.font.size.into(),
+            label_size: self.config.popup.font.label_size.into(),
+            weight: weight(popup_style.weight),
+            selected_weight: weight(popup_style.selected_weight.unwrap_or(popup_style.weight)),
+            hovered_weight: weight(popup_style.hovered_weight.unwrap_or(popup_style.weight)),
+            label_weight: weight(popup_style.label_weight),
+            tooltips: self.config.popup.tooltips && !self.masked(),
+            masked: self.masked(),
+        };
+        let pages = self.grid.view(&self.candidates, &style).map(Message::Grid);
+        let header = match (&self.char_map, &self.shape) {
+            (Some(char_map), _) => Some(char_map.title()),
+            (None, Some(query)) => Some(query.preedit()),
+            (None, None) => self.pinned.then(|| "Pinned".to_string()),
+        };
+        let mut content = match header {
+            Some(header) => vec![text(header).size(16).font(self.font).into(), pages],
+            None => vec![pages],
+        };
+        if self.config.popup.composition
+            && self.char_map.is_none()
+            && self.shape.is_none()
+            && !self.masked()
+        {
+            let composition = variants::localize(&self.chewing.preedit(), self.config.region);
+            let composition = tone::present(&composition, self.config.preedit.tone_marks);
+            let characters = composition.chars().enumerate().map(|(index, ch)| {
+                mouse_area(text(ch).size(20).font(self.font))
+                    .on_press(Message::EditAt(index))
                     .into()
-                })
-                .collect::<Vec<_>>())
-            .padding(2.0),
-        )
-        .padding(5.0)
-        .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
-            Box::new(CustomTheme),
-        ))
-        .into()
+            });
+            content.insert(
+                0,
+                row(characters.collect::<Vec<_>>())
+                    .spacing(2.0)
+                    .padding(4.0)
+                    .into(),
+            );
+        }
+        if self.char_map.is_none()
+            && self.shape.is_none()
+            && !self.completions.is_empty()
+            && !self.masked()
+        {
+            content.push(self.completion_row());
+        }
+        let content: Element<Message> = match content.len() {
+            1 => content.remove(0),
+            _ => column(content).padding(2.0).into(),
+        };
+        self.popup_container(content)
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        listen_raw(|event, status| match (event.clone(), status) {
+        let events = listen_raw(|event, status| match (event.clone(), status) {
             (
                 Event::PlatformSpecific(event::PlatformSpecific::Wayland(
                     event::wayland::Event::InputMethod(event),
@@ -630,28 +2864,185 @@ impl Application for InputMethod {
                     Some(Message::Modifiers(modifiers, raw_modifiers))
                 }
             },
+            (
+                Event::PlatformSpecific(event::PlatformSpecific::Wayland(
+                    event::wayland::Event::Output(event, output),
+                )),
+                _,
+            ) => Some(Message::Output(event, output)),
             _ => None,
-        })
+        });
+        // Only ticks while a page fades in, the popup is otherwise drawn on
+        // changes alone
+        let fade = if self.page_changed.is_some() {
+            iced::time::every(Duration::from_millis(16)).map(|_| Message::PageFadeTick)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch(vec![
+            events,
+            fade,
+            session::shutdown_signals(),
+            session::lock_signals(),
+            ipc::subscription(),
+            if self.config.popup.export_candidates {
+                ipc::candidate_signal()
+            } else {
+                Subscription::none()
+            },
+            config_watch::subscription(),
+            if self.config.popup.hide_while_sharing {
+                screencast::subscription()
+            } else {
+                Subscription::none()
+            },
+            if self.config.osd.respect_dnd {
+                dnd::subscription()
+            } else {
+                Subscription::none()
+            },
+            if self.config.popup.theme == PopupTheme::System {
+                appearance::subscription()
+            } else {
+                Subscription::none()
+            },
+        ])
+    }
+
+    fn theme(&self, _id: window::Id) -> Theme {
+        match self.config.popup.theme {
+            PopupTheme::Dark => Theme::Dark,
+            PopupTheme::Light => Theme::Light,
+            PopupTheme::System if self.dark_mode => Theme::Dark,
+            PopupTheme::System => Theme::Light,
+        }
     }
 
     fn style(&self) -> <Self::Theme as application::StyleSheet>::Style {
-        <Self::Theme as application::StyleSheet>::Style::Custom(Box::new(CustomTheme))
+        <Self::Theme as application::StyleSheet>::Style::Custom(Box::new(CustomTheme::default()))
+    }
+}
+
+/// The font named in `popup.font.family`, or the default one.
+fn popup_font(font: &config::PopupFont) -> Font {
+    // Names are kept for the life of the process, each family leaked once
+    // however often the config is reloaded
+    static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let Some(family) = &font.family else {
+        return Font::default();
+    };
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    let name = match names.iter().find(|name| **name == family.as_str()) {
+        Some(name) => *name,
+        None => {
+            let name: &'static str = Box::leak(family.clone().into_boxed_str());
+            names.push(name);
+            name
+        }
+    };
+    Font::with_name(name)
+}
+
+/// Loads `popup.font.file` into the renderer.
+fn load_font_file(font: &config::PopupFont) -> Command<Message> {
+    let Some(path) = &font.file else {
+        return Command::none();
+    };
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let path = path.clone();
+            font::load(bytes).map(move |result| Message::FontLoaded(path.clone(), result))
+        }
+        Err(error) => {
+            log::warn!("cannot load the font {}: {error}", path.display());
+            Command::none()
+        }
+    }
+}
+
+/// A grid labelled with the selection keys, a page per key at most.
+fn candidate_grid(popup: &config::Popup) -> CandidateGrid {
+    CandidateGrid::new(popup.selection_labels(), popup.columns.clamp(2, 6))
+}
+
+/// Whether `key` moves through the candidate pages, see
+/// [`InputMethod::page_key`].
+fn is_page_key(key: Named) -> bool {
+    matches!(
+        key,
+        Named::ArrowLeft
+            | Named::ArrowRight
+            | Named::PageUp
+            | Named::PageDown
+            | Named::Home
+            | Named::End
+    )
+}
+
+/// The character of a key that types exactly one.
+fn single_char(typed: &str) -> Option<char> {
+    let mut chars = typed.chars();
+    chars.next().filter(|_| chars.next().is_none())
+}
+
+/// Text and border color of the popup for the configured theme.
+fn foreground(theme: &Theme) -> Color {
+    match theme {
+        Theme::Light => Color::BLACK,
+        _ => Color::WHITE,
+    }
+}
+
+fn background(theme: &Theme) -> Color {
+    match theme {
+        Theme::Light => Color::WHITE,
+        _ => Color::BLACK,
+    }
+}
+
+/// A configured color.
+fn color(hex: config::HexColor) -> Color {
+    let [r, g, b, a] = hex.0;
+    Color::from_rgba8(r, g, b, f32::from(a) / 255.0)
+}
+
+fn weight(weight: config::FontWeight) -> font::Weight {
+    match weight {
+        config::FontWeight::Thin => font::Weight::Thin,
+        config::FontWeight::Light => font::Weight::Light,
+        config::FontWeight::Normal => font::Weight::Normal,
+        config::FontWeight::Medium => font::Weight::Medium,
+        config::FontWeight::Semibold => font::Weight::Semibold,
+        config::FontWeight::Bold => font::Weight::Bold,
+        config::FontWeight::Black => font::Weight::Black,
     }
 }
 
-pub struct CustomTheme;
+/// The frame of the popup in the colors and shape of `popup.style`; the
+/// other windows keep the defaults.
+#[derive(Default)]
+pub struct CustomTheme(pub config::PopupStyle);
 
 impl container::StyleSheet for CustomTheme {
     type Style = iced::Theme;
 
-    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+    fn appearance(&self, style: &Self::Style) -> container::Appearance {
+        let text = self.0.text.map_or_else(|| foreground(style), color);
+        let fill = self.0.background.map_or_else(|| background(style), color);
         container::Appearance {
+            text_color: Some(text),
             border: Border {
-                color: Color::from_rgb(1.0, 1.0, 1.0),
-                width: 3.0,
-                radius: 10.0.into(),
+                color: self.0.border.map_or(text, color),
+                width: self.0.border_width.max(0.0),
+                radius: self.0.border_radius.max(0.0).into(),
             },
-            background: Some(Color::from_rgb(0.0, 0.0, 0.0).into()),
+            background: Some(
+                Color {
+                    a: fill.a * self.0.background_opacity.clamp(0.0, 1.0),
+                    ..fill
+                }
+                .into(),
+            ),
             ..container::Appearance::default()
         }
     }
@@ -668,3 +3059,94 @@ impl iced_style::application::StyleSheet for CustomTheme {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An editor holding a composition as typed, without converting it.
+    struct Typed(String);
+
+    impl Backend for Typed {
+        fn process_key(&mut self, _key: KeyCode) {}
+
+        fn process_shift_key(&mut self, _key: KeyCode) {}
+
+        fn process_ascii(&mut self, ascii: u8) {
+            self.0.push(ascii as char);
+        }
+
+        fn preedit(&self) -> String {
+            self.0.clone()
+        }
+
+        fn composing_syllable(&self) -> bool {
+            false
+        }
+
+        fn cursor(&self) -> usize {
+            self.0.chars().count()
+        }
+
+        fn segments(&self) -> Vec<Range<usize>> {
+            Vec::new()
+        }
+
+        fn reading(&self, _range: Range<usize>) -> String {
+            String::new()
+        }
+
+        fn candidates(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn select(&mut self, _index: usize) {}
+
+        fn clear(&mut self) {
+            self.0.clear();
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    /// An input method in the middle of picking a candidate for `preedit`,
+    /// pinned, with a key buffered and English mode left on.
+    fn stuck(preedit: &str) -> InputMethod {
+        let chewing = Box::new(Typed(preedit.to_string()));
+        let (mut input_method, _) = InputMethod::with_backend(Config::default(), chewing, true);
+        input_method.active = true;
+        input_method.state = State::Popup;
+        input_method.popup = true;
+        input_method.pinned = true;
+        input_method.passthrough_mode = true;
+        input_method.buffered_keys.push(b'j');
+        input_method.shape = Some(shape::Query::default());
+        input_method
+    }
+
+    fn assert_reset(input_method: &InputMethod) {
+        assert!(input_method.chewing.preedit().is_empty());
+        assert!(matches!(input_method.state, State::PassThrough));
+        assert!(!input_method.popup && !input_method.pinned);
+        assert!(!input_method.passthrough_mode);
+        assert!(input_method.buffered_keys.is_empty());
+        assert!(input_method.shape.is_none());
+    }
+
+    #[test]
+    fn force_reset_commits_what_is_shown_and_forgets_the_rest() {
+        let mut input_method = stuck("測試");
+        let _ = input_method.force_reset();
+        assert_eq!(input_method.last_commit, "測試");
+        assert_reset(&input_method);
+    }
+
+    #[test]
+    fn force_reset_commits_nothing_without_a_text_field() {
+        let mut input_method = stuck("測試");
+        input_method.active = false;
+        let _ = input_method.force_reset();
+        assert!(input_method.last_commit.is_empty());
+        assert_reset(&input_method);
+    }
+}
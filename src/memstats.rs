@@ -0,0 +1,85 @@
+//! Approximate memory accounting for `chewingwlctl memstats`, to see what
+//! idle resource release or lazy loading would save. Figures are estimates
+//! from sizes the input method knows, next to what the kernel reports.
+use std::fs;
+
+use crate::{config::Config, dictionary, user_data};
+
+/// Bytes per pixel of the ARGB buffers surfaces are drawn into.
+const BYTES_PER_PIXEL: f64 = 4.0;
+/// Buffers a surface keeps, one shown and one being drawn.
+const BUFFERS: f64 = 2.0;
+
+#[derive(Default)]
+pub struct MemStats {
+    lines: Vec<(String, usize)>,
+}
+
+impl MemStats {
+    pub fn add(&mut self, name: impl Into<String>, bytes: usize) {
+        self.lines.push((name.into(), bytes));
+    }
+
+    /// Dictionary files are read or mapped whole, so their size is what
+    /// loading them costs.
+    pub fn add_dictionaries(&mut self, config: &Config, loaded: bool) {
+        if !loaded {
+            self.add("dictionaries (not loaded)", 0);
+            return;
+        }
+        for path in dictionary::select(&config.dictionaries) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.add(format!("dictionary {name}"), file_size(&path));
+        }
+        if let Ok(path) = user_data::user_dictionary_path() {
+            self.add("user dictionary", file_size(&path));
+        }
+    }
+
+    /// A surface of `width` by `height` logical pixels drawn at `scale`.
+    pub fn add_surface(&mut self, name: &str, (width, height): (f64, f64), scale: f64) {
+        let pixels = width * scale * height * scale;
+        self.add(
+            format!("surface {name}"),
+            (pixels * BYTES_PER_PIXEL * BUFFERS) as usize,
+        );
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for field in ["VmRSS", "VmHWM"] {
+            if let Some(value) = status(field) {
+                report.push_str(&format!("process {field}: {value}\n"));
+            }
+        }
+        for (name, bytes) in &self.lines {
+            report.push_str(&format!("{name}: {}\n", kib(*bytes)));
+        }
+        let total: usize = self.lines.iter().map(|(_, bytes)| bytes).sum();
+        report.push_str(&format!("estimated total: {}\n", kib(total)));
+        report
+    }
+}
+
+/// Heap held by a list of strings.
+pub fn strings(list: &[String]) -> usize {
+    list.capacity() * std::mem::size_of::<String>()
+        + list.iter().map(String::capacity).sum::<usize>()
+}
+
+fn file_size(path: &std::path::Path) -> usize {
+    fs::metadata(path).map_or(0, |metadata| metadata.len() as usize)
+}
+
+fn kib(bytes: usize) -> String {
+    format!("{} kB", bytes.div_ceil(1024))
+}
+
+/// A field of `/proc/self/status`, as the kernel formats it.
+fn status(field: &str) -> Option<String> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(field)?.strip_prefix(':')?;
+        Some(value.trim().to_string())
+    })
+}
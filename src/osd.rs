@@ -0,0 +1,137 @@
+//! Short-lived notices on an overlay layer surface, for feedback that cannot
+//! go through the input method popup.
+use std::time::Duration;
+
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+        },
+    },
+    widget::{container, text},
+    window, Command, Element,
+};
+
+use crate::{
+    config::{self, Surface},
+    surface, text_index, CustomTheme, Message,
+};
+
+/// The narrowest the notice gets.
+const WIDTH: u32 = 420;
+/// Roughly the advance of one display column at the notice's text size.
+const COLUMN_WIDTH: u32 = 9;
+const HEIGHT: u32 = 40;
+
+pub struct Osd {
+    id: window::Id,
+    text: Option<String>,
+    /// Bumped on every notice so an older timeout cannot hide a newer one.
+    generation: u64,
+    timeout: Duration,
+    respect_dnd: bool,
+    /// Whether the desktop is in do-not-disturb mode, as last polled.
+    do_not_disturb: bool,
+    surface: Surface,
+}
+
+impl Osd {
+    pub fn new(config: &config::Osd, surface: Surface) -> Self {
+        let mut osd = Osd {
+            id: window::Id::unique(),
+            text: None,
+            generation: 0,
+            timeout: Duration::ZERO,
+            respect_dnd: true,
+            do_not_disturb: false,
+            surface,
+        };
+        osd.configure(config, surface);
+        osd
+    }
+
+    /// Takes `config` and `surface` for the notices shown from now on.
+    pub fn configure(&mut self, config: &config::Osd, surface: Surface) {
+        self.timeout = Duration::from_millis(config.timeout_ms);
+        self.respect_dnd = config.respect_dnd;
+        self.surface = surface;
+    }
+
+    pub fn set_do_not_disturb(&mut self, active: bool) {
+        self.do_not_disturb = active;
+    }
+
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.text.is_some()
+    }
+
+    /// The size of the surface while a notice is shown. It keeps the size of
+    /// the notice that opened it.
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.text.as_deref().map(|text| (width(text), HEIGHT))
+    }
+
+    /// Shows `notice`, replacing the current one, and hides it after the
+    /// timeout.
+    pub fn show(&mut self, notice: impl Into<String>) -> Command<Message> {
+        let notice = notice.into();
+        if self.respect_dnd && self.text.is_none() && self.do_not_disturb {
+            log::info!("not shown while in do not disturb: {notice}");
+            return Command::none();
+        }
+        let create = if self.text.is_none() {
+            get_layer_surface(surface::configured(
+                self.surface,
+                SctkLayerSurfaceSettings {
+                    id: self.id,
+                    layer: Layer::Overlay,
+                    keyboard_interactivity: KeyboardInteractivity::None,
+                    anchor: Anchor::BOTTOM,
+                    size: Some((Some(width(&notice)), Some(HEIGHT))),
+                    namespace: "chewingwl-osd".into(),
+                    ..Default::default()
+                },
+            ))
+        } else {
+            Command::none()
+        };
+        self.text = Some(notice);
+        self.generation += 1;
+        let generation = self.generation;
+        let timeout = self.timeout;
+        Command::batch(vec![
+            create,
+            Command::perform(tokio::time::sleep(timeout), move |_| {
+                Message::OsdTimeout(generation)
+            }),
+        ])
+    }
+
+    /// Hides the notice if `generation` is still the one shown.
+    pub fn timeout(&mut self, generation: u64) -> Command<Message> {
+        if generation == self.generation && self.text.take().is_some() {
+            destroy_layer_surface(self.id)
+        } else {
+            Command::none()
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        container(text(self.text.as_deref().unwrap_or_default()).size(16))
+            .padding(8.0)
+            .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+                Box::new(CustomTheme::default()),
+            ))
+            .into()
+    }
+}
+
+fn width(notice: &str) -> u32 {
+    let width = text_index::columns(notice, usize::MAX) as u32 * COLUMN_WIDTH + 16;
+    width.max(WIDTH)
+}
@@ -0,0 +1,81 @@
+//! Connected outputs and the popup scale configured for them.
+use iced_core::event::wayland::OutputEvent;
+use wayland_client::protocol::wl_output::WlOutput;
+
+use crate::config;
+
+#[derive(Default)]
+pub struct Outputs {
+    outputs: Vec<(WlOutput, Option<String>)>,
+    /// The output the compositor says is focused, see
+    /// [`crate::focus::focused_output`].
+    focused: Option<String>,
+}
+
+impl Outputs {
+    pub fn update(&mut self, event: OutputEvent, output: WlOutput) {
+        match event {
+            OutputEvent::Created(info) => {
+                let name = info.and_then(|info| info.name);
+                self.outputs.push((output, name));
+            }
+            OutputEvent::InfoUpdate(info) => {
+                if let Some(entry) = self.outputs.iter_mut().find(|(o, _)| *o == output) {
+                    entry.1 = info.name;
+                }
+            }
+            OutputEvent::Removed => self.outputs.retain(|(o, _)| *o != output),
+        }
+    }
+
+    pub fn set_focused(&mut self, name: Option<String>) {
+        self.focused = name;
+    }
+
+    /// The scale multiplier for the popup. The input method protocol does
+    /// not tell which output the popup is on; it opens on the focused one,
+    /// where the compositor says which that is. Otherwise a per-output
+    /// override only applies when it is the same for every connected output.
+    pub fn popup_scale(&self, config: &config::Popup) -> f64 {
+        let override_for = |name: Option<&String>| {
+            name.and_then(|name| config.output_scale.get(name))
+                .copied()
+                .unwrap_or(1.0)
+        };
+        if let Some(focused) = &self.focused {
+            return config.scale * override_for(Some(focused));
+        }
+        let mut overrides = self
+            .outputs
+            .iter()
+            .map(|(_, name)| override_for(name.as_ref()));
+        let output_scale = match overrides.next() {
+            Some(first) if overrides.all(|scale| scale == first) => first,
+            _ => 1.0,
+        };
+        config.scale * output_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_popup_follows_the_focused_output() {
+        let config = config::Popup {
+            scale: 1.5,
+            output_scale: [("DP-1".to_string(), 2.0), ("eDP-1".to_string(), 1.0)].into(),
+            ..config::Popup::default()
+        };
+        let mut outputs = Outputs::default();
+        outputs.set_focused(Some("DP-1".to_string()));
+        assert_eq!(outputs.popup_scale(&config), 3.0);
+        outputs.set_focused(Some("eDP-1".to_string()));
+        assert_eq!(outputs.popup_scale(&config), 1.5);
+        outputs.set_focused(Some("HDMI-A-1".to_string()));
+        assert_eq!(outputs.popup_scale(&config), 1.5);
+        outputs.set_focused(None);
+        assert_eq!(outputs.popup_scale(&config), 1.5);
+    }
+}
@@ -0,0 +1,137 @@
+//! Keys that never mean anything to the input method, forwarded untouched
+//! in every state so they keep working mid-composition without disturbing
+//! the buffer.
+use std::ops::RangeInclusive;
+
+use iced::keyboard::key::Named;
+use iced_core::{event::wayland::Modifiers, keyboard::Key};
+
+/// `XKB_KEY_F1` through `XKB_KEY_F35`.
+const FUNCTION_KEYS: RangeInclusive<u32> = 0xffbe..=0xffe0;
+/// `XKB_KEY_Print`.
+const PRINT: u32 = 0xff61;
+/// The `XF86` vendor keysyms: media, volume, brightness and the like.
+const XF86_KEYS: RangeInclusive<u32> = 0x1008ff00..=0x1008ffff;
+
+/// Whether `keysym` bypasses the input method, being a function, media or
+/// Print key or one of the `extra` keysyms from `keys.always_forward`.
+pub fn always(keysym: u32, extra: &[u32]) -> bool {
+    FUNCTION_KEYS.contains(&keysym)
+        || keysym == PRINT
+        || XF86_KEYS.contains(&keysym)
+        || extra.contains(&keysym)
+}
+
+/// Whether the key is Super or pressed with Super held. Those are window
+/// manager bindings, which must keep working while composing, so they are
+/// never interpreted, not even as the input method's own hotkeys.
+pub fn super_shortcut(key: &Key, modifiers: &Modifiers) -> bool {
+    modifiers.logo || *key == Key::Named(Named::Super)
+}
+
+/// Keycodes whose press went to the virtual keyboard, so their release
+/// follows it there, and only theirs.
+#[derive(Debug, Default)]
+pub struct Forwarded(Vec<u32>);
+
+impl Forwarded {
+    pub fn press(&mut self, code: u32) {
+        if !self.0.contains(&code) {
+            self.0.push(code);
+        }
+    }
+
+    /// Whether the press of `code` was forwarded, forgetting it.
+    pub fn release(&mut self, code: u32) -> bool {
+        let forwarded = self.0.contains(&code);
+        self.0.retain(|held| *held != code);
+        forwarded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(modifiers: &[&str]) -> Modifiers {
+        Modifiers {
+            ctrl: modifiers.contains(&"ctrl"),
+            alt: modifiers.contains(&"alt"),
+            shift: modifiers.contains(&"shift"),
+            logo: modifiers.contains(&"logo"),
+            ..Modifiers::default()
+        }
+    }
+
+    fn character(ch: &str) -> Key {
+        Key::Character(ch.into())
+    }
+
+    #[test]
+    fn super_combinations_are_forwarded() {
+        let sequence = [
+            (Key::Named(Named::Super), with(&[])),
+            (character("q"), with(&["logo", "shift"])),
+            (character("1"), with(&["logo"])),
+            (Key::Named(Named::ArrowLeft), with(&["ctrl", "alt", "logo"])),
+            (Key::Named(Named::Space), with(&["logo"])),
+            (Key::Named(Named::Enter), with(&["logo", "shift"])),
+            (Key::Named(Named::Shift), with(&["logo"])),
+        ];
+        for (key, modifiers) in &sequence {
+            assert!(super_shortcut(key, modifiers), "{key:?} {modifiers:?}");
+        }
+    }
+
+    #[test]
+    fn other_modifiers_are_left_to_the_input_method() {
+        let sequence = [
+            (character("j"), with(&[])),
+            (character("c"), with(&["ctrl"])),
+            (character("s"), with(&["ctrl", "alt"])),
+            (Key::Named(Named::Space), with(&["shift"])),
+            (Key::Named(Named::Control), with(&["ctrl"])),
+            (Key::Named(Named::Alt), with(&["alt", "shift"])),
+        ];
+        for (key, modifiers) in &sequence {
+            assert!(!super_shortcut(key, modifiers), "{key:?} {modifiers:?}");
+        }
+    }
+
+    #[test]
+    fn super_released_first_ends_the_shortcut() {
+        // Super+Shift+Q typed, then Super let go before Q
+        let mut forwarded = Forwarded::default();
+        for (code, key) in [(125, Key::Named(Named::Super)), (16, character("q"))] {
+            if super_shortcut(&key, &with(&["logo", "shift"])) {
+                forwarded.press(code);
+            }
+        }
+        assert!(forwarded.release(125));
+        // Q is no Super combination any more, its release still follows
+        // the press
+        assert!(!super_shortcut(&character("q"), &with(&["shift"])));
+        assert!(forwarded.release(16));
+    }
+
+    #[test]
+    fn only_releases_of_forwarded_presses_are_forwarded() {
+        let mut forwarded = Forwarded::default();
+        forwarded.press(30);
+        forwarded.press(30);
+        assert!(!forwarded.release(88));
+        assert!(forwarded.release(30));
+        assert!(!forwarded.release(30));
+    }
+
+    #[test]
+    fn function_and_media_keys_are_forwarded() {
+        for keysym in [0xffbe, 0xffc9, 0xff61, 0x1008ff14, 0x1008ff02] {
+            assert!(always(keysym, &[]), "{keysym:#x}");
+        }
+        for keysym in [0x61, 0xff0d, 0xff13] {
+            assert!(!always(keysym, &[]), "{keysym:#x}");
+        }
+        assert!(always(0xff13, &[0xff13]));
+    }
+}
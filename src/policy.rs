@@ -0,0 +1,152 @@
+//! What the configurable key policies do to the composition, worked out
+//! apart from the editor as steps the input method carries out in order,
+//! so that the order itself can be tested.
+use crate::config::{EnterPolicy, ShortcutPolicy, StrayKeyPolicy};
+
+/// One thing done to the composition or with the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Backs out of candidate selection and hides the popup, keeping the
+    /// composition.
+    LeavePopup,
+    /// Commits the composition as shown.
+    Commit,
+    /// Drops the composition and clears the preedit.
+    Cancel,
+    /// Drops any half-typed syllable or Compose sequence, which shows
+    /// nothing to clear.
+    Reset,
+    /// Commits an ideographic space of its own.
+    FullWidthSpace,
+    /// Types the ascii key into the composition.
+    Type(u8),
+    /// Keeps the ascii key, typed once a candidate is picked.
+    Buffer(u8),
+    /// Sends the key to the application.
+    Forward,
+}
+
+/// A Ctrl or Alt shortcut. The composition is settled as `policy` says
+/// before the application gets the key, so e.g. Ctrl+S saves what was
+/// typed; with nothing `composing` the key goes straight through.
+pub fn shortcut(policy: ShortcutPolicy, composing: bool, popup: bool) -> Vec<Step> {
+    if !composing {
+        return vec![Step::Forward];
+    }
+    let mut steps = Vec::new();
+    if popup {
+        steps.push(Step::LeavePopup);
+    }
+    steps.push(match policy {
+        ShortcutPolicy::Commit => Step::Commit,
+        ShortcutPolicy::Cancel => Step::Cancel,
+    });
+    steps.push(Step::Forward);
+    steps
+}
+
+/// Enter in Chinese mode with nothing composed, which always reaches the
+/// application, last.
+pub fn enter_when_empty(policy: EnterPolicy) -> Vec<Step> {
+    match policy {
+        EnterPolicy::Forward => vec![Step::Forward],
+        EnterPolicy::Reset => vec![Step::Reset, Step::Forward],
+        EnterPolicy::FullWidthSpace => vec![Step::FullWidthSpace, Step::Forward],
+    }
+}
+
+/// A printable key that picks no candidate while the popup is open,
+/// `ascii` once mapped to the editor's keyboard.
+pub fn stray_in_popup(policy: StrayKeyPolicy, ascii: u8) -> Vec<Step> {
+    match policy {
+        StrayKeyPolicy::Ignore => Vec::new(),
+        StrayKeyPolicy::Cancel => vec![Step::LeavePopup, Step::Type(ascii)],
+        StrayKeyPolicy::Buffer => vec![Step::Buffer(ascii)],
+    }
+}
+
+/// Whether the popup opens on the first phrase rather than at `cursor`:
+/// a batch walks every phrase in turn, so with the cursor past the `len`
+/// characters composed it starts over.
+pub fn batch_from_start(batch: bool, cursor: usize, len: usize) -> bool {
+    batch && cursor >= len
+}
+
+/// Where the phrase after the one of `phrase_len` characters picked at
+/// `cursor` starts, for a batch or a pinned popup to move on to, or `None`
+/// past the `len` characters composed. An editor that shifts the cursor
+/// by itself has already moved past the pick.
+pub fn next_phrase(
+    cursor: usize,
+    phrase_len: usize,
+    auto_shift: bool,
+    len: usize,
+) -> Option<usize> {
+    let next = if auto_shift {
+        cursor
+    } else {
+        cursor + phrase_len
+    };
+    (next < len).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_settle_the_composition_before_going_out() {
+        use Step::*;
+        for policy in [ShortcutPolicy::Commit, ShortcutPolicy::Cancel] {
+            assert_eq!(shortcut(policy, false, false), [Forward]);
+        }
+        assert_eq!(
+            shortcut(ShortcutPolicy::Commit, true, false),
+            [Commit, Forward]
+        );
+        assert_eq!(
+            shortcut(ShortcutPolicy::Cancel, true, false),
+            [Cancel, Forward]
+        );
+        let steps = shortcut(ShortcutPolicy::Commit, true, true);
+        assert_eq!(steps, [LeavePopup, Commit, Forward]);
+        let steps = shortcut(ShortcutPolicy::Cancel, true, true);
+        assert_eq!(steps, [LeavePopup, Cancel, Forward]);
+    }
+
+    #[test]
+    fn enter_goes_out_after_what_its_policy_adds() {
+        use Step::*;
+        assert_eq!(enter_when_empty(EnterPolicy::Forward), [Forward]);
+        assert_eq!(enter_when_empty(EnterPolicy::Reset), [Reset, Forward]);
+        let steps = enter_when_empty(EnterPolicy::FullWidthSpace);
+        assert_eq!(steps, [FullWidthSpace, Forward]);
+    }
+
+    #[test]
+    fn a_batch_starts_over_from_the_end() {
+        assert!(batch_from_start(true, 5, 5));
+        assert!(!batch_from_start(true, 2, 5));
+        assert!(!batch_from_start(false, 5, 5));
+    }
+
+    #[test]
+    fn a_batch_walks_every_phrase_once() {
+        // 測試 一 下吧, picked from the start
+        assert_eq!(next_phrase(0, 2, false, 5), Some(2));
+        assert_eq!(next_phrase(2, 1, false, 5), Some(3));
+        assert_eq!(next_phrase(3, 2, false, 5), None);
+        // The editor moved past each pick itself
+        assert_eq!(next_phrase(2, 2, true, 5), Some(2));
+        assert_eq!(next_phrase(5, 2, true, 5), None);
+    }
+
+    #[test]
+    fn stray_keys_never_reach_the_application() {
+        use Step::*;
+        assert!(stray_in_popup(StrayKeyPolicy::Ignore, b'j').is_empty());
+        let steps = stray_in_popup(StrayKeyPolicy::Cancel, b'j');
+        assert_eq!(steps, [LeavePopup, Type(b'j')]);
+        assert_eq!(stray_in_popup(StrayKeyPolicy::Buffer, b'j'), [Buffer(b'j')]);
+    }
+}
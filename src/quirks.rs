@@ -0,0 +1,128 @@
+//! What is learned about misbehaving clients, kept across restarts. One
+//! client per line: its identity, the longest preedit in bytes it is known
+//! to acknowledge, and until when, in seconds since the epoch. A limit
+//! lapses after [`LIMIT_DAYS`], so a client that was fixed, or was only
+//! slow that once, gets long preedits again.
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use crate::{config, user_data};
+
+/// How long a limit on a client's preedits holds.
+const LIMIT_DAYS: u64 = 7;
+
+fn quirks_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("quirks"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[derive(Default)]
+pub struct Quirks {
+    /// Each client's longest preedit, and when the limit lapses.
+    max_preedit: HashMap<String, (usize, u64)>,
+    /// Where they are saved, none for quirks kept in memory only.
+    path: Option<PathBuf>,
+}
+
+impl Quirks {
+    pub fn load() -> Self {
+        let Some(path) = quirks_path() else {
+            return Quirks::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                warn!("failed to read quirks {}: {error}", path.display());
+                String::new()
+            }
+        };
+        Quirks {
+            max_preedit: parse(&text, now()),
+            path: Some(path),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.max_preedit.len()
+    }
+
+    /// The longest preedit `client` takes, if longer ones are known to go
+    /// unacknowledged.
+    pub fn max_preedit(&self, client: &str) -> Option<usize> {
+        let (len, until) = self.max_preedit.get(client)?;
+        (now() < *until).then_some(*len)
+    }
+
+    /// Remembers that `client` takes no preedit longer than `len` bytes,
+    /// for [`LIMIT_DAYS`].
+    pub fn limit_preedit(&mut self, client: String, len: usize) {
+        let until = now() + LIMIT_DAYS * 24 * 60 * 60;
+        self.max_preedit.insert(client, (len, until));
+        self.save();
+    }
+
+    /// Forgets every limit, for `chewingwlctl quirks clear`.
+    pub fn clear(&mut self) {
+        self.max_preedit.clear();
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let now = now();
+        let mut lines: Vec<String> = self
+            .max_preedit
+            .iter()
+            .filter(|(_, (_, until))| now < *until)
+            .map(|(client, (len, until))| format!("{client} {len} {until}\n"))
+            .collect();
+        lines.sort();
+        user_data::write_later("quirks", path.clone(), lines.concat().into_bytes());
+    }
+}
+
+/// The limits in `text` that still hold at `now`.
+fn parse(text: &str, now: u64) -> HashMap<String, (usize, u64)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ' ');
+            let until = fields.next()?.parse().ok()?;
+            let len = fields.next()?.parse().ok()?;
+            let client = fields.next()?;
+            (now < until).then(|| (client.to_string(), (len, until)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_lapse() {
+        let quirks = parse("foot 96 1000\norg.gnome.gedit 48 3000\n", 2000);
+        assert_eq!(quirks.get("foot"), None);
+        assert_eq!(quirks.get("org.gnome.gedit"), Some(&(48, 3000)));
+    }
+
+    #[test]
+    fn clients_may_have_spaces_in_their_names() {
+        let quirks = parse("Some Client 48 3000\nbroken 3000\n", 2000);
+        assert_eq!(quirks.get("Some Client"), Some(&(48, 3000)));
+        assert_eq!(quirks.len(), 1);
+    }
+}
@@ -0,0 +1,75 @@
+//! Notices screen sharing, so the popup can keep what is composed out of a
+//! meeting. The ScreenCast portal tells only the application sharing, so
+//! PipeWire is asked instead: while a screen or window is shared, the
+//! compositor's portal backend keeps a video source node that, unlike a
+//! camera, belongs to no device.
+use std::{any::TypeId, process::Command as Process, time::Duration};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+
+use crate::Message;
+
+/// How often PipeWire is asked.
+const POLL: Duration = Duration::from_secs(3);
+
+/// Whether `pw-cli ls Node` lists a video source with no device.
+fn sharing(nodes: &str) -> bool {
+    nodes.split("\tid ").any(|node| {
+        node.contains("media.class = \"Video/Source\"") && !node.contains("device.id = ")
+    })
+}
+
+struct Screencast;
+
+/// Emits [`Message::ScreenShared`] whenever sharing starts or stops.
+pub fn subscription() -> Subscription<Message> {
+    subscription::channel(TypeId::of::<Screencast>(), 1, |mut output| async move {
+        let mut shared = false;
+        let mut interval = tokio::time::interval(POLL);
+        loop {
+            interval.tick().await;
+            let now = tokio::task::spawn_blocking(|| {
+                let output = Process::new("pw-cli").args(["ls", "Node"]).output().ok()?;
+                Some(sharing(&String::from_utf8_lossy(&output.stdout)))
+            })
+            .await
+            .ok()
+            .flatten();
+            let Some(now) = now else {
+                log::warn!("pw-cli is unavailable, screen sharing goes unnoticed");
+                return std::future::pending().await;
+            };
+            if now != shared {
+                shared = now;
+                let _ = output.send(Message::ScreenShared(shared)).await;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "\tid 52, type PipeWire:Interface:Node/3
+ \t\tobject.serial = \"52\"
+ \t\tobject.path = \"v4l2:/dev/video0\"
+ \t\tdevice.id = \"44\"
+ \t\tnode.name = \"v4l2_input.pci-0000_00_14.0-usb-0_6_1.0\"
+ \t\tmedia.class = \"Video/Source\"
+";
+    const PORTAL: &str = "\tid 97, type PipeWire:Interface:Node/3
+ \t\tobject.serial = \"1204\"
+ \t\tclient.id = \"88\"
+ \t\tnode.name = \"xdpw_stream\"
+ \t\tmedia.class = \"Video/Source\"
+";
+
+    #[test]
+    fn portal_streams_count_cameras_do_not() {
+        assert!(!sharing(CAMERA));
+        assert!(sharing(PORTAL));
+        assert!(sharing(&format!("{CAMERA}{PORTAL}")));
+        assert!(!sharing(""));
+    }
+}
@@ -0,0 +1,130 @@
+//! `chewingwl --screenshot-theme <file.png>`: draws the candidate popup with
+//! sample candidates in the configured theme and writes it as a PNG, so a
+//! theme can be tried without a compositor or a text field to type in.
+use std::path::Path;
+
+use iced::{mouse, window, Application, Color, Font, Pixels, Rectangle, Size};
+use iced_core::{layout, renderer, widget::Tree, Layout};
+use iced_tiny_skia::graphics::Viewport;
+
+use crate::{backend, config::Config, InputMethod};
+
+/// Candidates for 測試 (cè shì), enough to fill more than one page.
+const SAMPLES: [&str; 14] = [
+    "測試", "側室", "策士", "廁所", "冊", "側", "測", "策", "廁", "惻", "筴", "畟", "萗", "憡",
+];
+/// The most room the popup is given, in logical pixels.
+const MAX_SIZE: Size = Size::new(1600.0, 1000.0);
+
+pub fn run(args: &[String]) -> i32 {
+    let [path] = args else {
+        eprintln!("usage: chewingwl --screenshot-theme <file.png>");
+        return 2;
+    };
+    match render(Config::load(), Path::new(path)) {
+        Ok(()) => {
+            println!("wrote {path}");
+            0
+        }
+        Err(error) => {
+            eprintln!("cannot write {path}: {error}");
+            1
+        }
+    }
+}
+
+fn render(config: Config, path: &Path) -> Result<(), String> {
+    let scale = config.popup.scale;
+    if let Some(font) = &config.popup.font.file {
+        let bytes = std::fs::read(font)
+            .map_err(|error| format!("cannot read the font {}: {error}", font.display()))?;
+        iced_tiny_skia::graphics::text::font_system()
+            .write()
+            .map_err(|_| "the font system is unavailable".to_string())?
+            .load_font(bytes.into());
+    }
+    // Nothing is typed, so no dictionary is opened or backed up
+    let (mut input_method, _) = InputMethod::with_backend(config, backend::inert(), true);
+    input_method.popup = true;
+    input_method.list_candidates(SAMPLES.map(String::from).to_vec());
+    let id = window::Id::MAIN;
+    let theme = input_method.theme(id);
+    let element = input_method.view(id);
+
+    let mut renderer = iced::Renderer::TinySkia(iced_tiny_skia::Renderer::new(
+        iced_tiny_skia::Backend::new(),
+        Font::default(),
+        Pixels(16.0),
+    ));
+    let mut tree = Tree::new(&element);
+    let node = element.as_widget().layout(
+        &mut tree,
+        &renderer,
+        &layout::Limits::new(Size::ZERO, MAX_SIZE),
+    );
+    let size = node.size();
+    let viewport = Viewport::with_physical_size(
+        Size::new(
+            (size.width * scale as f32).ceil() as u32,
+            (size.height * scale as f32).ceil() as u32,
+        ),
+        scale,
+    );
+    element.as_widget().draw(
+        &tree,
+        &mut renderer,
+        &theme,
+        &renderer::Style {
+            text_color: Color::WHITE,
+        },
+        Layout::new(&node),
+        mouse::Cursor::Unavailable,
+        &Rectangle::with_size(size),
+    );
+
+    let physical = viewport.physical_size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(physical.width, physical.height).ok_or("the popup has no size")?;
+    let mut clip_mask =
+        tiny_skia::Mask::new(physical.width, physical.height).ok_or("the popup has no size")?;
+    // The only variant unless the GPU renderer is built in
+    #[allow(irrefutable_let_patterns)]
+    let iced::Renderer::TinySkia(renderer) = &mut renderer
+    else {
+        return Err("the software renderer is unavailable".into());
+    };
+    renderer.with_primitives(|backend, primitives| {
+        backend.draw(
+            &mut pixmap.as_mut(),
+            &mut clip_mask,
+            primitives,
+            &viewport,
+            &[Rectangle::with_size(Size::new(
+                physical.width as f32,
+                physical.height as f32,
+            ))],
+            Color::TRANSPARENT,
+            &[] as &[&str],
+        );
+    });
+    // The renderer draws for the BGRA buffers of the windowing system
+    for pixel in pixmap.data_mut().chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    pixmap.save_png(path).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_popup_is_written_as_a_png() {
+        let path = std::env::temp_dir().join(format!("chewingwl-theme-{}.png", std::process::id()));
+        let rendered = render(Config::default(), &path);
+        let png = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(rendered, Ok(()));
+        assert!(png.is_ok_and(|png| png.starts_with(b"\x89PNG")));
+    }
+}
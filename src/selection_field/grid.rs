@@ -0,0 +1,667 @@
+//! Candidates laid out in labelled columns, one page per column, with a
+//! highlight that can be moved by keys or the pointer.
+use std::{cmp::min, ops::Range};
+
+use iced::{
+    font::Weight,
+    widget::{column, container, row, text, tooltip},
+    Alignment, Color, Element, Font,
+};
+use iced_core::Border;
+
+use super::{
+    style::{self, Appearance, SelectionField},
+    widget::selection_field,
+};
+use crate::{
+    background,
+    config::{Highlight, PageIndicator},
+    foreground, unihan,
+};
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The pointer moved over the entry at `index` in the `column` on screen.
+    Highlight { column: usize, index: usize },
+    /// The highlighted entry was clicked.
+    Pick,
+    /// The wheel turned up over an entry.
+    PreviousPage,
+    /// The wheel turned down over an entry.
+    NextPage,
+}
+
+/// How the grid is drawn, from the popup settings.
+pub struct GridStyle {
+    /// Show only the column of the highlighted page.
+    pub compact: bool,
+    /// Lay the page out as a row.
+    pub bar: bool,
+    /// Write each candidate top to bottom, the page right to left and the
+    /// pages under each other.
+    pub vertical: bool,
+    /// How far the active page has faded in after a page change, from 0 to 1.
+    pub fade: f32,
+    pub highlight: Highlight,
+    /// Background of the highlighted entry, overriding `highlight`.
+    pub selected: Option<Color>,
+    /// Text of the highlighted entry, overriding `highlight`.
+    pub selected_text: Option<Color>,
+    /// Background of the entry under the pointer, if not highlighted.
+    pub hovered: Option<Color>,
+    /// Text of the entry under the pointer.
+    pub hovered_text: Option<Color>,
+    /// Corner radius of the popup, which the highlight follows.
+    pub radius: f32,
+    pub indicator: PageIndicator,
+    /// Color of the frame of the active page, the text color if not set.
+    pub indicator_color: Option<Color>,
+    pub indicator_width: f32,
+    pub inactive_opacity: f32,
+    /// Color of the labels on the active page.
+    pub label_color: Color,
+    pub font: Font,
+    /// Size of the candidates.
+    pub text_size: f32,
+    /// Size of the labels.
+    pub label_size: f32,
+    pub weight: Weight,
+    pub selected_weight: Weight,
+    pub hovered_weight: Weight,
+    pub label_weight: Weight,
+    /// Wrap entries so the hovered one can show a tooltip.
+    pub tooltips: bool,
+    /// Show the candidates as dots, keeping their length.
+    pub masked: bool,
+}
+
+pub struct CandidateGrid {
+    /// The highlighted page, counted over all candidates.
+    pub page: usize,
+    /// The highlighted entry on that page.
+    pub index: usize,
+    /// Show the strokes and radical of the highlighted entry underneath.
+    pub details: bool,
+    /// The columns on screen, as ranges of the candidate list so paging
+    /// never copies the strings.
+    columns: Vec<Range<usize>>,
+    labels: Vec<String>,
+    per_page: usize,
+    max_columns: usize,
+    /// The entry under the pointer, as column on screen and index.
+    hovered: Option<(usize, usize)>,
+    /// What the tooltip of the hovered entry says, once it is due.
+    tooltip: Option<String>,
+    /// Variants folded under each candidate, see [`crate::variants`].
+    folded: Vec<usize>,
+    /// The zhuyin reading of each candidate, if annotated.
+    readings: Vec<String>,
+}
+
+impl CandidateGrid {
+    /// A grid with a page per `labels`, the keys picking each candidate,
+    /// and `max_columns` pages on screen, at least two.
+    pub fn new(labels: Vec<String>, max_columns: usize) -> Self {
+        CandidateGrid {
+            page: 0,
+            index: 0,
+            details: false,
+            columns: Vec::new(),
+            per_page: labels.len(),
+            labels,
+            max_columns: max_columns.max(2),
+            hovered: None,
+            tooltip: None,
+            folded: Vec::new(),
+            readings: Vec::new(),
+        }
+    }
+
+    pub fn columns(&self) -> &[Range<usize>] {
+        &self.columns
+    }
+
+    /// The labels of the entries on a page, the keys picking them.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Where the highlight is in the whole candidate list.
+    pub fn position(&self) -> usize {
+        self.page * self.per_page + self.index
+    }
+
+    /// Where the entry at `index` on the current page is in the whole list.
+    pub fn position_of(&self, index: usize) -> usize {
+        self.page * self.per_page + index
+    }
+
+    /// The column on screen holding the highlighted page.
+    fn active_column(&self) -> usize {
+        self.page % self.max_columns
+    }
+
+    /// Shows the first page of `len` candidates, highlighting its first.
+    pub fn reset(&mut self, len: usize) {
+        self.page = 0;
+        self.index = 0;
+        self.details = false;
+        self.columns = vec![0..min(self.per_page, len)];
+        self.folded.clear();
+        self.readings.clear();
+        self.leave();
+    }
+
+    /// Marks each candidate with the number of variants folded under it.
+    pub fn fold(&mut self, folded: Vec<usize>) {
+        self.folded = folded;
+    }
+
+    /// Shows `readings` next to the candidates, in the same order.
+    pub fn annotate(&mut self, readings: Vec<String>) {
+        self.readings = readings;
+    }
+
+    /// Variants folded under the candidate at `position`.
+    pub fn folded_at(&self, position: usize) -> usize {
+        self.folded.get(position).copied().unwrap_or(0)
+    }
+
+    /// Forgets the hovered entry, whose slot now holds another candidate.
+    fn leave(&mut self) {
+        self.hovered = None;
+        self.tooltip = None;
+    }
+
+    /// Shows `text` next to the hovered entry.
+    pub fn show_tooltip(&mut self, text: String) {
+        if self.hovered.is_some() {
+            self.tooltip = Some(text);
+        }
+    }
+
+    /// Pages needed for `len` candidates.
+    pub fn total_pages(&self, len: usize) -> usize {
+        len.div_ceil(self.per_page)
+    }
+
+    /// Candidates on the current page, out of `len`.
+    pub fn page_len(&self, len: usize) -> usize {
+        min(self.per_page, len.saturating_sub(self.page * self.per_page))
+    }
+
+    /// Whether the highlight is on the last entry of its page, where Down
+    /// moves on to other phrases.
+    pub fn at_last(&self, len: usize) -> bool {
+        self.index + 1 >= self.page_len(len)
+    }
+
+    /// Moves the highlight down a row within the page.
+    pub fn next_index(&mut self, len: usize) {
+        self.index = min(self.index + 1, self.page_len(len).saturating_sub(1));
+    }
+
+    /// Moves the highlight to the next page, laying out the next group of
+    /// columns at the edge of the current one.
+    pub fn next_page(&mut self, len: usize) {
+        let total_pages = self.total_pages(len);
+        if total_pages == 0 {
+            return;
+        }
+        let next = self.page + 1;
+        if next < total_pages && (self.page == 0 || next % self.max_columns == 0) {
+            let group = next / self.max_columns;
+            let columns = min(total_pages - self.max_columns * group, self.max_columns);
+            self.columns = (0..columns)
+                .map(|column| self.column_range(group, column, len))
+                .collect();
+        }
+        self.page = min(self.page + 1, total_pages - 1);
+        self.index = min(self.index, self.page_len(len).saturating_sub(1));
+        self.leave();
+    }
+
+    /// Moves the highlight to the previous page, laying out the previous
+    /// group of columns at the edge of the current one.
+    pub fn previous_page(&mut self, len: usize) {
+        if self.page != 0 && self.page % self.max_columns == 0 {
+            let group = self.page / self.max_columns - 1;
+            self.columns = (0..self.max_columns)
+                .map(|column| self.column_range(group, column, len))
+                .collect();
+        }
+        self.page = self.page.saturating_sub(1);
+        self.leave();
+    }
+
+    /// Moves the highlight to the first candidate.
+    pub fn first(&mut self, len: usize) {
+        while self.page > 0 {
+            self.previous_page(len);
+        }
+        self.index = 0;
+    }
+
+    /// Moves the highlight to the last candidate, turning the pages one by
+    /// one so the columns are laid out as paging there would.
+    pub fn last(&mut self, len: usize) {
+        while self.page + 1 < self.total_pages(len) {
+            self.next_page(len);
+        }
+        self.index = self.page_len(len).saturating_sub(1);
+    }
+
+    fn column_range(&self, group: usize, column: usize, len: usize) -> Range<usize> {
+        let start = (group * self.max_columns + column) * self.per_page;
+        start..min(start + self.per_page, len)
+    }
+
+    /// Applies `event`, returning whether the pointer moved onto another
+    /// entry.
+    pub fn update(&mut self, event: Event) -> bool {
+        match event {
+            Event::Highlight { column, index } => {
+                self.page = self.page - self.active_column() + column;
+                self.index = index;
+                if self.hovered == Some((column, index)) {
+                    return false;
+                }
+                self.hovered = Some((column, index));
+                self.tooltip = None;
+                true
+            }
+            Event::Pick | Event::PreviousPage | Event::NextPage => false,
+        }
+    }
+
+    pub fn view<'a>(&self, candidates: &'a [String], style: &GridStyle) -> Element<'a, Event> {
+        let active = self.active_column();
+        let pages = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(page, _)| !(style.compact || style.bar) || *page == active)
+            .map(|(page, range)| {
+                let mut entries = candidates[range.clone()]
+                    .iter()
+                    .zip(&self.labels)
+                    .enumerate()
+                    .map(|(index, (candidate, label))| {
+                        let folded = self.folded_at(range.start + index);
+                        let selected = page == active && index == self.index;
+                        let hovered = !selected && self.hovered == Some((page, index));
+                        let (text_color, weight) = if selected {
+                            (style.selected_text, style.selected_weight)
+                        } else if hovered {
+                            (style.hovered_text, style.hovered_weight)
+                        } else {
+                            (None, style.weight)
+                        };
+                        // Only the active page is labelled, so the labels
+                        // always pick from the highlighted column
+                        let label_color = if page == active {
+                            let color = text_color.unwrap_or(style.label_color);
+                            Color {
+                                a: color.a * style.fade,
+                                ..color
+                            }
+                        } else {
+                            Color::TRANSPARENT
+                        };
+                        let label = text(label)
+                            .size(style.label_size)
+                            .font(Font {
+                                weight: style.label_weight,
+                                ..Font::default()
+                            })
+                            .style(label_color);
+                        let font = Font {
+                            weight,
+                            ..style.font
+                        };
+                        let shown = if style.masked {
+                            "●".repeat(candidate.chars().count())
+                        } else {
+                            candidate.clone()
+                        };
+                        let candidate: Element<'a, Event> = if style.vertical {
+                            column(
+                                shown
+                                    .chars()
+                                    .map(|c| text(c).size(style.text_size).font(font).into())
+                                    .collect::<Vec<_>>(),
+                            )
+                            .align_items(Alignment::Center)
+                            .into()
+                        } else {
+                            text(shown).size(style.text_size).font(font).into()
+                        };
+                        let mut content = vec![label.into(), candidate];
+                        if let Some(reading) = self
+                            .readings
+                            .get(range.start + index)
+                            .filter(|reading| !reading.is_empty() && !style.masked)
+                        {
+                            content.push(
+                                text(reading)
+                                    .size(style.text_size * 0.4)
+                                    .font(style.font)
+                                    .into(),
+                            );
+                        }
+                        if folded > 0 {
+                            content.push(text(format!("+{folded}")).size(20).into());
+                        }
+                        // Vertically, the label heads the candidate
+                        let content: Element<'a, Event> = if style.vertical {
+                            column(content)
+                                .align_items(Alignment::Center)
+                                .padding(5.0)
+                                .spacing(4.0)
+                                .into()
+                        } else {
+                            row(content)
+                                .align_items(Alignment::Center)
+                                .padding(5.0)
+                                .spacing(4.0)
+                                .into()
+                        };
+                        selection_field(if style.tooltips {
+                            self.tooltip(content, page, index)
+                        } else {
+                            content
+                        })
+                        .set_indexes(page, index)
+                        .selected(active, self.index)
+                        .style(SelectionField::custom(Palette {
+                            highlight: style.highlight,
+                            fill: style.selected,
+                            text: style.selected_text,
+                            hovered: style.hovered,
+                            hovered_text: style.hovered_text,
+                            radius: style.radius,
+                        }))
+                        .on_press(Event::Pick)
+                        .on_select(Event::Highlight {
+                            column: page,
+                            index,
+                        })
+                        .on_scroll(Event::PreviousPage, Event::NextPage)
+                        .into()
+                    })
+                    .collect::<Vec<_>>();
+                // Short pages keep the height of full ones, so the popup
+                // does not jump when paging onto the last
+                if candidates.len() > self.per_page {
+                    entries.extend(self.labels[range.len()..].iter().map(|label| {
+                        selection_field(
+                            row(vec![text(label)
+                                .size(style.label_size.max(style.text_size))
+                                .into()])
+                            .padding(5.0),
+                        )
+                        .disabled(true)
+                        .into()
+                    }));
+                }
+                if style.vertical {
+                    entries.reverse();
+                }
+                let entries: Element<'a, Event> = if style.bar || style.vertical {
+                    row(entries)
+                        .spacing(5.0)
+                        .padding(5.0)
+                        .align_items(Alignment::Center)
+                        .into()
+                } else {
+                    column(entries)
+                        .spacing(5.0)
+                        .padding(5.0)
+                        .align_items(Alignment::Center)
+                        .into()
+                };
+                container(entries)
+                    .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+                        Box::new(PageColumn {
+                            active: page == active,
+                            fade: style.fade,
+                            indicator: style.indicator,
+                            color: style.indicator_color,
+                            width: style.indicator_width,
+                            inactive_opacity: style.inactive_opacity,
+                        }),
+                    ))
+                    .into()
+            })
+            .collect::<Vec<_>>();
+        let pages: Element<'a, Event> = if style.vertical {
+            column(pages).padding(2.0).into()
+        } else {
+            row(pages).padding(2.0).into()
+        };
+        match candidates
+            .get(self.position())
+            .filter(|_| self.details && !style.masked)
+        {
+            Some(candidate) => column(vec![
+                pages,
+                container(text(unihan::describe(candidate)).size(20).font(style.font))
+                    .padding(8.0)
+                    .into(),
+            ])
+            .into(),
+            None => pages,
+        }
+    }
+
+    /// Every entry is wrapped, so the tooltip keeps track of the pointer
+    /// from before it is due; only the hovered one has anything to show.
+    fn tooltip<'a>(
+        &self,
+        content: impl Into<Element<'a, Event>>,
+        page: usize,
+        index: usize,
+    ) -> Element<'a, Event> {
+        match self
+            .tooltip
+            .as_ref()
+            .filter(|_| self.hovered == Some((page, index)))
+        {
+            Some(tip) => tooltip(content, text(tip).size(20), tooltip::Position::Bottom)
+                .gap(4.0)
+                .padding(6.0)
+                .style(iced::theme::Container::Box)
+                .into(),
+            None => tooltip(content, text(""), tooltip::Position::Bottom).into(),
+        }
+    }
+}
+
+/// The highlight colors picked in `popup.highlight`, or set in
+/// `popup.style`, and those of the entry under the pointer.
+struct Palette {
+    highlight: Highlight,
+    fill: Option<Color>,
+    text: Option<Color>,
+    hovered: Option<Color>,
+    hovered_text: Option<Color>,
+    radius: f32,
+}
+
+impl style::StyleSheet for Palette {
+    type Style = iced::Theme;
+
+    fn default(&self, style: &Self::Style) -> Appearance {
+        style::StyleSheet::default(style, &SelectionField::Default)
+    }
+
+    fn selected(&self, style: &Self::Style) -> Appearance {
+        let base = style::StyleSheet::selected(style, &SelectionField::Default);
+        let (fill, text) = match self.highlight {
+            Highlight::Blue => (Color::from_rgba(0.0, 0.07, 0.42, 1.0), base.text_color),
+            Highlight::Orange => (Color::from_rgb8(0xE6, 0x9F, 0x00), Color::BLACK),
+            Highlight::Inverted => (foreground(style), background(style)),
+        };
+        let text = self.text.unwrap_or(text);
+        Appearance {
+            background: Some(self.fill.unwrap_or(fill).into()),
+            text_color: text,
+            border: Border {
+                color: text,
+                // Nested in the popup's corners at the same curve
+                radius: (self.radius * 0.55).max(0.0).into(),
+                ..base.border
+            },
+            ..base
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style) -> Appearance {
+        let base = style::StyleSheet::default(style, &SelectionField::Default);
+        Appearance {
+            background: self.hovered.map(Into::into),
+            text_color: self.hovered_text.unwrap_or(base.text_color),
+            border: Border {
+                radius: (self.radius * 0.55).max(0.0).into(),
+                ..base.border
+            },
+            ..base
+        }
+    }
+
+    fn disabled(&self, style: &Self::Style) -> Appearance {
+        style::StyleSheet::disabled(style, &SelectionField::Default)
+    }
+}
+
+/// A candidate column, framed when it holds the highlighted page and
+/// faded otherwise.
+struct PageColumn {
+    active: bool,
+    fade: f32,
+    indicator: PageIndicator,
+    color: Option<Color>,
+    width: f32,
+    inactive_opacity: f32,
+}
+
+impl container::StyleSheet for PageColumn {
+    type Style = iced::Theme;
+
+    fn appearance(&self, style: &Self::Style) -> container::Appearance {
+        if !self.active {
+            return container::Appearance {
+                text_color: Some(Color {
+                    a: self.inactive_opacity.clamp(0.0, 1.0),
+                    ..foreground(style)
+                }),
+                ..container::Appearance::default()
+            };
+        }
+        let border = match self.indicator {
+            PageIndicator::Labels => Border::default(),
+            PageIndicator::Border => Border {
+                color: self.color.unwrap_or_else(|| foreground(style)),
+                width: self.width,
+                radius: 6.0.into(),
+            },
+        };
+        container::Appearance {
+            text_color: Some(Color {
+                a: self.fade,
+                ..foreground(style)
+            }),
+            border,
+            ..container::Appearance::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three candidates a page, two columns on screen.
+    fn grid(len: usize) -> CandidateGrid {
+        let mut grid = CandidateGrid::new(vec!["1".into(), "2".into(), "3".into()], 2);
+        grid.reset(len);
+        grid
+    }
+
+    #[test]
+    fn an_empty_list_pages_nowhere() {
+        let mut grid = grid(0);
+        assert_eq!(grid.total_pages(0), 0);
+        assert_eq!(grid.page_len(0), 0);
+        assert!(grid.at_last(0));
+        grid.next_index(0);
+        grid.next_page(0);
+        grid.last(0);
+        assert_eq!((grid.page, grid.index), (0, 0));
+    }
+
+    #[test]
+    fn an_exact_multiple_fills_the_last_page() {
+        let mut grid = grid(6);
+        grid.index = 2;
+        assert!(grid.at_last(6));
+        grid.next_page(6);
+        assert_eq!((grid.page, grid.index), (1, 2));
+        assert!(grid.at_last(6));
+        grid.next_index(6);
+        grid.next_page(6);
+        assert_eq!((grid.page, grid.index), (1, 2));
+        grid.first(6);
+        grid.last(6);
+        assert_eq!((grid.page, grid.index), (1, 2));
+    }
+
+    #[test]
+    fn a_short_last_page_clamps_the_highlight() {
+        let mut grid = grid(7);
+        grid.index = 2;
+        grid.next_page(7);
+        grid.next_page(7);
+        assert_eq!((grid.page, grid.index), (2, 0));
+        assert!(grid.at_last(7));
+        grid.last(7);
+        assert_eq!((grid.page, grid.index), (2, 0));
+    }
+
+    /// Whether the highlighted column on screen holds the highlighted entry.
+    fn shows_the_highlight(grid: &CandidateGrid) -> bool {
+        grid.columns()[grid.active_column()].contains(&grid.position())
+    }
+
+    #[test]
+    fn paging_across_column_groups_keeps_the_highlight_on_screen() {
+        let mut grid = grid(25);
+        while grid.page < 8 {
+            grid.next_page(25);
+            assert!(shows_the_highlight(&grid), "page {}", grid.page);
+        }
+        assert_eq!(grid.columns(), [24..25]);
+        while grid.page > 0 {
+            grid.previous_page(25);
+            assert!(shows_the_highlight(&grid), "page {}", grid.page);
+        }
+        let labels = (0..10).map(|label| label.to_string()).collect();
+        let mut grid = CandidateGrid::new(labels, 4);
+        grid.reset(120);
+        grid.last(120);
+        assert_eq!(grid.page, 11);
+        assert_eq!(grid.columns()[grid.active_column()], 110..120);
+        assert_eq!(grid.position(), 119);
+    }
+
+    #[test]
+    fn at_least_two_columns_are_shown() {
+        let mut grid = CandidateGrid::new(vec!["1".into()], 0);
+        grid.reset(3);
+        grid.next_page(3);
+        grid.next_page(3);
+        assert!(shows_the_highlight(&grid));
+        grid.previous_page(3);
+        assert!(shows_the_highlight(&grid));
+    }
+}
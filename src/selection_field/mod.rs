@@ -1,2 +1,3 @@
+pub mod grid;
 pub mod style;
 pub mod widget;
@@ -1,28 +1,25 @@
-//! Change the apperance of a button.
-use iced_core::{Background, Border, Color, Shadow, Vector};
+//! Change the appearance of a selection field.
+use iced_core::{Background, Border, Color, Shadow};
 use iced_style::Theme;
 
-/// The appearance of a button.
+/// The appearance of a selection field.
 #[derive(Debug, Clone, Copy)]
 pub struct Appearance {
-    /// The amount of offset to apply to the shadow of the
-    pub shadow_offset: Vector,
-    /// The [`Background`] of the button.
+    /// The [`Background`] of the field.
     pub background: Option<Background>,
-    /// The icon [`Color`] of the button.
+    /// The icon [`Color`] of the field.
     pub icon_color: Option<Color>,
-    /// The text [`Color`] of the button.
+    /// The text [`Color`] of the field.
     pub text_color: Color,
-    /// The [`Border`] of the buton.
+    /// The [`Border`] of the field.
     pub border: Border,
-    /// The [`Shadow`] of the
+    /// The [`Shadow`] of the field, offset included.
     pub shadow: Shadow,
 }
 
 impl std::default::Default for Appearance {
     fn default() -> Self {
         Self {
-            shadow_offset: Vector::default(),
             background: None,
             icon_color: None,
             text_color: Color::WHITE,
@@ -32,19 +29,26 @@ impl std::default::Default for Appearance {
     }
 }
 
-/// A set of rules that dictate the style of a button.
+/// A set of rules that dictate the style of a selection field.
 pub trait StyleSheet {
     /// The supported style of the [`StyleSheet`].
     type Style: Default;
 
-    /// Produces the active [`Appearance`] of a button.
+    /// Produces the active [`Appearance`] of a selection field.
     fn default(&self, style: &Self::Style) -> Appearance;
 
-    /// Produces the selected [`Appearance`] of a button.
+    /// Produces the selected [`Appearance`] of a selection field.
     fn selected(&self, style: &Self::Style) -> Appearance;
+
+    /// Produces the [`Appearance`] of a selection field under the pointer
+    /// but not selected.
+    fn hovered(&self, style: &Self::Style) -> Appearance;
+
+    /// Produces the [`Appearance`] of a placeholder that holds no entry.
+    fn disabled(&self, style: &Self::Style) -> Appearance;
 }
 
-/// The style of a button.
+/// The style of a selection field.
 #[derive(Default)]
 pub enum SelectionField {
     /// The primary style.
@@ -55,7 +59,7 @@ pub enum SelectionField {
 }
 
 impl SelectionField {
-    /// Creates a custom [`Button`] style variant.
+    /// Creates a custom [`SelectionField`] style variant.
     pub fn custom(style_sheet: impl StyleSheet<Style = Theme> + 'static) -> Self {
         Self::Custom(Box::new(style_sheet))
     }
@@ -64,13 +68,24 @@ impl SelectionField {
 impl StyleSheet for Theme {
     type Style = SelectionField;
 
-    fn default(&self, _style: &Self::Style) -> Appearance {
-        Appearance::default()
+    fn default(&self, style: &Self::Style) -> Appearance {
+        if let SelectionField::Custom(custom) = style {
+            return custom.default(self);
+        }
+        Appearance {
+            text_color: match self {
+                Theme::Light => Color::BLACK,
+                _ => Color::WHITE,
+            },
+            ..Appearance::default()
+        }
     }
 
-    fn selected(&self, _style: &Self::Style) -> Appearance {
+    fn selected(&self, style: &Self::Style) -> Appearance {
+        if let SelectionField::Custom(custom) = style {
+            return custom.selected(self);
+        }
         Appearance {
-            shadow_offset: Vector::default(),
             background: Some(Background::Color(Color::from_rgba(0.0, 0.07, 0.42, 1.0))),
             icon_color: None,
             text_color: Color::WHITE,
@@ -82,4 +97,21 @@ impl StyleSheet for Theme {
             shadow: Shadow::default(),
         }
     }
+
+    fn hovered(&self, style: &Self::Style) -> Appearance {
+        match style {
+            SelectionField::Default => self.default(style),
+            SelectionField::Custom(custom) => custom.hovered(self),
+        }
+    }
+
+    fn disabled(&self, style: &Self::Style) -> Appearance {
+        if let SelectionField::Custom(custom) = style {
+            return custom.disabled(self);
+        }
+        Appearance {
+            text_color: Color::TRANSPARENT,
+            ..Appearance::default()
+        }
+    }
 }
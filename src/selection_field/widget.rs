@@ -12,6 +12,9 @@ use iced_runtime::core::{
     Background, Clipboard, Color, Element, Layout, Length, Padding, Rectangle, Shell, Widget,
 };
 
+/// Pixels of touchpad scrolling that make up one wheel step.
+const SCROLL_STEP: f32 = 40.0;
+
 /// A generic widget that produces a message when pressed.
 #[allow(missing_debug_implementations)]
 pub struct SelectionField<'a, Message, Theme = crate::Theme, Renderer = iced::Renderer>
@@ -23,9 +26,12 @@ where
     content: Element<'a, Message, Theme, Renderer>,
     on_press: Option<Message>,
     on_select: Option<Message>,
+    /// Produced for each wheel step up and down.
+    on_scroll: Option<(Message, Message)>,
     page: usize,
     index: usize,
     is_selected: bool,
+    is_disabled: bool,
     width: Length,
     height: Length,
     padding: Padding,
@@ -44,9 +50,11 @@ where
             content: content.into(),
             on_press: None,
             on_select: None,
+            on_scroll: None,
             page: 0,
             index: 0,
             is_selected: false,
+            is_disabled: false,
             width: Length::Shrink,
             height: Length::Shrink,
             padding: Padding::new(2.0),
@@ -86,6 +94,13 @@ where
         self
     }
 
+    /// Sets the messages produced when the wheel is turned up, or down,
+    /// over the [`SelectionField`].
+    pub fn on_scroll(mut self, up: Message, down: Message) -> Self {
+        self.on_scroll = Some((up, down));
+        self
+    }
+
     /// Sets the index values
     pub fn set_indexes(mut self, page: usize, index: usize) -> Self {
         self.index = index;
@@ -99,6 +114,13 @@ where
         self
     }
 
+    /// Makes the [`SelectionField`] a placeholder that keeps its place in a
+    /// layout but draws nothing and ignores input.
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.is_disabled = disabled;
+        self
+    }
+
     /// Sets the style variant of this [`Button`].
     pub fn style(mut self, style: <Theme as StyleSheet>::Style) -> Self {
         self.style = style;
@@ -178,6 +200,9 @@ where
         ) {
             return event::Status::Captured;
         }
+        if self.is_disabled {
+            return event::Status::Ignored;
+        }
         let state = tree.state.downcast_mut::<State>();
         match event {
             Event::Mouse(mouse::Event::CursorMoved { .. }) => {
@@ -208,6 +233,26 @@ where
                     }
                 }
             }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                if let Some((up, down)) = &self.on_scroll {
+                    if cursor.is_over(layout.bounds()) {
+                        // Touchpads scroll by pixels, a few at a time
+                        state.scrolled += match delta {
+                            mouse::ScrollDelta::Lines { y, .. } => y,
+                            mouse::ScrollDelta::Pixels { y, .. } => y / SCROLL_STEP,
+                        };
+                        while state.scrolled >= 1.0 {
+                            state.scrolled -= 1.0;
+                            shell.publish(up.clone());
+                        }
+                        while state.scrolled <= -1.0 {
+                            state.scrolled += 1.0;
+                            shell.publish(down.clone());
+                        }
+                        return event::Status::Captured;
+                    }
+                }
+            }
             Event::Touch(touch::Event::FingerLost { .. })
             | Event::Mouse(mouse::Event::CursorLeft) => {
                 state.is_hovered = false;
@@ -231,8 +276,12 @@ where
     ) {
         let content_layout = layout.children().next().unwrap();
 
-        let styling = if self.is_selected {
+        let styling = if self.is_disabled {
+            theme.disabled(&self.style)
+        } else if self.is_selected {
             theme.selected(&self.style)
+        } else if cursor.is_over(layout.bounds()) {
+            theme.hovered(&self.style)
         } else {
             theme.default(&self.style)
         };
@@ -276,7 +325,7 @@ where
         _viewport: &Rectangle,
         _renderer: &Renderer,
     ) -> mouse::Interaction {
-        let is_mouse_over = cursor.is_over(layout.bounds());
+        let is_mouse_over = !self.is_disabled && cursor.is_over(layout.bounds());
         if is_mouse_over {
             mouse::Interaction::Pointer
         } else {
@@ -319,10 +368,12 @@ where
 }
 
 /// The local state of a [`Button`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct State {
     is_hovered: bool,
     is_pressed: bool,
+    /// Wheel steps short of a whole one, up being positive.
+    scrolled: f32,
 }
 
 impl State {
@@ -342,3 +393,260 @@ where
 {
     SelectionField::new(content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::widget::Space;
+    use iced_core::{clipboard, renderer::Null, touch::Finger, Point};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Message {
+        Press,
+        Select,
+        Up,
+        Down,
+    }
+
+    /// A 20x10 field at the origin, padded to 24x14.
+    fn field() -> SelectionField<'static, Message, iced_style::Theme, Null> {
+        SelectionField::new(Space::new(20.0, 10.0))
+            .on_press(Message::Press)
+            .on_select(Message::Select)
+            .on_scroll(Message::Up, Message::Down)
+    }
+
+    const INSIDE: Point = Point::new(5.0, 5.0);
+    const OUTSIDE: Point = Point::new(50.0, 50.0);
+
+    struct Harness {
+        field: SelectionField<'static, Message, iced_style::Theme, Null>,
+        tree: Tree,
+        node: layout::Node,
+    }
+
+    impl Harness {
+        fn new(field: SelectionField<'static, Message, iced_style::Theme, Null>) -> Self {
+            let mut tree = Tree::new(&field as &dyn Widget<Message, iced_style::Theme, Null>);
+            let limits = layout::Limits::new(Size::ZERO, Size::new(100.0, 100.0));
+            let node = field.layout(&mut tree, &Null, &limits);
+            Harness { field, tree, node }
+        }
+
+        fn send(&mut self, event: Event, at: Point) -> (event::Status, Vec<Message>) {
+            let mut messages = Vec::new();
+            let mut shell = Shell::new(&mut messages);
+            let status = self.field.on_event(
+                &mut self.tree,
+                event,
+                Layout::new(&self.node),
+                mouse::Cursor::Available(at),
+                &Null,
+                &mut clipboard::Null,
+                &mut shell,
+                &Rectangle::with_size(Size::new(100.0, 100.0)),
+            );
+            (status, messages)
+        }
+
+        fn state(&self) -> State {
+            *self.tree.state.downcast_ref::<State>()
+        }
+    }
+
+    fn pressed() -> Event {
+        Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+    }
+
+    fn released() -> Event {
+        Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+    }
+
+    fn lines(y: f32) -> Event {
+        Event::Mouse(mouse::Event::WheelScrolled {
+            delta: mouse::ScrollDelta::Lines { x: 0.0, y },
+        })
+    }
+
+    fn pixels(y: f32) -> Event {
+        Event::Mouse(mouse::Event::WheelScrolled {
+            delta: mouse::ScrollDelta::Pixels { x: 0.0, y },
+        })
+    }
+
+    fn finger(event: fn(Finger, Point) -> touch::Event, at: Point) -> Event {
+        Event::Touch(event(Finger(0), at))
+    }
+
+    fn finger_pressed(id: Finger, position: Point) -> touch::Event {
+        touch::Event::FingerPressed { id, position }
+    }
+
+    fn finger_lifted(id: Finger, position: Point) -> touch::Event {
+        touch::Event::FingerLifted { id, position }
+    }
+
+    fn finger_lost(id: Finger, position: Point) -> touch::Event {
+        touch::Event::FingerLost { id, position }
+    }
+
+    #[test]
+    fn press_and_release_inside_publishes_on_press() {
+        let mut harness = Harness::new(field());
+        assert_eq!(
+            harness.send(pressed(), INSIDE),
+            (event::Status::Captured, vec![])
+        );
+        assert!(harness.state().is_pressed);
+        assert_eq!(
+            harness.send(released(), INSIDE),
+            (event::Status::Captured, vec![Message::Press])
+        );
+        assert!(!harness.state().is_pressed);
+    }
+
+    #[test]
+    fn release_outside_after_press_inside_publishes_nothing() {
+        let mut harness = Harness::new(field());
+        harness.send(pressed(), INSIDE);
+        assert_eq!(
+            harness.send(released(), OUTSIDE),
+            (event::Status::Captured, vec![])
+        );
+        assert!(!harness.state().is_pressed);
+    }
+
+    #[test]
+    fn press_outside_is_ignored() {
+        let mut harness = Harness::new(field());
+        assert_eq!(
+            harness.send(pressed(), OUTSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert_eq!(
+            harness.send(released(), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+    }
+
+    #[test]
+    fn press_without_on_press_is_ignored() {
+        let mut harness = Harness::new(SelectionField::new(Space::new(20.0, 10.0)));
+        assert_eq!(
+            harness.send(pressed(), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert!(!harness.state().is_pressed);
+    }
+
+    #[test]
+    fn disabled_ignores_press_and_hover() {
+        let mut harness = Harness::new(field().disabled(true));
+        assert_eq!(
+            harness.send(pressed(), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert_eq!(
+            harness.send(released(), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        let moved = Event::Mouse(mouse::Event::CursorMoved { position: INSIDE });
+        assert_eq!(
+            harness.send(moved, INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert_eq!(harness.state(), State::default());
+    }
+
+    #[test]
+    fn finger_pressed_and_lifted_inside_publishes_on_press() {
+        let mut harness = Harness::new(field());
+        let (status, messages) = harness.send(finger(finger_pressed, INSIDE), INSIDE);
+        assert_eq!((status, messages), (event::Status::Captured, vec![]));
+        assert_eq!(
+            harness.send(finger(finger_lifted, INSIDE), INSIDE),
+            (event::Status::Captured, vec![Message::Press])
+        );
+    }
+
+    #[test]
+    fn finger_lost_cancels_the_press() {
+        let mut harness = Harness::new(field());
+        harness.send(finger(finger_pressed, INSIDE), INSIDE);
+        assert_eq!(
+            harness.send(finger(finger_lost, INSIDE), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert_eq!(harness.state(), State::default());
+        assert_eq!(
+            harness.send(finger(finger_lifted, INSIDE), INSIDE),
+            (event::Status::Ignored, vec![])
+        );
+    }
+
+    #[test]
+    fn hover_inside_publishes_on_select() {
+        let mut harness = Harness::new(field());
+        let moved = Event::Mouse(mouse::Event::CursorMoved { position: INSIDE });
+        assert_eq!(
+            harness.send(moved, INSIDE),
+            (event::Status::Captured, vec![Message::Select])
+        );
+        assert!(harness.state().is_hovered);
+    }
+
+    #[test]
+    fn hover_outside_publishes_nothing() {
+        let mut harness = Harness::new(field());
+        let moved = Event::Mouse(mouse::Event::CursorMoved { position: OUTSIDE });
+        assert_eq!(
+            harness.send(moved, OUTSIDE),
+            (event::Status::Ignored, vec![])
+        );
+        assert!(!harness.state().is_hovered);
+    }
+
+    #[test]
+    fn cursor_left_clears_hover() {
+        let mut harness = Harness::new(field());
+        let moved = Event::Mouse(mouse::Event::CursorMoved { position: INSIDE });
+        harness.send(moved, INSIDE);
+        harness.send(Event::Mouse(mouse::Event::CursorLeft), OUTSIDE);
+        assert_eq!(harness.state(), State::default());
+    }
+
+    #[test]
+    fn wheel_steps_publish_on_scroll() {
+        let mut harness = Harness::new(field());
+        assert_eq!(
+            harness.send(lines(-1.0), INSIDE),
+            (event::Status::Captured, vec![Message::Down])
+        );
+        assert_eq!(
+            harness.send(lines(2.0), INSIDE),
+            (event::Status::Captured, vec![Message::Up, Message::Up])
+        );
+    }
+
+    #[test]
+    fn touchpad_pixels_add_up_to_a_step() {
+        let mut harness = Harness::new(field());
+        assert_eq!(
+            harness.send(pixels(-30.0), INSIDE),
+            (event::Status::Captured, vec![])
+        );
+        assert_eq!(
+            harness.send(pixels(-30.0), INSIDE),
+            (event::Status::Captured, vec![Message::Down])
+        );
+    }
+
+    #[test]
+    fn wheel_outside_publishes_nothing() {
+        let mut harness = Harness::new(field());
+        assert_eq!(
+            harness.send(lines(-1.0), OUTSIDE),
+            (event::Status::Ignored, vec![])
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Keeps an unfinished composition across a logout or restart.
+use std::{fs, io, path::PathBuf};
+
+use iced::{futures::SinkExt, subscription, Subscription};
+use log::warn;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{config, user_data, Message};
+
+fn session_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("session"))
+}
+
+/// Stores `text` so the next start can offer it again.
+pub fn save(text: &str) {
+    let Some(path) = session_path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| user_data::write_atomic(&path, text.as_bytes()));
+    if let Err(error) = result {
+        warn!("failed to save session to {}: {error}", path.display());
+    }
+}
+
+/// Returns the composition saved by the previous run, removing it so it is
+/// offered only once.
+pub fn take() -> Option<String> {
+    let path = session_path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            warn!("failed to read session {}: {error}", path.display());
+            return None;
+        }
+    };
+    let _ = fs::remove_file(&path);
+    Some(text).filter(|text| !text.is_empty())
+}
+
+#[cfg(feature = "dbus")]
+async fn watch_lock(
+    output: &mut iced::futures::channel::mpsc::Sender<Message>,
+) -> zbus::Result<()> {
+    use iced::futures::StreamExt;
+
+    let connection = zbus::Connection::system().await?;
+    let reply = connection
+        .call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "GetSession",
+            &("auto",),
+        )
+        .await?;
+    let path: zbus::zvariant::OwnedObjectPath = reply.body().deserialize()?;
+    let session = zbus::Proxy::new(
+        &connection,
+        "org.freedesktop.login1",
+        path,
+        "org.freedesktop.login1.Session",
+    )
+    .await?;
+    // Lock and Unlock ask screen lockers to act, LockedHint is what they
+    // report having done
+    let mut hints = session.receive_property_changed::<bool>("LockedHint").await;
+    while let Some(hint) = hints.next().await {
+        let locked = hint.get().await?;
+        let _ = output.send(Message::SessionLocked(locked)).await;
+    }
+    Ok(())
+}
+
+struct Lock;
+
+/// Emits [`Message::SessionLocked`] when logind's hint that the session is
+/// locked changes. Needs the `dbus` feature.
+pub fn lock_signals() -> Subscription<Message> {
+    subscription::channel(std::any::TypeId::of::<Lock>(), 1, |mut output| async move {
+        #[cfg(feature = "dbus")]
+        if let Err(error) = watch_lock(&mut output).await {
+            warn!("session locks go unnoticed: {error}");
+        }
+        #[cfg(not(feature = "dbus"))]
+        let _ = &mut output;
+        std::future::pending().await
+    })
+}
+
+async fn forward_shutdown(
+    output: &mut iced::futures::channel::mpsc::Sender<Message>,
+) -> io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    loop {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = hangup.recv() => {}
+            _ = interrupt.recv() => {}
+        }
+        let _ = output.send(Message::Shutdown).await;
+    }
+}
+
+struct Shutdown;
+
+/// Emits [`Message::Shutdown`] when the session manager asks the process to
+/// quit.
+pub fn shutdown_signals() -> Subscription<Message> {
+    subscription::channel(
+        std::any::TypeId::of::<Shutdown>(),
+        1,
+        |mut output| async move {
+            if let Err(error) = forward_shutdown(&mut output).await {
+                warn!("the composition is not saved on shutdown: {error}");
+            }
+            std::future::pending().await
+        },
+    )
+}
@@ -0,0 +1,250 @@
+//! Finding characters by shape rather than reading: by stroke sequence, with
+//! 1 to 5 standing for 一丨丿丶乛, or by four-corner code. The codes come
+//! from data files installed separately, see [`data_dirs`].
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+/// Four-corner codes, as distributed with the Unihan database.
+const FOUR_CORNER_FILE: &str = "Unihan_DictionaryLikeData.txt";
+/// Stroke sequences, as distributed with cjkvi-data.
+const STROKES_FILE: &str = "ucs-strokes.txt";
+/// Candidates listed for one query at most.
+const MAX_RESULTS: usize = 500;
+/// How each stroke key is shown in the preedit.
+const STROKES: [char; 5] = ['一', '丨', '丿', '丶', '乛'];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    #[default]
+    Strokes,
+    FourCorner,
+}
+
+/// The code being typed.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub kind: Kind,
+    pub keys: String,
+}
+
+impl Query {
+    /// Adds `key` if it is part of a code of this kind.
+    pub fn push(&mut self, key: char) -> bool {
+        let valid = match self.kind {
+            Kind::Strokes => ('1'..='5').contains(&key),
+            Kind::FourCorner => key.is_ascii_digit() && self.keys.len() < 5,
+        };
+        if valid {
+            self.keys.push(key);
+        }
+        valid
+    }
+
+    /// Switches between stroke sequences and four-corner codes.
+    pub fn switch(&mut self) {
+        self.kind = match self.kind {
+            Kind::Strokes => Kind::FourCorner,
+            Kind::FourCorner => Kind::Strokes,
+        };
+        self.keys.clear();
+    }
+
+    /// What is typed so far, as shown in the preedit.
+    pub fn preedit(&self) -> String {
+        match self.kind {
+            Kind::Strokes => {
+                let strokes = self.keys.bytes().map(|key| STROKES[(key - b'1') as usize]);
+                format!("筆畫 {}", strokes.collect::<String>())
+            }
+            Kind::FourCorner => format!("四角 {}", self.keys),
+        }
+    }
+}
+
+/// Characters by code, each list sorted by code so a prefix is a range.
+#[derive(Default)]
+pub struct Index {
+    strokes: Vec<(String, char)>,
+    four_corner: Vec<(String, char)>,
+}
+
+impl Index {
+    /// Reads the first copy of each data file found.
+    pub fn load() -> Self {
+        let mut index = Index::default();
+        let find = |name: &str| {
+            data_dirs()
+                .into_iter()
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+        };
+        if let Some(path) = find(STROKES_FILE) {
+            index.strokes = read(&path, parse_strokes);
+        }
+        if let Some(path) = find(FOUR_CORNER_FILE) {
+            index.four_corner = read(&path, parse_four_corner);
+        }
+        index
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty() && self.four_corner.is_empty()
+    }
+
+    /// Characters whose code starts with the typed keys, exact matches and
+    /// shorter codes first.
+    pub fn find(&self, query: &Query) -> Vec<String> {
+        let codes = match query.kind {
+            Kind::Strokes => &self.strokes,
+            Kind::FourCorner => &self.four_corner,
+        };
+        let start = codes.partition_point(|(code, _)| code.as_str() < query.keys.as_str());
+        let mut found: Vec<&(String, char)> = codes[start..]
+            .iter()
+            .take_while(|(code, _)| code.starts_with(&query.keys))
+            .collect();
+        found.sort_by_key(|(code, _)| code.len());
+        let mut characters: Vec<String> = Vec::new();
+        for (_, ch) in found {
+            let ch = ch.to_string();
+            if !characters.contains(&ch) {
+                characters.push(ch);
+            }
+            if characters.len() == MAX_RESULTS {
+                break;
+            }
+        }
+        characters
+    }
+}
+
+/// Where the data files are looked for, most specific first.
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    {
+        dirs.push(data_home.join("chewingwl"));
+    }
+    dirs.push(PathBuf::from("/usr/local/share/chewingwl"));
+    dirs.push(PathBuf::from("/usr/share/chewingwl"));
+    dirs
+}
+
+fn read(path: &Path, parse: fn(&str) -> Vec<(String, char)>) -> Vec<(String, char)> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let mut codes: Vec<(String, char)> = text
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .flat_map(parse)
+                .collect();
+            codes.sort_unstable();
+            codes
+        }
+        Err(error) => {
+            warn!("cannot read {}: {error}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+fn code_point(field: &str) -> Option<char> {
+    field
+        .strip_prefix("U+")
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(char::from_u32)
+}
+
+/// `U+4E00	一	1`, with alternative sequences separated by commas and
+/// regional variants tagged in parentheses.
+fn parse_strokes(line: &str) -> Vec<(String, char)> {
+    let mut fields = line.split('\t');
+    let (Some(ch), Some(sequences)) = (fields.next().and_then(code_point), fields.nth(1)) else {
+        return Vec::new();
+    };
+    sequences
+        .split(',')
+        .map(|sequence| sequence.split('(').next().unwrap_or_default().trim())
+        .filter(|sequence| {
+            !sequence.is_empty() && sequence.bytes().all(|b| (b'1'..=b'5').contains(&b))
+        })
+        .map(|sequence| (sequence.to_string(), ch))
+        .collect()
+}
+
+/// `U+4E00	kFourCornerCode	1000.0`, read as the five digits.
+fn parse_four_corner(line: &str) -> Vec<(String, char)> {
+    let mut fields = line.split('\t');
+    let (Some(ch), Some("kFourCornerCode"), Some(codes)) = (
+        fields.next().and_then(code_point),
+        fields.next(),
+        fields.next(),
+    ) else {
+        return Vec::new();
+    };
+    codes
+        .split(' ')
+        .map(|code| (code.replace('.', ""), ch))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_file_formats_are_read() {
+        assert_eq!(parse_strokes("U+4E00\t一\t1"), [("1".to_string(), '一')]);
+        assert_eq!(
+            parse_strokes("U+5203\t刃\t534,5354(J)"),
+            [("534".to_string(), '刃'), ("5354".to_string(), '刃')]
+        );
+        assert!(parse_strokes("U+4E00\t1").is_empty());
+        assert_eq!(
+            parse_four_corner("U+4E00\tkFourCornerCode\t1000.0"),
+            [("10000".to_string(), '一')]
+        );
+        assert_eq!(
+            parse_four_corner("U+4E2D\tkFourCornerCode\t5000.6 5000.0"),
+            [("50006".to_string(), '中'), ("50000".to_string(), '中')]
+        );
+        assert!(parse_four_corner("U+4E00\tkPhonetic\t1000").is_empty());
+    }
+
+    #[test]
+    fn exact_and_shorter_codes_come_first() {
+        let mut strokes: Vec<(String, char)> = [
+            "U+7530\t田\t25121",
+            "U+4E2D\t中\t2512,25122(K)",
+            "U+53E3\t口\t251",
+            "U+7533\t申\t25112",
+            "U+65E5\t日\t2511",
+            "U+5341\t十\t12",
+            "U+5DDD\t川\t322",
+        ]
+        .into_iter()
+        .flat_map(parse_strokes)
+        .collect();
+        strokes.sort_unstable();
+        let index = Index {
+            strokes,
+            four_corner: Vec::new(),
+        };
+        let query = Query {
+            kind: Kind::Strokes,
+            keys: "251".to_string(),
+        };
+        assert_eq!(index.find(&query), ["口", "日", "中", "申", "田"]);
+        let query = Query {
+            kind: Kind::FourCorner,
+            keys: "251".to_string(),
+        };
+        assert!(index.find(&query).is_empty());
+    }
+}
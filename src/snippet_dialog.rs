@@ -0,0 +1,134 @@
+//! A small dialog naming the text taken from the composition, opened with
+//! `hotkeys.add_snippet`, to save it as a snippet.
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+        },
+    },
+    widget::{button, column, container, row, text, text_input},
+    window, Alignment, Command, Element, Length,
+};
+
+use crate::{config::Surface, surface, CustomTheme, Message};
+
+const WIDTH: u32 = 360;
+const HEIGHT: u32 = 180;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Name(String),
+    Save,
+    Cancel,
+}
+
+pub struct SnippetDialog {
+    id: window::Id,
+    input: text_input::Id,
+    /// The text being saved, while the dialog is open.
+    text: Option<String>,
+    name: String,
+}
+
+impl Default for SnippetDialog {
+    fn default() -> Self {
+        SnippetDialog {
+            id: window::Id::unique(),
+            input: text_input::Id::unique(),
+            text: None,
+            name: String::new(),
+        }
+    }
+}
+
+impl SnippetDialog {
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    /// Whether the dialog is asking for a name.
+    pub fn is_open(&self) -> bool {
+        self.text.is_some()
+    }
+
+    /// Asks for a name for `text`. The dialog takes the keyboard, so the
+    /// name is typed here rather than into the application.
+    pub fn open(&mut self, text: String, surface: Surface) -> Command<Message> {
+        let create = if self.text.is_none() {
+            get_layer_surface(surface::configured(
+                surface,
+                SctkLayerSurfaceSettings {
+                    id: self.id,
+                    layer: Layer::Overlay,
+                    keyboard_interactivity: KeyboardInteractivity::Exclusive,
+                    anchor: Anchor::empty(),
+                    size: Some((Some(WIDTH), Some(HEIGHT))),
+                    namespace: "chewingwl-snippet".into(),
+                    ..Default::default()
+                },
+            ))
+        } else {
+            Command::none()
+        };
+        self.text = Some(text);
+        self.name.clear();
+        Command::batch(vec![create, text_input::focus(self.input.clone())])
+    }
+
+    /// Applies `event`, returning the name and text to save once confirmed.
+    pub fn update(&mut self, event: Event) -> (Command<Message>, Option<(String, String)>) {
+        match event {
+            Event::Name(name) => {
+                self.name = name;
+                (Command::none(), None)
+            }
+            Event::Save if self.name.trim().is_empty() => (Command::none(), None),
+            Event::Save => {
+                let saved = self
+                    .text
+                    .take()
+                    .map(|text| (self.name.trim().to_string(), text));
+                (destroy_layer_surface(self.id), saved)
+            }
+            Event::Cancel => {
+                self.text = None;
+                (destroy_layer_surface(self.id), None)
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        container(
+            column(vec![
+                text(self.text.as_deref().unwrap_or_default())
+                    .size(24)
+                    .into(),
+                text_input("Snippet name", &self.name)
+                    .id(self.input.clone())
+                    .on_input(|name| Message::Snippet(Event::Name(name)))
+                    .on_submit(Message::Snippet(Event::Save))
+                    .into(),
+                row(vec![
+                    button("Cancel")
+                        .on_press(Message::Snippet(Event::Cancel))
+                        .into(),
+                    button("Save")
+                        .on_press(Message::Snippet(Event::Save))
+                        .into(),
+                ])
+                .spacing(8.0)
+                .into(),
+            ])
+            .spacing(12.0)
+            .align_items(Alignment::End),
+        )
+        .padding(16.0)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+            Box::new(CustomTheme::default()),
+        ))
+        .into()
+    }
+}
@@ -0,0 +1,60 @@
+//! Named pieces of text kept in `snippets.toml` next to the config, typed
+//! with `chewingwlctl snippet <name>`.
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use log::warn;
+
+use crate::{config, user_data};
+
+#[derive(Default)]
+pub struct Snippets {
+    snippets: BTreeMap<String, String>,
+}
+
+impl Snippets {
+    pub fn load() -> Self {
+        let Some(path) = path() else {
+            return Snippets::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    warn!("failed to read {}: {error}", path.display());
+                }
+                return Snippets::default();
+            }
+        };
+        match toml::from_str(&text) {
+            Ok(snippets) => Snippets { snippets },
+            Err(error) => {
+                warn!("ignoring malformed {}: {error}", path.display());
+                Snippets::default()
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.snippets.get(name).map(String::as_str)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.snippets.keys().map(String::as_str)
+    }
+
+    /// Saves `text` under `name`, replacing any snippet of that name.
+    pub fn insert(&mut self, name: String, text: String) -> io::Result<()> {
+        self.snippets.insert(name, text);
+        let path = path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home"))?;
+        let contents = toml::to_string(&self.snippets)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        user_data::write_atomic(&path, contents.as_bytes())
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("snippets.toml"))
+}
@@ -0,0 +1,257 @@
+//! `chewingwl --soak [minutes] [seed]`: feeds the input method random but
+//! plausible typing, candidate picks, mode toggles and focus changes,
+//! acting as the client itself, and checks after every event that the state
+//! still makes sense. The seed is printed so a failure can be replayed.
+//!
+//! Nothing reaches a compositor: the commands the input method returns are
+//! dropped, and the user dictionary and state live in a scratch directory.
+use std::{
+    collections::VecDeque,
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use iced::{keyboard::key::Named, Application};
+use iced_core::{
+    event::wayland::{KeyEvent, Modifiers},
+    keyboard::Key,
+};
+use xkeysym::Keysym;
+
+use crate::{
+    config::{Config, Popup},
+    InputMethod, Message, State,
+};
+
+/// Syllables in the standard layout, tone key included.
+const SYLLABLES: [&str; 10] = [
+    "ji3", "su3", "cl3", "g4", "2k7", "5j/ ", "jp6", "bp6", "284", "1j4",
+];
+/// Events kept to print when an invariant breaks.
+const HISTORY: usize = 40;
+const DEFAULT_MINUTES: u64 = 60;
+/// Evdev keycode of the left Shift, the only key whose code matters here.
+const LEFT_SHIFT: u32 = 42;
+/// Between progress lines.
+const REPORT_EVERY: Duration = Duration::from_secs(60);
+
+pub fn run(args: &[String]) -> i32 {
+    let mut rest = args.iter();
+    let minutes = rest
+        .next()
+        .and_then(|minutes| minutes.parse().ok())
+        .unwrap_or(DEFAULT_MINUTES);
+    let seed = rest
+        .next()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |elapsed| elapsed.as_nanos() as u64)
+        });
+    let mut config = Config::load();
+    config.session.restore = false;
+    config.speech.enabled = false;
+    // Candidates are picked with digits
+    config.popup.selection_keys = Popup::default().selection_keys;
+    let scratch = env::temp_dir().join(format!("chewingwl-soak-{}", std::process::id()));
+    if let Err(error) = fs::create_dir_all(&scratch) {
+        eprintln!("cannot create {}: {error}", scratch.display());
+        return 1;
+    }
+    env::set_var("CHEWING_USER_PATH", &scratch);
+    env::set_var("XDG_STATE_HOME", &scratch);
+    println!("soak for {minutes} minutes with seed {seed}");
+    let code = Soak::new(config, seed).run(Duration::from_secs(minutes * 60));
+    let _ = fs::remove_dir_all(&scratch);
+    code
+}
+
+struct Soak {
+    input_method: InputMethod,
+    rng: Rng,
+    seed: u64,
+    steps: u64,
+    history: VecDeque<String>,
+}
+
+impl Soak {
+    fn new(config: Config, seed: u64) -> Self {
+        let (mut input_method, _) = InputMethod::new(config);
+        input_method.update(Message::Activate);
+        Soak {
+            input_method,
+            rng: Rng(seed.max(1)),
+            seed,
+            steps: 0,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn run(mut self, duration: Duration) -> i32 {
+        let started = Instant::now();
+        let mut reported = started;
+        while started.elapsed() < duration {
+            for message in self.next_events() {
+                if let Err(broken) = self.send(message) {
+                    self.report(&broken);
+                    return 1;
+                }
+            }
+            if reported.elapsed() >= REPORT_EVERY {
+                reported = Instant::now();
+                println!("{} events, all invariants held", self.steps);
+            }
+        }
+        println!("{} events, all invariants held", self.steps);
+        0
+    }
+
+    /// A gesture a user or client could make, as the events it produces.
+    fn next_events(&mut self) -> Vec<Message> {
+        let popup = matches!(self.input_method.state, State::Popup);
+        match self.rng.below(100) {
+            0..=39 => {
+                let syllable = SYLLABLES[self.rng.below(SYLLABLES.len())];
+                syllable.chars().flat_map(|ch| tap(character(ch))).collect()
+            }
+            40..=49 if popup => {
+                let digit = char::from(b'0' + self.rng.below(10) as u8);
+                tap(character(digit))
+            }
+            50..=54 => tap(named(Named::Enter)),
+            55..=59 => tap(named(Named::Backspace)),
+            60..=64 => tap(named(Named::ArrowDown)),
+            65..=69 => tap(named(Named::ArrowUp)),
+            70..=72 => tap(named(Named::ArrowLeft)),
+            73..=75 => tap(named(Named::ArrowRight)),
+            76..=78 => tap(named(Named::Escape)),
+            79..=80 => tap(named(Named::Shift)),
+            81..=84 => vec![Message::Deactivate, Message::Activate],
+            85..=86 => vec![Message::Deactivate],
+            87..=88 => vec![Message::Activate],
+            89..=90 => vec![Message::PreeditDue],
+            91..=92 => vec![Message::Prefetch],
+            _ => vec![Message::Done],
+        }
+    }
+
+    /// Hands `message` to the input method, then acknowledges what it sent
+    /// most of the time, as a client would.
+    fn send(&mut self, message: Message) -> Result<(), String> {
+        self.update(message)?;
+        if matches!(self.input_method.state, State::WaitingForDone) && self.rng.below(10) > 0 {
+            self.update(Message::Done)?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, message: Message) -> Result<(), String> {
+        self.steps += 1;
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(format!("{message:?}"));
+        let input_method = &mut self.input_method;
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            input_method.update(message);
+        }))
+        .map_err(|_| "the input method panicked".to_string())?;
+        self.check()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let im = &self.input_method;
+        let state = &im.state;
+        if !im.active && (!matches!(state, State::PassThrough) || im.popup) {
+            return Err(format!("inactive but {state:?}, popup {}", im.popup));
+        }
+        if !im.active && !im.chewing.preedit().is_empty() {
+            return Err("inactive with a composition left".into());
+        }
+        if im.popup && !matches!(state, State::Popup | State::WaitingForDone) {
+            return Err(format!("popup shown in {state:?}"));
+        }
+        if matches!(state, State::Popup) && im.grid.position() >= im.candidates.len() {
+            return Err(format!(
+                "highlight {} past {} candidates",
+                im.grid.position(),
+                im.candidates.len()
+            ));
+        }
+        if matches!(state, State::PreEdit) && im.current_preedit.is_empty() {
+            return Err("composing with an empty preedit".into());
+        }
+        Ok(())
+    }
+
+    fn report(&self, broken: &str) {
+        eprintln!("invariant broken after {} events: {broken}", self.steps);
+        eprintln!("seed {}, pass it after the minutes to replay", self.seed);
+        eprintln!("state: {}", self.input_method.state_summary());
+        eprintln!("last events:");
+        for event in &self.history {
+            eprintln!("  {event}");
+        }
+    }
+}
+
+/// Press and release of `key`, with Shift held for Shift itself.
+fn tap((key_event, key): (KeyEvent, Key)) -> Vec<Message> {
+    let modifiers = Modifiers {
+        shift: key == Key::Named(Named::Shift),
+        ..Modifiers::default()
+    };
+    vec![
+        Message::KeyPressed(key_event.clone(), key.clone(), modifiers),
+        Message::KeyReleased(key_event, key, Modifiers::default()),
+    ]
+}
+
+fn character(ch: char) -> (KeyEvent, Key) {
+    let utf8 = ch.to_string();
+    (
+        key_event(0, ch as u32, Some(utf8.clone())),
+        Key::Character(utf8.as_str().into()),
+    )
+}
+
+fn named(named: Named) -> (KeyEvent, Key) {
+    let (code, keysym, utf8) = match named {
+        Named::Enter => (28, 0xff0d, Some("\r")),
+        Named::Backspace => (14, 0xff08, Some("\u{8}")),
+        Named::Escape => (1, 0xff1b, Some("\u{1b}")),
+        Named::ArrowLeft => (105, 0xff51, None),
+        Named::ArrowUp => (103, 0xff52, None),
+        Named::ArrowRight => (106, 0xff53, None),
+        Named::ArrowDown => (108, 0xff54, None),
+        Named::Shift => (LEFT_SHIFT, 0xffe1, None),
+        _ => (0, 0, None),
+    };
+    (
+        key_event(code, keysym, utf8.map(str::to_string)),
+        Key::Named(named),
+    )
+}
+
+fn key_event(raw_code: u32, keysym: u32, utf8: Option<String>) -> KeyEvent {
+    KeyEvent {
+        time: 0,
+        raw_code,
+        keysym: Keysym::new(keysym),
+        utf8,
+    }
+}
+
+/// Xorshift, enough to make typing up and reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
@@ -0,0 +1,108 @@
+//! Says picked candidates aloud, for learners studying with Zhuyin. Each
+//! voice is an outside program: a recording player or a speech synthesizer,
+//! tried in that order.
+use std::{
+    path::{self, PathBuf},
+    process::{Child, Command as Process, Stdio},
+};
+
+use log::warn;
+
+use crate::config;
+
+/// Extensions tried for a recording, in order.
+const RECORDING_EXTENSIONS: [&str; 4] = ["ogg", "opus", "wav", "mp3"];
+
+/// A way of saying text aloud.
+trait Voice {
+    /// The program that says `text`, or `None` if this voice cannot.
+    fn command(&self, text: &str) -> Option<Process>;
+}
+
+/// Recordings named after what they say, e.g. `你好.ogg`.
+struct Recordings {
+    dir: PathBuf,
+    player: Vec<String>,
+}
+
+impl Voice for Recordings {
+    fn command(&self, text: &str) -> Option<Process> {
+        // A user phrase could otherwise name a file outside the directory
+        if text.starts_with('.') || text.contains(path::is_separator) {
+            return None;
+        }
+        let (program, args) = self.player.split_first()?;
+        let path = RECORDING_EXTENSIONS
+            .iter()
+            .map(|extension| self.dir.join(format!("{text}.{extension}")))
+            .find(|path| path.is_file())?;
+        let mut command = Process::new(program);
+        command.args(args).arg(path);
+        Some(command)
+    }
+}
+
+/// A speech synthesizer taking the text as its last argument.
+struct Synthesizer(Vec<String>);
+
+impl Voice for Synthesizer {
+    fn command(&self, text: &str) -> Option<Process> {
+        let (program, args) = self.0.split_first()?;
+        let mut command = Process::new(program);
+        command.args(args).arg(text);
+        Some(command)
+    }
+}
+
+pub struct Speaker {
+    voices: Vec<Box<dyn Voice>>,
+    /// What is being said, cut off by the next pick.
+    playing: Option<Child>,
+}
+
+impl Speaker {
+    pub fn new(config: &config::Speech) -> Self {
+        let mut voices: Vec<Box<dyn Voice>> = Vec::new();
+        if let Some(dir) = &config.recordings {
+            voices.push(Box::new(Recordings {
+                dir: dir.clone(),
+                player: config.player.clone(),
+            }));
+        }
+        voices.push(Box::new(Synthesizer(config.synthesizer.clone())));
+        Speaker {
+            voices,
+            playing: None,
+        }
+    }
+
+    /// Says `text` with the first voice that can, without waiting for it.
+    pub fn say(&mut self, text: &str) {
+        self.stop();
+        let Some(mut command) = self.voices.iter().find_map(|voice| voice.command(text)) else {
+            return;
+        };
+        let spawned = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(child) => self.playing = Some(child),
+            Err(error) => warn!("cannot say {text:?}: {error}"),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut playing) = self.playing.take() {
+            let _ = playing.kill();
+            let _ = playing.wait();
+        }
+    }
+}
+
+impl Drop for Speaker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
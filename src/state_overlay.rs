@@ -0,0 +1,134 @@
+//! A corner overlay showing the input method state and its last few
+//! transitions, for catching the Chinese/English state desyncing after focus
+//! changes. Enabled with `--debug-state-overlay` or `debug.state_overlay`.
+use std::{collections::VecDeque, time::Instant};
+
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{get_layer_surface, Anchor, KeyboardInteractivity, Layer},
+    },
+    widget::{column, container, text},
+    window, Command, Element, Length,
+};
+
+use crate::{config::Surface, surface, CustomTheme, Message};
+
+const WIDTH: u32 = 360;
+const HEIGHT: u32 = 150;
+/// Transitions shown on screen.
+const SHOWN: usize = 6;
+/// Transitions kept for `chewingwlctl transitions` and debug bundles.
+const HISTORY: usize = 200;
+
+struct Transition {
+    at: Instant,
+    from: String,
+    to: String,
+    cause: String,
+}
+
+impl Transition {
+    fn line(&self, started: Instant) -> String {
+        format!(
+            "+{:.3}s {} -> {} ({})",
+            self.at.duration_since(started).as_secs_f64(),
+            self.from,
+            self.to,
+            self.cause,
+        )
+    }
+}
+
+pub struct StateOverlay {
+    id: window::Id,
+    started: Instant,
+    current: String,
+    transitions: VecDeque<Transition>,
+}
+
+impl Default for StateOverlay {
+    fn default() -> Self {
+        StateOverlay {
+            id: window::Id::unique(),
+            started: Instant::now(),
+            current: String::new(),
+            transitions: VecDeque::with_capacity(HISTORY),
+        }
+    }
+}
+
+impl StateOverlay {
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (WIDTH, HEIGHT)
+    }
+
+    pub fn open(&self, surface: Surface) -> Command<Message> {
+        get_layer_surface(surface::configured(
+            surface,
+            SctkLayerSurfaceSettings {
+                id: self.id,
+                layer: Layer::Overlay,
+                keyboard_interactivity: KeyboardInteractivity::None,
+                anchor: Anchor::TOP | Anchor::RIGHT,
+                size: Some((Some(WIDTH), Some(HEIGHT))),
+                namespace: "chewingwl-debug".into(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Records the state summary after handling `cause`, keeping it as a
+    /// transition if it changed.
+    pub fn record(&mut self, state: String, cause: &str) {
+        if state == self.current {
+            return;
+        }
+        if self.current.is_empty() {
+            self.current = state;
+            return;
+        }
+        if self.transitions.len() == HISTORY {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(Transition {
+            at: Instant::now(),
+            from: std::mem::replace(&mut self.current, state.clone()),
+            to: state,
+            cause: cause.to_string(),
+        });
+    }
+
+    /// The current state followed by every kept transition, oldest first.
+    pub fn dump(&self) -> String {
+        let mut dump = format!("{}\n", self.current);
+        for transition in &self.transitions {
+            dump.push_str(&transition.line(self.started));
+            dump.push('\n');
+        }
+        dump
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let mut lines = vec![text(&self.current).size(14).into()];
+        lines.extend(
+            self.transitions
+                .iter()
+                .rev()
+                .take(SHOWN)
+                .map(|transition| text(transition.line(self.started)).size(11).into()),
+        );
+        container(column(lines).spacing(2.0))
+            .padding(8.0)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+                Box::new(CustomTheme::default()),
+            ))
+            .into()
+    }
+}
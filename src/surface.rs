@@ -0,0 +1,32 @@
+//! The layer and keyboard interactivity of the layer surfaces, as
+//! `surfaces` in the config overrides them.
+use iced::wayland::{
+    actions::layer_surface::SctkLayerSurfaceSettings,
+    layer_surface::{KeyboardInteractivity, Layer},
+};
+
+use crate::config::{Interactivity, Surface, SurfaceLayer};
+
+/// `settings` with the layer and keyboard interactivity set in `surface`.
+pub fn configured(
+    surface: Surface,
+    settings: SctkLayerSurfaceSettings,
+) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        layer: surface.layer.map_or(settings.layer, |layer| match layer {
+            SurfaceLayer::Background => Layer::Background,
+            SurfaceLayer::Bottom => Layer::Bottom,
+            SurfaceLayer::Top => Layer::Top,
+            SurfaceLayer::Overlay => Layer::Overlay,
+        }),
+        keyboard_interactivity: surface.keyboard.map_or(
+            settings.keyboard_interactivity,
+            |keyboard| match keyboard {
+                Interactivity::None => KeyboardInteractivity::None,
+                Interactivity::Exclusive => KeyboardInteractivity::Exclusive,
+                Interactivity::OnDemand => KeyboardInteractivity::OnDemand,
+            },
+        ),
+        ..settings
+    }
+}
@@ -0,0 +1,319 @@
+//! Opt-in WebDAV sync of the user dictionary.
+//!
+//! The newer copy of the dictionary wins, and phrases only the older copy
+//! knows about are added to it, so learning from either machine survives.
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chewing::dictionary::{Dictionary, UserDictionaryLoader};
+use log::info;
+
+use crate::{config, user_data};
+
+/// How long to wait for the server to answer or send more, so a dead one
+/// does not hold up startup.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn authorization(config: &config::Sync) -> Option<String> {
+    let user = config.username.as_ref()?;
+    let password = config
+        .password
+        .clone()
+        .or_else(|| std::env::var("CHEWINGWL_SYNC_PASSWORD").ok())
+        .unwrap_or_default();
+    Some(format!("Basic {}", STANDARD.encode(format!("{user}:{password}"))))
+}
+
+fn request(method: &str, config: &config::Sync) -> ureq::Request {
+    let request = ureq::AgentBuilder::new()
+        .timeout_connect(TIMEOUT)
+        .timeout_read(TIMEOUT)
+        .timeout_write(TIMEOUT)
+        .build()
+        .request(method, &config.url);
+    match authorization(config) {
+        Some(auth) => request.set("Authorization", &auth),
+        None => request,
+    }
+}
+
+/// Downloads the remote dictionary, returning its contents and modification
+/// time, or `None` if nothing was uploaded yet.
+fn pull(config: &config::Sync) -> io::Result<Option<(Vec<u8>, SystemTime)>> {
+    let response = match request("GET", config).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(error) => return Err(io::Error::other(error)),
+    };
+    let modified = response
+        .header("Last-Modified")
+        .and_then(|date| httpdate::parse_http_date(date).ok())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut contents = Vec::new();
+    io::copy(&mut response.into_reader(), &mut contents)?;
+    Ok(Some((contents, modified)))
+}
+
+fn push(config: &config::Sync, path: &Path) -> io::Result<()> {
+    request("PUT", config)
+        .send_bytes(&fs::read(path)?)
+        .map_err(io::Error::other)?;
+    Ok(())
+}
+
+fn open(path: &Path) -> io::Result<Box<dyn Dictionary>> {
+    UserDictionaryLoader::new()
+        .userphrase_path(path)
+        .load()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Adds every phrase of `from` that `into` does not have yet.
+fn merge(into: &Path, from: &Path) -> io::Result<usize> {
+    let mut base = open(into)?;
+    let known: HashSet<(Vec<_>, String)> = base
+        .entries()
+        .map(|(syllables, phrase)| (syllables, phrase.as_str().to_string()))
+        .collect();
+    let mut added = 0;
+    for (syllables, phrase) in open(from)?.entries() {
+        if !known.contains(&(syllables.clone(), phrase.as_str().to_string())) {
+            base.add_phrase(&syllables, phrase)
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            added += 1;
+        }
+    }
+    base.flush().map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(added)
+}
+
+/// The remote copy staged next to the user dictionary, removed however
+/// the sync ends.
+struct Staged(PathBuf);
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Reconciles the local user dictionary with the remote copy and uploads
+/// the result. Must run while no editor holds the dictionary open.
+pub fn run(config: &config::Sync, keep: usize) -> io::Result<()> {
+    sync(config, &user_data::user_dictionary_path()?, keep)
+}
+
+fn sync(config: &config::Sync, path: &Path, keep: usize) -> io::Result<()> {
+    let Some((remote, remote_modified)) = pull(config)? else {
+        info!("no remote dictionary yet, uploading {}", path.display());
+        return push(config, path);
+    };
+    let local_modified = fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let staged = Staged(user_data::with_tag(path, "remote"));
+    user_data::write_atomic(&staged.0, &remote)?;
+    user_data::rotate_backups(path, keep)?;
+    let added = if remote_modified > local_modified {
+        let added = merge(&staged.0, path)?;
+        user_data::copy_atomic(&staged.0, path)?;
+        added
+    } else {
+        merge(path, &staged.0)?
+    };
+    drop(staged);
+    info!("merged {added} phrases from the older copy");
+    push(config, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        str::FromStr,
+        thread::{self, JoinHandle},
+    };
+
+    use chewing::{dictionary::Phrase, zhuyin::Syllable};
+
+    use super::*;
+
+    fn create(path: &Path, phrases: &[(&str, &str, u32)]) {
+        let mut dictionary = open(path).unwrap();
+        for (reading, phrase, freq) in phrases {
+            let syllables: Vec<Syllable> = reading
+                .split(' ')
+                .map(|syllable| Syllable::from_str(syllable).unwrap())
+                .collect();
+            dictionary
+                .add_phrase(&syllables, Phrase::new(*phrase, *freq))
+                .unwrap();
+        }
+        dictionary.flush().unwrap();
+    }
+
+    fn phrases(path: &Path) -> Vec<String> {
+        let mut phrases: Vec<String> = open(path)
+            .unwrap()
+            .entries()
+            .map(|(_, phrase)| phrase.as_str().to_string())
+            .collect();
+        phrases.sort();
+        phrases
+    }
+
+    fn frequency(path: &Path, wanted: &str) -> Option<u32> {
+        open(path)
+            .unwrap()
+            .entries()
+            .find(|(_, phrase)| phrase.as_str() == wanted)
+            .map(|(_, phrase)| phrase.freq())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chewingwl-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    /// Answers one request with each of `responses` in turn, returning the
+    /// server's URL and the method and body of every request it took.
+    fn serve(responses: Vec<Vec<u8>>) -> (config::Sync, JoinHandle<Vec<(String, Vec<u8>)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = config::Sync {
+            url: format!("http://{}/chewing.dat", listener.local_addr().unwrap()),
+            ..config::Sync::default()
+        };
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let method = line.split(' ').next().unwrap().to_string();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                stream.write_all(&response).unwrap();
+                requests.push((method, body));
+            }
+            requests
+        });
+        (config, server)
+    }
+
+    fn dictionary_response(path: &Path, modified: SystemTime) -> Vec<u8> {
+        let modified = format!("Last-Modified: {}\r\n", httpdate::fmt_http_date(modified));
+        response("200 OK", &modified, &fs::read(path).unwrap())
+    }
+
+    #[test]
+    fn phrases_only_the_older_copy_has_are_added_once() {
+        let dir = temp_dir("sync-merge");
+        let (newer, older) = (dir.join("newer.dat"), dir.join("older.dat"));
+        create(&newer, &[("ㄘㄜˋ ㄕˋ", "測試", 1), ("ㄕˋ", "是", 1)]);
+        create(&older, &[("ㄘㄜˋ ㄕˋ", "測試", 1), ("ㄗˋ", "字", 1)]);
+        assert_eq!(merge(&newer, &older).unwrap(), 1);
+        assert_eq!(merge(&newer, &older).unwrap(), 0);
+        assert_eq!(phrases(&newer), ["字", "是", "測試"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_local_copy_is_uploaded_when_the_server_has_none() {
+        let dir = temp_dir("sync-first");
+        let local = dir.join("chewing.dat");
+        create(&local, &[("ㄕˋ", "是", 1)]);
+        let (config, server) = serve(vec![
+            response("404 Not Found", "", b""),
+            response("201 Created", "", b""),
+        ]);
+        sync(&config, &local, 0).unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].0, "GET");
+        assert_eq!(requests[1], ("PUT".to_string(), fs::read(&local).unwrap()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_newer_remote_copy_wins_and_keeps_local_phrases() {
+        let dir = temp_dir("sync-remote");
+        let (local, remote) = (dir.join("chewing.dat"), dir.join("remote.dat"));
+        create(&local, &[("ㄘㄜˋ ㄕˋ", "測試", 1), ("ㄕˋ", "是", 1)]);
+        create(&remote, &[("ㄘㄜˋ ㄕˋ", "測試", 9), ("ㄗˋ", "字", 1)]);
+        let tomorrow = SystemTime::now() + Duration::from_secs(86_400);
+        let (config, server) = serve(vec![
+            dictionary_response(&remote, tomorrow),
+            response("201 Created", "", b""),
+        ]);
+        sync(&config, &local, 1).unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(phrases(&local), ["字", "是", "測試"]);
+        assert_eq!(frequency(&local, "測試"), Some(9));
+        assert_eq!(requests[1], ("PUT".to_string(), fs::read(&local).unwrap()));
+        assert!(!dir.join("chewing.remote.dat").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn an_older_remote_copy_only_adds_its_phrases() {
+        let dir = temp_dir("sync-local");
+        let (local, remote) = (dir.join("chewing.dat"), dir.join("remote.dat"));
+        create(&local, &[("ㄘㄜˋ ㄕˋ", "測試", 1), ("ㄕˋ", "是", 1)]);
+        create(&remote, &[("ㄘㄜˋ ㄕˋ", "測試", 9), ("ㄗˋ", "字", 1)]);
+        let (config, server) = serve(vec![
+            dictionary_response(&remote, SystemTime::UNIX_EPOCH),
+            response("201 Created", "", b""),
+        ]);
+        sync(&config, &local, 1).unwrap();
+        server.join().unwrap();
+        assert_eq!(phrases(&local), ["字", "是", "測試"]);
+        assert_eq!(frequency(&local, "測試"), Some(1));
+        assert!(!dir.join("chewing.remote.dat").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn an_unreadable_remote_copy_is_not_left_behind() {
+        let dir = temp_dir("sync-broken");
+        let local = dir.join("chewing.dat");
+        create(&local, &[("ㄕˋ", "是", 1)]);
+        let before = fs::read(&local).unwrap();
+        let (config, server) = serve(vec![response("200 OK", "", b"not a dictionary")]);
+        assert!(sync(&config, &local, 0).is_err());
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(fs::read(&local).unwrap(), before);
+        assert!(!dir.join("chewing.remote.dat").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,111 @@
+//! Conversions between the offsets used for the same text: the editor counts
+//! characters, the text input protocol counts UTF-8 bytes, and layout counts
+//! display columns, where most Chinese characters take two.
+use unicode_width::UnicodeWidthChar;
+
+/// The byte offset of character `index` in `text`, or the length of `text`
+/// for an index at or past its end.
+pub fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+/// The display columns taken by the first `index` characters of `text`.
+pub fn columns(text: &str, index: usize) -> usize {
+    text.chars()
+        .take(index)
+        .map(|c| c.width().unwrap_or(0))
+        .sum()
+}
+
+/// Inserts `separator` before each phrase starting at one of the character
+/// indexes in `starts`, returning the text and the byte offset of character
+/// `cursor` in it.
+pub fn with_separators(
+    text: &str,
+    starts: &[usize],
+    cursor: usize,
+    separator: &str,
+) -> (String, usize) {
+    let mut separated = String::with_capacity(text.len());
+    let mut offset = None;
+    for (i, c) in text.chars().enumerate() {
+        if i > 0 && starts.contains(&i) {
+            separated.push_str(separator);
+        }
+        if i == cursor {
+            offset = Some(separated.len());
+        }
+        separated.push(c);
+    }
+    let offset = offset.unwrap_or(separated.len());
+    (separated, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_offset_of_ascii_is_the_index() {
+        assert_eq!(byte_offset("abc", 0), 0);
+        assert_eq!(byte_offset("abc", 2), 2);
+    }
+
+    #[test]
+    fn byte_offset_counts_multibyte_characters() {
+        // Chinese characters and bopomofo take three bytes each
+        assert_eq!(byte_offset("今天ㄊㄧㄢ", 1), 3);
+        assert_eq!(byte_offset("今天ㄊㄧㄢ", 3), 9);
+        // The third tone mark takes two
+        assert_eq!(byte_offset("ˇ好", 1), 2);
+    }
+
+    #[test]
+    fn byte_offset_past_the_end_is_the_length() {
+        assert_eq!(byte_offset("", 0), 0);
+        assert_eq!(byte_offset("今天", 2), 6);
+        assert_eq!(byte_offset("今天", 5), 6);
+    }
+
+    #[test]
+    fn columns_count_wide_characters_twice() {
+        assert_eq!(columns("ab今天", 4), 6);
+        assert_eq!(columns("ab今天", 3), 4);
+        assert_eq!(columns("ㄊㄧㄢ", usize::MAX), 6);
+    }
+
+    #[test]
+    fn columns_skip_combining_marks() {
+        assert_eq!(columns("e\u{301}", 2), 1);
+    }
+
+    #[test]
+    fn separators_go_between_phrases_only() {
+        let (text, _) = with_separators("今天天氣好", &[0, 2, 4], 0, "·");
+        assert_eq!(text, "今天·天氣·好");
+    }
+
+    #[test]
+    fn separated_cursor_lands_after_the_separator() {
+        let (text, offset) = with_separators("今天天氣", &[0, 2], 2, "·");
+        assert_eq!(&text[offset..], "天氣");
+        let (_, offset) = with_separators("今天天氣", &[0, 2], 1, "·");
+        assert_eq!(offset, 3);
+    }
+
+    #[test]
+    fn separated_cursor_at_the_end() {
+        let (text, offset) = with_separators("今天天氣", &[0, 2], 4, "·");
+        assert_eq!(offset, text.len());
+    }
+
+    #[test]
+    fn no_separators_without_phrases() {
+        assert_eq!(
+            with_separators("今天", &[], 1, "·"),
+            ("今天".to_string(), 3)
+        );
+    }
+}
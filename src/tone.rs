@@ -0,0 +1,61 @@
+//! Presentation of the zhuyin tone marks wherever zhuyin is shown: the
+//! preedit, the popup with its annotations and tooltips, and the history
+//! panel. The editor uses spacing modifier letters, which some clients draw
+//! detached from the syllable.
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMarks {
+    /// As the editor produces them, e.g. `ㄏㄠˇ`.
+    #[default]
+    Spacing,
+    /// Combining marks that attach to the preceding symbol.
+    Combining,
+    /// Tone numbers, e.g. `ㄏㄠ3`, the first tone staying unmarked.
+    Numbers,
+}
+
+const MARKS: [(char, char, char); 4] = [
+    ('ˊ', '\u{301}', '2'),
+    ('ˇ', '\u{30c}', '3'),
+    ('ˋ', '\u{300}', '4'),
+    ('˙', '\u{307}', '5'),
+];
+
+/// Replaces the tone marks in `text` one for one, so character indexes into
+/// it stay valid.
+pub fn present(text: &str, style: ToneMarks) -> String {
+    if style == ToneMarks::Spacing {
+        return text.to_string();
+    }
+    text.chars()
+        .map(|c| match MARKS.iter().find(|(spacing, ..)| *spacing == c) {
+            Some((_, combining, _)) if style == ToneMarks::Combining => *combining,
+            Some((.., number)) => *number,
+            None => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_are_replaced_one_for_one() {
+        let text = "ㄇㄚ ㄇㄚˊ ㄇㄚˇ ㄇㄚˋ ㄇㄚ˙";
+        for (style, marks) in [
+            (ToneMarks::Combining, ['\u{301}', '\u{30c}', '\u{300}', '\u{307}']),
+            (ToneMarks::Numbers, ['2', '3', '4', '5']),
+        ] {
+            let presented = present(text, style);
+            assert_eq!(presented.chars().count(), text.chars().count());
+            for (mark, (spacing, ..)) in marks.into_iter().zip(MARKS) {
+                let at = text.chars().position(|c| c == spacing).unwrap();
+                assert_eq!(presented.chars().nth(at), Some(mark));
+            }
+            assert!(!presented.chars().any(|c| MARKS.iter().any(|m| m.0 == c)));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Stroke counts and radicals from the Unihan database, for telling
+//! look-alike characters apart in the popup. A subset of common characters
+//! is embedded; the whole of `Unihan_IRGSources.txt` is read instead when
+//! installed in one of the [`shape::data_dirs`].
+use std::{collections::HashMap, fs, sync::OnceLock};
+
+use crate::shape;
+
+const TABLE: &str = include_str!("../data/unihan.txt");
+/// Where `kRSUnicode` and `kTotalStrokes` are distributed.
+const SOURCES_FILE: &str = "Unihan_IRGSources.txt";
+
+/// First of the Kangxi Radicals block, radical 1.
+const KANGXI_RADICALS: u32 = 0x2F00;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Entry {
+    /// Kangxi radical number, from 1 to 214.
+    radical: u32,
+    /// Strokes besides the radical.
+    residual: u32,
+    strokes: u32,
+}
+
+fn table() -> &'static HashMap<char, Entry> {
+    static ENTRIES: OnceLock<HashMap<char, Entry>> = OnceLock::new();
+    ENTRIES.get_or_init(|| {
+        let mut entries = HashMap::new();
+        parse(TABLE, &mut entries);
+        if let Some(text) = shape::data_dirs()
+            .into_iter()
+            .find_map(|dir| fs::read_to_string(dir.join(SOURCES_FILE)).ok())
+        {
+            parse(&text, &mut entries);
+        }
+        entries
+    })
+}
+
+/// Adds the stroke counts and radicals of `text`, lines of code point,
+/// field and value as in the Unihan files.
+fn parse(text: &str, entries: &mut HashMap<char, Entry>) {
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let mut fields = line.split('\t');
+        let (Some(code), Some(field @ ("kRSUnicode" | "kTotalStrokes")), Some(value)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some(ch) = code
+            .strip_prefix("U+")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+        else {
+            continue;
+        };
+        // Several values list the traditional reading first
+        let value = value.split(' ').next().unwrap_or_default();
+        let entry = entries.entry(ch).or_default();
+        if field == "kRSUnicode" {
+            // A trailing ' marks the simplified form of the radical
+            let value = value.replace('\'', "");
+            if let Some((radical, residual)) = value.split_once('.') {
+                entry.radical = radical.parse().unwrap_or_default();
+                entry.residual = residual.parse().unwrap_or_default();
+            }
+        } else {
+            entry.strokes = value.parse().unwrap_or_default();
+        }
+    }
+}
+
+/// One line per character of `text`: its code point, and its stroke count
+/// and radical where known.
+pub fn describe(text: &str) -> String {
+    text.chars()
+        .map(|ch| {
+            let code = format!("{ch} U+{:04X}", ch as u32);
+            match table().get(&ch) {
+                Some(entry) => {
+                    let radical = entry
+                        .radical
+                        .checked_sub(1)
+                        .and_then(|offset| char::from_u32(KANGXI_RADICALS + offset))
+                        .unwrap_or('?');
+                    format!(
+                        "{code}  {} strokes, radical {radical} ({}) + {}",
+                        entry.strokes, entry.radical, entry.residual
+                    )
+                }
+                None => code,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_strokes_and_radicals_are_kept() {
+        let mut entries = HashMap::new();
+        parse(
+            "U+4E00\tkIRG_GSource\tG0-523B\nU+4E2D\tkRSUnicode\t2.3\nU+4E2D\tkTotalStrokes\t4 5\n",
+            &mut entries,
+        );
+        assert_eq!(entries.len(), 1);
+        let entry = entries[&'中'];
+        assert_eq!((entry.radical, entry.residual, entry.strokes), (2, 3, 4));
+    }
+}
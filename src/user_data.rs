@@ -0,0 +1,666 @@
+//! Safekeeping of the learned user dictionary.
+//!
+//! The editor keeps learned frequencies in memory and persists them through
+//! the user dictionary itself, so every file this module writes goes through
+//! [`copy_atomic`] or [`write_atomic`]: a crash leaves either the old or the
+//! new file, never a torn one. Files saved while typing go through
+//! [`write_later`] or [`UserFile::save`] so a slow disk does not hold up the
+//! next key.
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chewing::{
+    dictionary::{Dictionary, DictionaryBuilder, Phrase, TrieBuilder, UserDictionaryLoader},
+    zhuyin::Syllable,
+};
+use log::{error, warn};
+
+use crate::config::FlushPolicy;
+
+const USER_DICTIONARY_FILES: [&str; 2] = ["chewing.sqlite3", "chewing.dat"];
+/// How long a running session keeps its changes before a save rotates the
+/// backups once more.
+const BACKUP_EVERY: Duration = Duration::from_secs(60 * 60);
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue {
+    saves: Vec::new(),
+    writing: false,
+});
+/// Signalled when a file is queued and when the writer thread has written
+/// the files it took.
+static CHANGED: Condvar = Condvar::new();
+/// Whether the writer thread runs, started by the first file queued.
+static WRITER: OnceLock<bool> = OnceLock::new();
+
+/// The backup `generation` of `path`, `chewing.dat.1` being the newest.
+fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &format!(".{generation}"))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// `path` with `tag` before its extension, e.g. `chewing.remote.dat`, so
+/// the dictionary loader still tells the format from the extension.
+pub fn with_tag(path: &Path, tag: &str) -> PathBuf {
+    match path.extension() {
+        Some(extension) => {
+            let mut name = path.with_extension("").into_os_string();
+            name.push(format!(".{tag}."));
+            name.push(extension);
+            PathBuf::from(name)
+        }
+        None => with_suffix(path, &format!(".{tag}")),
+    }
+}
+
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `contents` to a temporary sibling of `path`, flushes it to disk
+/// and renames it over `path`.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    sync_parent(path)
+}
+
+/// Builds a trie dictionary of `phrases` in a temporary sibling of `path`,
+/// then publishes it like [`write_atomic`].
+fn write_trie(path: &Path, phrases: &[(Vec<Syllable>, Phrase)]) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let mut builder = TrieBuilder::new();
+    for (syllables, phrase) in phrases {
+        builder
+            .insert(syllables, phrase.clone())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+    builder
+        .build(&tmp)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    File::open(&tmp)?.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_parent(path)
+}
+
+enum Contents {
+    Bytes(Vec<u8>),
+    /// The phrases of a trie user dictionary, as the editor knew them.
+    Phrases(Vec<(Vec<Syllable>, Phrase)>),
+    /// A SQLite user dictionary, which commits as the editor learns. The
+    /// writer flushes its journal into the file on a connection of its own.
+    Journal,
+}
+
+/// A file for the writer thread, named by `what` in the log.
+struct Save {
+    what: &'static str,
+    path: PathBuf,
+    contents: Contents,
+    /// How many backups of the file to keep, rotated before it is written;
+    /// zero rotates none.
+    backups: usize,
+}
+
+impl Save {
+    fn write(&self) {
+        let result = match &self.contents {
+            Contents::Bytes(bytes) => rotate_backups(&self.path, self.backups)
+                .and_then(|()| self.path.parent().map_or(Ok(()), fs::create_dir_all))
+                .and_then(|()| write_atomic(&self.path, bytes)),
+            Contents::Phrases(phrases) => rotate_backups(&self.path, self.backups)
+                .and_then(|()| write_trie(&self.path, phrases)),
+            // Backed up after the flush, the file alone missing what is
+            // still in the journal
+            Contents::Journal => open(&self.path)
+                .and_then(|mut dictionary| {
+                    dictionary
+                        .flush()
+                        .map_err(|e| io::Error::other(e.to_string()))
+                })
+                .and_then(|()| rotate_backups(&self.path, self.backups)),
+        };
+        if let Err(error) = result {
+            warn!(
+                "failed to save {} to {}: {error}",
+                self.what,
+                self.path.display()
+            );
+        }
+    }
+}
+
+struct Queue {
+    /// The newest contents of each file waiting to be written.
+    saves: Vec<Save>,
+    /// Whether the writer thread is writing files it took off the queue.
+    writing: bool,
+}
+
+fn queue() -> MutexGuard<'static, Queue> {
+    QUEUE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_queued() {
+    let mut queue = queue();
+    loop {
+        if queue.saves.is_empty() {
+            queue.writing = false;
+            CHANGED.notify_all();
+            queue = CHANGED.wait(queue).unwrap_or_else(PoisonError::into_inner);
+            continue;
+        }
+        let saves = std::mem::take(&mut queue.saves);
+        queue.writing = true;
+        drop(queue);
+        for save in &saves {
+            save.write();
+        }
+        queue = self::queue();
+    }
+}
+
+/// Queues `save` for the writer thread, or writes it right away should the
+/// thread not start.
+fn save_later(save: Save) {
+    let started = *WRITER.get_or_init(|| {
+        let started = thread::Builder::new()
+            .name("writer".into())
+            .spawn(write_queued);
+        if let Err(error) = &started {
+            warn!("saving files on the main thread: {error}");
+        }
+        started.is_ok()
+    });
+    if !started {
+        return save.write();
+    }
+    coalesce(&mut queue().saves, save);
+    CHANGED.notify_all();
+}
+
+/// Adds `save` to `saves`. Each file is written whole, so only its newest
+/// contents need to be, though with the backups any save of it asked for.
+fn coalesce(saves: &mut Vec<Save>, mut save: Save) {
+    match saves.iter_mut().find(|queued| queued.path == save.path) {
+        Some(queued) => {
+            save.backups = save.backups.max(queued.backups);
+            *queued = save;
+        }
+        None => saves.push(save),
+    }
+}
+
+/// Writes `contents` to `path` like [`write_atomic`], creating its
+/// directory, on a thread of its own.
+pub fn write_later(what: &'static str, path: PathBuf, contents: Vec<u8>) {
+    save_later(Save {
+        what,
+        path,
+        contents: Contents::Bytes(contents),
+        backups: 0,
+    });
+}
+
+/// Waits for the files queued by [`write_later`] and [`UserFile::save`],
+/// before exiting or reopening a file being written.
+pub fn finish_writes() {
+    let mut queue = queue();
+    while queue.writing || !queue.saves.is_empty() {
+        queue = CHANGED.wait(queue).unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Removes a file kept with [`write_later`], once a save of it still
+/// queued can no longer bring it back.
+pub fn remove_state_file(path: &Path, what: &str) {
+    finish_writes();
+    if let Err(error) = fs::remove_file(path) {
+        if error.kind() != io::ErrorKind::NotFound {
+            warn!("failed to remove {what} {}: {error}", path.display());
+        }
+    }
+}
+
+/// Copies `from` over `to` with the same guarantees as [`write_atomic`].
+pub fn copy_atomic(from: &Path, to: &Path) -> io::Result<()> {
+    let tmp = with_suffix(to, ".tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        io::copy(&mut File::open(from)?, &mut file)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, to)?;
+    sync_parent(to)
+}
+
+/// Shifts existing backups of `path` one generation back, dropping the
+/// oldest, and copies `path` to the newest slot. Keeps at most `keep`
+/// backups; zero disables rotation.
+pub fn rotate_backups(path: &Path, keep: usize) -> io::Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    let oldest = backup_path(path, keep);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for generation in (1..keep).rev() {
+        let from = backup_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, generation + 1))?;
+        }
+    }
+    copy_atomic(path, &backup_path(path, 1))
+}
+
+/// Existing backups of `path`, newest first, including any past a missing
+/// generation.
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let mut generations: Vec<usize> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            let generation = name.to_str()?.strip_prefix(&prefix)?;
+            generation.parse().ok().filter(|generation| *generation > 0)
+        })
+        .collect();
+    generations.sort_unstable();
+    generations
+        .into_iter()
+        .map(|generation| backup_path(path, generation))
+        .collect()
+}
+
+/// The working copy the editor opens in place of the trie file at `path`.
+///
+/// libchewing saves the trie by writing over the file it loaded, so it is
+/// given a copy, and `path` is only ever replaced whole by
+/// [`UserFile::save`]. SQLite dictionaries keep their own journal and are
+/// opened directly.
+fn working_path(path: &Path) -> Option<PathBuf> {
+    (path.extension()? == "dat").then(|| with_tag(path, "work"))
+}
+
+/// The user dictionary file of a running session, backed up again every
+/// [`BACKUP_EVERY`] while it is saved.
+pub struct UserFile {
+    path: PathBuf,
+    working: Option<PathBuf>,
+    keep: usize,
+    backed_up: Instant,
+}
+
+impl UserFile {
+    /// Saves what `dictionary` has learned. A trie is only read here, in
+    /// memory, and built and written from that snapshot on the writer
+    /// thread; SQLite commits as it learns and is flushed on the writer
+    /// thread too. The backups are rotated there as well, if the newest is
+    /// old enough, so a long session does not leave only the state it
+    /// started from.
+    pub fn save(&mut self, dictionary: &mut dyn Dictionary) {
+        let backups = if self.keep != 0 && self.backed_up.elapsed() >= BACKUP_EVERY {
+            self.backed_up = Instant::now();
+            self.keep
+        } else {
+            0
+        };
+        let contents = if self.working.is_some() {
+            Contents::Phrases(dictionary.entries().collect())
+        } else {
+            Contents::Journal
+        };
+        save_later(Save {
+            what: "user dictionary",
+            path: self.path.clone(),
+            contents,
+            backups,
+        });
+    }
+}
+
+/// The file the user dictionary is stored in, resolved the same way as
+/// libchewing: `CHEWING_USER_PATH`, then `$XDG_DATA_HOME/chewing`.
+pub fn user_dictionary_path() -> io::Result<PathBuf> {
+    let dir = env::var_os("CHEWING_USER_PATH")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("XDG_DATA_HOME").map(|dir| Path::new(&dir).join("chewing")))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share/chewing")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let path = USER_DICTIONARY_FILES
+        .iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(USER_DICTIONARY_FILES[1]));
+    Ok(path)
+}
+
+fn open(path: &Path) -> io::Result<Box<dyn Dictionary>> {
+    UserDictionaryLoader::new()
+        .userphrase_path(path)
+        .load()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Opens the user dictionary at `path`. One that fails to load is moved
+/// aside and replaced with the newest backup that does.
+fn open_or_recover(path: &Path) -> io::Result<Box<dyn Dictionary>> {
+    let load_error = match open(path) {
+        Ok(dictionary) => return Ok(dictionary),
+        Err(error) => error,
+    };
+    error!("user dictionary {} is unreadable: {load_error}", path.display());
+    // Each backup is tried under the extension the loader tells the format by
+    let candidate = with_tag(path, "recover");
+    for backup in backups(path) {
+        copy_atomic(&backup, &candidate)?;
+        if open(&candidate).is_ok() {
+            warn!("falling back to {}", backup.display());
+            fs::rename(path, with_suffix(path, ".corrupt"))?;
+            fs::rename(&candidate, path)?;
+            sync_parent(path)?;
+            return open(path);
+        }
+    }
+    let _ = fs::remove_file(&candidate);
+    Err(load_error)
+}
+
+/// Loads the user dictionary and backs it up before this session writes to
+/// it, recovering it from a backup if it does not load. The editor is
+/// handed a working copy of trie dictionaries, see [`UserFile::save`].
+pub fn load_user_dictionary(keep: usize) -> io::Result<(Box<dyn Dictionary>, UserFile)> {
+    let path = user_dictionary_path()?;
+    let dictionary = open_or_recover(&path)?;
+    if let Err(error) = rotate_backups(&path, keep) {
+        warn!("failed to back up {}: {error}", path.display());
+    }
+    let working = working_path(&path);
+    let dictionary = match &working {
+        Some(working) => {
+            drop(dictionary);
+            // A copy left behind by a crash may be torn, the published file
+            // is not
+            match fs::remove_file(working) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+            if path.exists() {
+                copy_atomic(&path, working)?;
+            }
+            open(working)?
+        }
+        None => dictionary,
+    };
+    let file = UserFile {
+        path,
+        working,
+        keep,
+        backed_up: Instant::now(),
+    };
+    Ok((dictionary, file))
+}
+
+/// Replaces the user dictionary with `backup`, first backing up the current
+/// file so the restore itself can be undone.
+pub fn restore(path: &Path, backup: &Path, keep: usize) -> io::Result<()> {
+    let staged = backup_path(path, 0);
+    copy_atomic(backup, &staged)?;
+    rotate_backups(path, keep.max(1))?;
+    fs::rename(&staged, path)?;
+    sync_parent(path)
+}
+
+/// Tracks unflushed commits against the configured [`FlushPolicy`].
+pub struct FlushSchedule {
+    policy: FlushPolicy,
+    pending: usize,
+    last_commit: Option<Instant>,
+}
+
+impl FlushSchedule {
+    pub fn new(policy: FlushPolicy) -> Self {
+        FlushSchedule {
+            policy,
+            pending: 0,
+            last_commit: None,
+        }
+    }
+
+    /// Whether there are commits that have not been flushed yet.
+    pub fn is_dirty(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Records a commit, returning whether it is time to flush.
+    pub fn committed(&mut self) -> bool {
+        self.pending += 1;
+        self.last_commit = Some(Instant::now());
+        self.policy.every_commits != 0 && self.pending >= self.policy.every_commits
+    }
+
+    /// How long after a commit to check [`Self::idle_due`], if the idle
+    /// trigger is enabled.
+    pub fn idle_delay(&self) -> Option<Duration> {
+        (self.policy.idle_seconds != 0).then(|| Duration::from_secs(self.policy.idle_seconds))
+    }
+
+    /// Whether the idle trigger has fired.
+    pub fn idle_due(&self) -> bool {
+        self.policy.idle_seconds != 0
+            && self.is_dirty()
+            && self.last_commit.is_some_and(|last| {
+                last.elapsed() >= Duration::from_secs(self.policy.idle_seconds)
+            })
+    }
+
+    /// Whether losing focus should flush.
+    pub fn deactivate_due(&self) -> bool {
+        self.policy.on_deactivate && self.is_dirty()
+    }
+
+    pub fn flushed(&mut self) {
+        self.pending = 0;
+        self.last_commit = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chewingwl-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    fn phrase(text: &str) -> (Vec<Syllable>, Phrase) {
+        let syllables = ["ㄘㄜˋ", "ㄕˋ"].map(|syllable| syllable.parse::<Syllable>().unwrap());
+        (syllables.to_vec(), Phrase::new(text, 1))
+    }
+
+    fn phrases(dictionary: &dyn Dictionary) -> Vec<String> {
+        dictionary
+            .entries()
+            .map(|(_, phrase)| phrase.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_old_file() {
+        let dir = temp_dir("failed-write");
+        let path = dir.join("state");
+        write_atomic(&path, b"old").unwrap();
+        // The temporary file cannot be created
+        fs::create_dir(with_suffix(&path, ".tmp")).unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(read(&path), "old");
+        let trie = dir.join("chewing.dat");
+        write_trie(&trie, &[phrase("測試")]).unwrap();
+        fs::create_dir(with_suffix(&trie, ".tmp")).unwrap();
+        assert!(write_trie(&trie, &[phrase("側室")]).is_err());
+        assert_eq!(phrases(&*open(&trie).unwrap()), ["測試"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn an_unreadable_dictionary_is_replaced_by_the_newest_readable_backup() {
+        let dir = temp_dir("recover");
+        let path = dir.join("chewing.dat");
+        fs::write(&path, "torn").unwrap();
+        fs::write(backup_path(&path, 1), "torn too").unwrap();
+        write_trie(&backup_path(&path, 2), &[phrase("測試")]).unwrap();
+        write_trie(&backup_path(&path, 3), &[phrase("側室")]).unwrap();
+        let dictionary = open_or_recover(&path).unwrap();
+        assert_eq!(phrases(&*dictionary), ["測試"]);
+        assert_eq!(read(&with_suffix(&path, ".corrupt")), "torn");
+        assert_eq!(read(&backup_path(&path, 1)), "torn too");
+        assert!(!with_tag(&path, "recover").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn nothing_is_moved_when_no_backup_is_readable() {
+        let dir = temp_dir("no-recovery");
+        let path = dir.join("chewing.dat");
+        fs::write(&path, "torn").unwrap();
+        fs::write(backup_path(&path, 1), "torn too").unwrap();
+        assert!(open_or_recover(&path).is_err());
+        assert_eq!(read(&path), "torn");
+        assert!(!with_suffix(&path, ".corrupt").exists());
+        assert!(!with_tag(&path, "recover").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn backups_rotate_newest_first_up_to_keep() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("chewing.dat");
+        for version in 1..=4 {
+            fs::write(&path, format!("v{version}")).unwrap();
+            rotate_backups(&path, 3).unwrap();
+        }
+        let backups = backups(&path);
+        assert_eq!(backups, (1..=3).map(|n| backup_path(&path, n)).collect::<Vec<_>>());
+        assert_eq!(backups.iter().map(|b| read(b)).collect::<Vec<_>>(), ["v4", "v3", "v2"]);
+        rotate_backups(&path, 0).unwrap();
+        assert_eq!(read(&backup_path(&path, 1)), "v4");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn backups_are_listed_past_a_missing_generation() {
+        let dir = temp_dir("list-backups");
+        let path = dir.join("chewing.dat");
+        for name in ["chewing.dat.10", "chewing.dat.1", "chewing.dat.3"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        for name in ["chewing.dat.0", "chewing.dat.tmp", "chewing.dat.2.tmp", "other.dat.2"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let expected: Vec<PathBuf> = [1, 3, 10].map(|n| backup_path(&path, n)).into();
+        assert_eq!(backups(&path), expected);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restoring_backs_up_the_current_file_first() {
+        let dir = temp_dir("restore");
+        let path = dir.join("chewing.dat");
+        fs::write(&path, "current").unwrap();
+        fs::write(backup_path(&path, 1), "older").unwrap();
+        fs::write(backup_path(&path, 2), "oldest").unwrap();
+        restore(&path, &backup_path(&path, 2), 3).unwrap();
+        assert_eq!(read(&path), "oldest");
+        let backups: Vec<String> = backups(&path).iter().map(|b| read(b)).collect();
+        assert_eq!(backups, ["current", "older", "oldest"]);
+        assert!(!backup_path(&path, 0).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn save(path: &str, contents: &str, backups: usize) -> Save {
+        Save {
+            what: "test",
+            path: PathBuf::from(path),
+            contents: Contents::Bytes(contents.into()),
+            backups,
+        }
+    }
+
+    #[test]
+    fn queued_saves_of_a_file_coalesce() {
+        let mut saves = Vec::new();
+        for generation in 0..100 {
+            coalesce(&mut saves, save("a", &generation.to_string(), 0));
+            coalesce(&mut saves, save("b", &generation.to_string(), 0));
+        }
+        let queued: Vec<(&Path, &[u8])> = saves
+            .iter()
+            .map(|save| match &save.contents {
+                Contents::Bytes(bytes) => (save.path.as_path(), bytes.as_slice()),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(queued, [(Path::new("a"), &b"99"[..]), (Path::new("b"), &b"99"[..])]);
+    }
+
+    #[test]
+    fn coalesced_saves_keep_the_most_backups() {
+        let mut saves = Vec::new();
+        coalesce(&mut saves, save("a", "rotating", 5));
+        coalesce(&mut saves, save("a", "newer", 0));
+        coalesce(&mut saves, save("a", "newest", 2));
+        assert_eq!(saves.len(), 1);
+        assert_eq!(saves[0].backups, 5);
+    }
+
+    #[test]
+    fn only_the_newest_contents_are_written() {
+        let dir = env::temp_dir().join(format!("chewingwl-writes-{}", std::process::id()));
+        for generation in 0..100 {
+            write_later("a", dir.join("a"), format!("{generation}").into_bytes());
+            write_later("b", dir.join("new/b"), format!("{generation}").into_bytes());
+        }
+        finish_writes();
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap_or_default();
+        assert_eq!(read("a"), "99");
+        assert_eq!(read("new/b"), "99");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,181 @@
+//! Variant forms of the same character, e.g. 臺 and 台, so the popup can
+//! list them next to each other or fold them under one entry, and text can
+//! follow the forms of the region configured.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use crate::config::{Region, VariantDisplay};
+
+const TABLE: &str = include_str!("../data/variants.txt");
+
+#[derive(Default)]
+struct Forms {
+    /// The Taiwanese standard form of each variant.
+    standard: HashMap<char, char>,
+    /// The Hong Kong form of each character of a group that has one.
+    hong_kong: HashMap<char, char>,
+}
+
+fn forms() -> &'static Forms {
+    static FORMS: OnceLock<Forms> = OnceLock::new();
+    FORMS.get_or_init(|| {
+        let mut forms = Forms::default();
+        for line in TABLE.lines().filter(|line| !line.starts_with('#')) {
+            let group: Vec<(char, bool)> = line
+                .split_whitespace()
+                .filter_map(|form| {
+                    let marked = form.starts_with('*');
+                    form.trim_start_matches('*')
+                        .chars()
+                        .next()
+                        .map(|ch| (ch, marked))
+                })
+                .collect();
+            let Some(&(standard, _)) = group.first() else {
+                continue;
+            };
+            forms
+                .standard
+                .extend(group[1..].iter().map(|&(variant, _)| (variant, standard)));
+            if let Some(&(hong_kong, _)) = group.iter().find(|(_, marked)| *marked) {
+                forms
+                    .hong_kong
+                    .extend(group.iter().map(|&(form, _)| (form, hong_kong)));
+            }
+        }
+        forms
+    })
+}
+
+/// `candidate` with every character in its standard form, the same for all
+/// variant spellings of a phrase.
+pub fn key(candidate: &str) -> String {
+    let standard = &forms().standard;
+    candidate
+        .chars()
+        .map(|ch| standard.get(&ch).copied().unwrap_or(ch))
+        .collect()
+}
+
+/// `text` in the forms of `region`. The editor's dictionaries already
+/// follow the Taiwanese standard.
+pub fn localize(text: &str, region: Region) -> String {
+    match region {
+        Region::Taiwan => text.to_string(),
+        Region::HongKong => {
+            let hong_kong = &forms().hong_kong;
+            text.chars()
+                .map(|ch| hong_kong.get(&ch).copied().unwrap_or(ch))
+                .collect()
+        }
+    }
+}
+
+/// Candidates as shown, given as indexes into the editor's list.
+#[derive(Debug, Default)]
+pub struct Arranged {
+    pub order: Vec<usize>,
+    /// Variants folded under each entry shown.
+    pub folded: Vec<usize>,
+}
+
+/// Lays out `candidates` per `display`, keeping the editor's order for the
+/// first spelling of each phrase and dropping repeats, which a region's
+/// forms can make of two variants. Variants of the phrases in `expanded`,
+/// by [`key`], are never folded.
+pub fn arrange(
+    candidates: &[String],
+    display: VariantDisplay,
+    expanded: &HashSet<String>,
+) -> Arranged {
+    let mut seen = HashSet::new();
+    let distinct = (0..candidates.len()).filter(|&index| seen.insert(&candidates[index]));
+    if display == VariantDisplay::Off {
+        return Arranged {
+            order: distinct.collect(),
+            folded: Vec::new(),
+        };
+    }
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for index in distinct {
+        let candidate = &candidates[index];
+        let key = key(candidate);
+        match by_key.get(&key) {
+            Some(&group) => groups[group].1.push(index),
+            None => {
+                by_key.insert(key.clone(), groups.len());
+                groups.push((key, vec![index]));
+            }
+        }
+    }
+    let mut arranged = Arranged::default();
+    for (key, group) in groups {
+        if display == VariantDisplay::Collapse && !expanded.contains(&key) {
+            arranged.order.push(group[0]);
+            arranged.folded.push(group.len() - 1);
+        } else {
+            arranged.folded.extend(group.iter().map(|_| 0));
+            arranged.order.extend(group);
+        }
+    }
+    arranged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(list: &[&str]) -> Vec<String> {
+        list.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn variant_spellings_share_a_key() {
+        assert_eq!(key("台灣"), key("臺灣"));
+        assert_ne!(key("台"), key("胎"));
+    }
+
+    #[test]
+    fn grouping_moves_variants_next_to_the_first() {
+        let list = candidates(&["台", "胎", "抬", "臺"]);
+        let arranged = arrange(&list, VariantDisplay::Group, &HashSet::new());
+        assert_eq!(arranged.order, [0, 3, 1, 2]);
+        assert_eq!(arranged.folded, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn collapsing_folds_variants_unless_expanded() {
+        let list = candidates(&["台", "胎", "臺"]);
+        let arranged = arrange(&list, VariantDisplay::Collapse, &HashSet::new());
+        assert_eq!(arranged.order, [0, 1]);
+        assert_eq!(arranged.folded, [1, 0]);
+        let expanded = HashSet::from([key("台")]);
+        let arranged = arrange(&list, VariantDisplay::Collapse, &expanded);
+        assert_eq!(arranged.order, [0, 2, 1]);
+    }
+
+    #[test]
+    fn repeats_are_dropped() {
+        let list = candidates(&["裏", "理", "裏"]);
+        let arranged = arrange(&list, VariantDisplay::Off, &HashSet::new());
+        assert_eq!(arranged.order, [0, 1]);
+    }
+
+    #[test]
+    fn hong_kong_forms_replace_the_taiwanese_ones() {
+        assert_eq!(localize("這裡說", Region::HongKong), "這裏説");
+        assert_eq!(localize("這裡說", Region::Taiwan), "這裡說");
+        assert_eq!(localize("艶", Region::HongKong), "艷");
+    }
+
+    #[test]
+    fn off_keeps_the_editor_order() {
+        let list = candidates(&["台", "胎", "臺"]);
+        let arranged = arrange(&list, VariantDisplay::Off, &HashSet::new());
+        assert_eq!(arranged.order, [0, 1, 2]);
+        assert!(arranged.folded.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+//! Detects clients that keep leaving commits without a Done event, which
+//! would otherwise freeze input in `WaitingForDone`. After a few misses the
+//! focused client is put in degraded mode: its commits are taken as done
+//! right away instead of waiting for an acknowledgement.
+//!
+//! A client that chokes on long preedits shows the same way, though only
+//! past some length. A few misses in a row on preedits longer than any the
+//! client has acknowledged limit its preedits instead, remembered in
+//! [`Quirks`] for a while. The protocol errors such a client may hit are
+//! between it and the compositor, so the missing Done is all there is to go
+//! by.
+use std::{collections::HashSet, time::Duration};
+
+use iced::Command;
+use log::{info, warn};
+
+use crate::{focus, quirks::Quirks, Message};
+
+/// How long a commit may go unacknowledged before it counts as a miss.
+const DONE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Misses in a row before the client is degraded.
+const MISSES: u32 = 3;
+/// Preedits shorter than this, in bytes, never count as too long for a
+/// client.
+const LONG_PREEDIT: usize = 48;
+
+#[derive(Default)]
+pub struct Watchdog {
+    generation: u64,
+    armed: bool,
+    misses: u32,
+    /// Misses in a row on preedits longer than any acknowledged.
+    long_misses: u32,
+    /// Identities of the clients known to drop Done.
+    degraded: HashSet<String>,
+    /// Whether the focused client is one of them.
+    degraded_focus: bool,
+    quirks: Quirks,
+    /// Bumped on every focus change, so a late lookup for an earlier client
+    /// is ignored.
+    focus_generation: u64,
+    /// The focused client, once the compositor has named it.
+    client: Option<String>,
+    /// Whether the compositor was asked to name it.
+    asked: bool,
+    /// Bytes of the preedit being waited on.
+    sent: usize,
+    /// The longest preedit the focused client acknowledged.
+    acknowledged: usize,
+    /// The longest preedit the focused client takes, if limited.
+    max_preedit: Option<usize>,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Watchdog {
+            quirks: Quirks::load(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether commits to the focused client are not waited on.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded_focus
+    }
+
+    /// A client took focus. The compositor is asked which one if
+    /// `identify` says its identity matters or something is known about
+    /// some client, and otherwise only once it misses a Done.
+    pub fn activate(&mut self, identify: bool) -> Command<Message> {
+        self.misses = 0;
+        self.long_misses = 0;
+        self.armed = false;
+        self.acknowledged = 0;
+        self.degraded_focus = false;
+        self.max_preedit = None;
+        self.client = None;
+        self.asked = false;
+        self.focus_generation += 1;
+        if identify || !self.degraded.is_empty() || self.quirks.len() > 0 {
+            self.identify()
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Asks the compositor to name the focused client, unless it was
+    /// already asked.
+    pub fn identify(&mut self) -> Command<Message> {
+        if self.asked {
+            return Command::none();
+        }
+        self.asked = true;
+        let generation = self.focus_generation;
+        Command::perform(focus::focused_client(), move |client| {
+            Message::FocusedClient(generation, client)
+        })
+    }
+
+    /// The focused client, if the compositor has named it.
+    pub fn client(&self) -> Option<&str> {
+        self.client.as_deref()
+    }
+
+    /// The compositor named the client that took focus in `generation`.
+    pub fn focused(&mut self, generation: u64, client: Option<String>) {
+        if generation != self.focus_generation {
+            return;
+        }
+        self.degraded_focus |= client
+            .as_ref()
+            .is_some_and(|client| self.degraded.contains(client));
+        if let Some(client) = &client {
+            // Named only after missing a Done, what was learned is its own
+            if self.degraded_focus {
+                self.degraded.insert(client.clone());
+            }
+            match (self.quirks.max_preedit(client), self.max_preedit) {
+                (Some(len), _) => self.max_preedit = Some(len),
+                (None, Some(len)) => self.quirks.limit_preedit(client.clone(), len),
+                (None, None) => {}
+            }
+        }
+        self.client = client;
+    }
+
+    /// The longest preedit in bytes the focused client takes, if limited.
+    pub fn max_preedit(&self) -> Option<usize> {
+        self.max_preedit
+    }
+
+    /// A commit was sent with a preedit of `preedit_len` bytes, starts
+    /// timing it unless one is already timed.
+    pub fn arm(&mut self, preedit_len: usize) -> Command<Message> {
+        self.sent = preedit_len;
+        if self.armed {
+            return Command::none();
+        }
+        self.armed = true;
+        self.generation += 1;
+        let generation = self.generation;
+        Command::perform(tokio::time::sleep(DONE_TIMEOUT), move |_| {
+            Message::DoneTimeout(generation)
+        })
+    }
+
+    pub fn done(&mut self) {
+        self.armed = false;
+        self.misses = 0;
+        self.long_misses = 0;
+        self.acknowledged = self.acknowledged.max(self.sent);
+    }
+
+    /// Forgets which clients drop Done and which take short preedits only,
+    /// for `chewingwlctl quirks clear`.
+    pub fn forget(&mut self) {
+        self.misses = 0;
+        self.long_misses = 0;
+        self.degraded.clear();
+        self.degraded_focus = false;
+        self.quirks.clear();
+        self.max_preedit = None;
+    }
+
+    /// The timer for `generation` ran out. Returns whether it was still
+    /// pending, in which case the commit should be treated as done.
+    pub fn timed_out(&mut self, generation: u64) -> bool {
+        if !self.armed || generation != self.generation {
+            return false;
+        }
+        self.armed = false;
+        let long = self.sent >= LONG_PREEDIT
+            && self.sent > self.acknowledged
+            && self.acknowledged > 0
+            && self.max_preedit.is_none();
+        if long {
+            self.long_misses += 1;
+            if self.long_misses < MISSES {
+                info!("no Done within {DONE_TIMEOUT:?} for a long preedit, continuing without it");
+                return true;
+            }
+            let client = self.client.clone();
+            warn!(
+                "{} acknowledged no preedit over {} bytes, committing in chunks for now",
+                client.as_deref().unwrap_or("the focused client"),
+                self.acknowledged,
+            );
+            self.max_preedit = Some(self.acknowledged);
+            if let Some(client) = client {
+                self.quirks.limit_preedit(client, self.acknowledged);
+            }
+            return true;
+        }
+        self.misses += 1;
+        if self.misses >= MISSES && !self.degraded_focus {
+            // Without an identity the client is only degraded until it loses focus
+            let client = self.client.clone();
+            warn!(
+                "{} did not send Done for {MISSES} commits in a row, \
+                 no longer waiting on it; please report this client as a quirk",
+                client.as_deref().unwrap_or("the focused client"),
+            );
+            self.degraded.extend(client);
+            self.degraded_focus = true;
+        } else {
+            info!("no Done within {DONE_TIMEOUT:?}, continuing without it");
+        }
+        true
+    }
+
+    /// Whether a Done is being waited on and how many were missed, for
+    /// `chewingwlctl dump-state`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}, {} missed in a row, {} degraded clients{}, {} with short preedits{}",
+            if self.armed { "waiting" } else { "idle" },
+            self.misses,
+            self.degraded.len(),
+            if self.degraded_focus {
+                ", focused one degraded"
+            } else {
+                ""
+            },
+            self.quirks.len(),
+            self.max_preedit
+                .map(|len| format!(", focused one up to {len} bytes"))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn miss(watchdog: &mut Watchdog, preedit_len: usize) -> bool {
+        let _ = watchdog.arm(preedit_len);
+        watchdog.timed_out(watchdog.generation)
+    }
+
+    fn focused(client: &str) -> Watchdog {
+        let mut watchdog = Watchdog::default();
+        let _ = watchdog.activate(true);
+        watchdog.focused(watchdog.focus_generation, Some(client.into()));
+        let _ = watchdog.arm(LONG_PREEDIT);
+        watchdog.done();
+        watchdog
+    }
+
+    #[test]
+    fn one_miss_does_not_limit_preedits() {
+        let mut watchdog = focused("foot");
+        assert!(miss(&mut watchdog, 2 * LONG_PREEDIT));
+        let _ = watchdog.arm(LONG_PREEDIT);
+        watchdog.done();
+        assert!(miss(&mut watchdog, 2 * LONG_PREEDIT));
+        assert_eq!(watchdog.max_preedit(), None);
+        assert!(!watchdog.is_degraded());
+    }
+
+    #[test]
+    fn misses_in_a_row_limit_preedits_until_forgotten() {
+        let mut watchdog = focused("foot");
+        for _ in 0..MISSES {
+            assert!(miss(&mut watchdog, 2 * LONG_PREEDIT));
+        }
+        assert_eq!(watchdog.max_preedit(), Some(LONG_PREEDIT));
+        assert_eq!(watchdog.quirks.max_preedit("foot"), Some(LONG_PREEDIT));
+        watchdog.forget();
+        assert_eq!(watchdog.max_preedit(), None);
+        assert_eq!(watchdog.quirks.max_preedit("foot"), None);
+    }
+
+    #[test]
+    fn forgetting_ends_degraded_mode() {
+        let mut watchdog = focused("foot");
+        for _ in 0..MISSES {
+            assert!(miss(&mut watchdog, 1));
+        }
+        assert!(watchdog.is_degraded());
+        watchdog.forget();
+        assert!(!watchdog.is_degraded());
+        let _ = watchdog.activate(false);
+        watchdog.focused(watchdog.focus_generation, Some("foot".into()));
+        assert!(!watchdog.is_degraded());
+    }
+
+    #[test]
+    fn a_client_is_named_once_it_misses_a_done() {
+        let mut watchdog = Watchdog::default();
+        let _ = watchdog.activate(false);
+        assert!(!watchdog.asked);
+        for _ in 0..MISSES {
+            assert!(miss(&mut watchdog, 1));
+            let _ = watchdog.identify();
+        }
+        assert!(watchdog.asked && watchdog.is_degraded());
+        // The compositor answers after the client was degraded
+        watchdog.focused(watchdog.focus_generation, Some("foot".into()));
+        let _ = watchdog.activate(false);
+        assert!(watchdog.asked);
+        watchdog.focused(watchdog.focus_generation, Some("foot".into()));
+        assert!(watchdog.is_degraded());
+    }
+}
@@ -0,0 +1,245 @@
+//! Setup shown on first launch, while there is no config file yet, so the
+//! basics can be chosen without writing TOML by hand.
+use std::fs;
+
+use iced::{
+    wayland::{
+        actions::layer_surface::SctkLayerSurfaceSettings,
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+        },
+    },
+    widget::{button, column, container, pick_list, row, text},
+    window, Alignment, Command, Element, Length,
+};
+use log::warn;
+
+use crate::{
+    config::{self, Config, Layout, PopupTheme, Surface},
+    layout, surface, user_data, CustomTheme, Message,
+};
+
+const WIDTH: u32 = 420;
+const HEIGHT: u32 = 500;
+
+const CANDIDATES: [usize; 6] = [5, 6, 7, 8, 9, 10];
+const TEXT_SIZES: [u16; 5] = [30, 40, 50, 60, 70];
+/// Mode toggle choices, the first one being the built-in Shift tap.
+const TOGGLE_KEYS: [&str; 4] = ["Shift", "Ctrl+Space", "RightAlt", "RightCtrl"];
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Layout(Layout),
+    Candidates(usize),
+    TextSize(u16),
+    Theme(PopupTheme),
+    ToggleKey(&'static str),
+    Save,
+    Skip,
+}
+
+pub struct Wizard {
+    id: window::Id,
+    layout: Layout,
+    candidates: usize,
+    text_size: u16,
+    theme: PopupTheme,
+    toggle_key: &'static str,
+    /// What the keys type in the chosen layout.
+    preview: Vec<Vec<(char, String)>>,
+}
+
+impl Wizard {
+    pub fn new(config: &Config) -> Self {
+        Wizard {
+            id: window::Id::unique(),
+            layout: config.keyboard.layout,
+            candidates: config.popup.candidates,
+            text_size: config.popup.font.size,
+            theme: config.popup.theme,
+            toggle_key: TOGGLE_KEYS[0],
+            preview: layout::preview(config.keyboard.layout),
+        }
+    }
+
+    pub fn id(&self) -> window::Id {
+        self.id
+    }
+
+    /// Whether this is the first launch, with no config file written yet.
+    pub fn is_due() -> bool {
+        config::path().is_some_and(|path| !path.exists())
+    }
+
+    pub fn open(&self, surface: Surface) -> Command<Message> {
+        get_layer_surface(surface::configured(
+            surface,
+            SctkLayerSurfaceSettings {
+                id: self.id,
+                layer: Layer::Top,
+                keyboard_interactivity: KeyboardInteractivity::None,
+                anchor: Anchor::empty(),
+                size: Some((Some(WIDTH), Some(HEIGHT))),
+                namespace: "chewingwl-setup".into(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Applies `event`. Saving writes the config file and returns the config
+    /// with the choices applied; saving and skipping both close the wizard.
+    pub fn update(&mut self, event: Event, config: &Config) -> (Command<Message>, Option<Config>) {
+        match event {
+            Event::Layout(layout) => {
+                self.layout = layout;
+                self.preview = layout::preview(layout);
+            }
+            Event::Candidates(candidates) => self.candidates = candidates,
+            Event::TextSize(size) => self.text_size = size,
+            Event::Theme(theme) => self.theme = theme,
+            Event::ToggleKey(toggle_key) => self.toggle_key = toggle_key,
+            Event::Save => {
+                let mut config = config.clone();
+                config.keyboard.layout = self.layout;
+                config.popup.candidates = self.candidates;
+                config.popup.font.size = self.text_size;
+                config.popup.font.label_size = self.text_size;
+                config.popup.theme = self.theme;
+                config.hotkeys.toggle_mode = self.toggle_mode().and_then(|key| key.parse().ok());
+                write(&self.contents());
+                return (destroy_layer_surface(self.id), Some(config));
+            }
+            Event::Skip => {
+                write("# Setup skipped, every option keeps its default.\n");
+                return (destroy_layer_surface(self.id), None);
+            }
+        }
+        (Command::none(), None)
+    }
+
+    fn toggle_mode(&self) -> Option<&'static str> {
+        Some(self.toggle_key).filter(|key| *key != TOGGLE_KEYS[0])
+    }
+
+    fn contents(&self) -> String {
+        let mut contents = format!(
+            "# Written by the first-run setup.\n\n\
+             [keyboard]\nlayout = \"{}\"\n\n\
+             [popup]\ncandidates = {}\ntheme = \"{}\"\n\n\
+             [popup.font]\nsize = {size}\nlabel_size = {size}\n",
+            self.layout.name(),
+            self.candidates,
+            self.theme.name(),
+            size = self.text_size,
+        );
+        if let Some(key) = self.toggle_mode() {
+            contents.push_str(&format!("\n[hotkeys]\ntoggle_mode = \"{key}\"\n"));
+        }
+        contents
+    }
+
+    /// A diagram of the keyboard labeled with what each key types.
+    fn keyboard(&self) -> Element<'static, Message> {
+        let rows = self.preview.iter().map(|keys| {
+            keys.iter()
+                .map(|(key, symbol)| {
+                    container(
+                        column(vec![
+                            text(key.to_ascii_uppercase()).size(9).into(),
+                            text(symbol).size(14).into(),
+                        ])
+                        .align_items(Alignment::Center),
+                    )
+                    .width(30.0)
+                    .padding(2.0)
+                    .style(iced::theme::Container::Box)
+                    .into()
+                })
+                .collect::<Vec<_>>()
+        });
+        column(
+            rows.map(|keys| row(keys).spacing(2.0).into())
+                .collect::<Vec<_>>(),
+        )
+        .spacing(2.0)
+        .into()
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        container(
+            column(vec![
+                text("Welcome to chewingwl").size(20).into(),
+                choice(
+                    "Keyboard layout",
+                    pick_list(Layout::ALL, Some(self.layout), |layout| {
+                        Message::Wizard(Event::Layout(layout))
+                    })
+                    .into(),
+                ),
+                self.keyboard(),
+                choice(
+                    "Candidates per page",
+                    pick_list(&CANDIDATES[..], Some(self.candidates), |candidates| {
+                        Message::Wizard(Event::Candidates(candidates))
+                    })
+                    .into(),
+                ),
+                choice(
+                    "Text size",
+                    pick_list(&TEXT_SIZES[..], Some(self.text_size), |size| {
+                        Message::Wizard(Event::TextSize(size))
+                    })
+                    .into(),
+                ),
+                choice(
+                    "Color theme",
+                    pick_list(PopupTheme::ALL, Some(self.theme), |theme| {
+                        Message::Wizard(Event::Theme(theme))
+                    })
+                    .into(),
+                ),
+                choice(
+                    "Chinese/English toggle",
+                    pick_list(&TOGGLE_KEYS[..], Some(self.toggle_key), |key| {
+                        Message::Wizard(Event::ToggleKey(key))
+                    })
+                    .into(),
+                ),
+                row(vec![
+                    button("Skip").on_press(Message::Wizard(Event::Skip)).into(),
+                    button("Save").on_press(Message::Wizard(Event::Save)).into(),
+                ])
+                .spacing(8.0)
+                .into(),
+            ])
+            .spacing(12.0)
+            .align_items(Alignment::End),
+        )
+        .padding(16.0)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(<iced_style::Theme as container::StyleSheet>::Style::Custom(
+            Box::new(CustomTheme::default()),
+        ))
+        .into()
+    }
+}
+
+fn choice(label: &str, picker: Element<'static, Message>) -> Element<'static, Message> {
+    row(vec![text(label).width(Length::Fill).into(), picker])
+        .align_items(Alignment::Center)
+        .into()
+}
+
+fn write(contents: &str) {
+    let Some(path) = config::path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| user_data::write_atomic(&path, contents.as_bytes()));
+    if let Err(error) = result {
+        warn!("cannot write {}: {error}", path.display());
+    }
+}